@@ -0,0 +1,39 @@
+//! Embeds what `--version` reports about the build: the git commit, the build time and the
+//! target triple.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .current_dir(&manifest_dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| "unknown".into());
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let timestamp = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()));
+
+    println!("cargo:rustc-env=RUNNER_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=RUNNER_BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rustc-env=RUNNER_TARGET={}", env::var("TARGET").unwrap_or_default());
+
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=build.rs");
+    let git_dir = Path::new(&manifest_dir).join(".git");
+    for path in ["HEAD", "refs", "packed-refs"] {
+        if git_dir.join(path).exists() {
+            println!("cargo:rerun-if-changed={}", git_dir.join(path).display());
+        }
+    }
+}
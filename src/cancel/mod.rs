@@ -0,0 +1,116 @@
+//! Cancellation shared by every part of a run. Cancelling stops the requests and the download,
+//! the extraction and the wait before the launch, whether the user, the window or the OS asked.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+/// Cheap to clone; all clones share one state.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// `Error::Cancelled` once cancelled, for work done in steps.
+    pub fn check(&self) -> crate::Result<()> {
+        if self.is_cancelled() {
+            return Err(cancelled_error());
+        }
+        Ok(())
+    }
+
+    /// Completes once cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            // Registered before the check, so a cancel in between isn't missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Runs `future` unless cancelled first, in which case it's dropped.
+    pub async fn run<F: Future>(&self, future: F) -> crate::Result<F::Output> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(cancelled_error()),
+            output = future => Ok(output),
+        }
+    }
+}
+
+fn cancelled_error() -> crate::Error {
+    crate::Error::Cancelled("Update cancelled".into())
+}
+
+/// Cancels `token` when the process is asked to stop: Ctrl+C, or SIGTERM on Unix.
+pub async fn cancel_on_shutdown(token: CancellationToken) {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            },
+            Err(e) => {
+                log::debug!("Can't listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            },
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            if let Err(e) = result {
+                log::debug!("Can't listen for Ctrl+C: {}", e);
+                return;
+            }
+        },
+        _ = terminate => {},
+    }
+    log::info!("Shutdown requested, cancelling the update");
+    token.cancel();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_stops_waiting_work() {
+        let token = CancellationToken::default();
+        assert!(token.check().is_ok());
+        assert_eq!(token.run(async { 1 }).await.unwrap(), 1);
+
+        let shared = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            shared.cancel();
+        });
+        let result = token.run(std::future::pending::<()>()).await;
+        assert!(matches!(result, Err(crate::Error::Cancelled(_))));
+        assert!(token.check().is_err());
+    }
+}
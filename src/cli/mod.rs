@@ -0,0 +1,326 @@
+use crate::deeplink;
+use crate::format::format_timestamp;
+use crate::manifest::MANIFEST_VERSION;
+use crate::runner::CAPABILITIES;
+use serde::Serialize;
+use std::fmt;
+
+const MIN_SCALE: f32 = 0.5;
+const MAX_SCALE: f32 = 3.0;
+
+/// Flag that verifies the installed files and reinstalls the launcher.
+pub const REPAIR_FLAG: &str = "--repair";
+
+/// Flag that downloads and installs the latest version anew.
+pub const FORCE_UPDATE_FLAG: &str = "--force-update";
+
+/// Flag that removes the installed app, run from its entry in Windows' installed apps list.
+pub const UNINSTALL_FLAG: &str = "--uninstall";
+
+/// Environment variable setting the log level when `--log-level` isn't given.
+pub const LOG_LEVEL_ENV: &str = "PK_RUNNER_LOG";
+
+/// Environment variable setting the level of the log output on stderr.
+pub const STDERR_LOG_LEVEL_ENV: &str = "PK_RUNNER_STDERR_LOG";
+
+/// Verbosity of a log output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+}
+
+impl LogLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" => Some(Self::Warn),
+            _ => None,
+        }
+    }
+
+    /// Level from the command line, falling back to the environment variable.
+    pub fn select(flag: Option<Self>, env_value: Option<&str>) -> Option<Self> {
+        flag.or_else(|| env_value.and_then(Self::parse))
+    }
+
+    /// Logger filter for this level. At debug the HTTP client's request traces are included too.
+    pub fn filter(self) -> &'static str {
+        match self {
+            Self::Debug => "debug,reqwest=trace,hyper=debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+        }
+    }
+}
+
+/// Command line arguments understood by the runner.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Args {
+    /// Run the whole update without opening a window, reporting progress only to the log.
+    pub silent: bool,
+    /// Show progress in the terminal instead of opening a window.
+    pub no_gui: bool,
+    /// Only report whether an update is available, as JSON on stdout.
+    pub check_update: bool,
+    /// Only print the version and build information.
+    pub version: bool,
+    /// Print `--version` as JSON.
+    pub json: bool,
+    /// Only write a support bundle and print its path.
+    pub diagnose: bool,
+    /// Only check the conditions an update needs and print a pass/fail table.
+    pub self_test: bool,
+    /// Re-verify the installed launcher and reinstall it even if it's up to date.
+    pub repair: bool,
+    /// Download and install the latest version even if it's already installed.
+    pub force_update: bool,
+    /// Only remove the installed app, see [`crate::uninstall`].
+    pub uninstall: bool,
+    /// Stay in the background and download updates as they come, see [`crate::daemon`].
+    pub daemon: bool,
+    /// Render the window without GPU acceleration, for broken or very old graphics drivers.
+    pub software_render: bool,
+    /// Log level override, see [`LogLevel::select`].
+    pub log_level: Option<LogLevel>,
+    /// UI scale override applied on top of the OS scale factor.
+    pub scale: Option<f32>,
+    /// App to run when the runner ships several, see [`crate::config::apps`].
+    pub app: Option<String>,
+    /// Version ID to install instead of the latest one.
+    pub pin_version: Option<String>,
+    /// Deep link the runner was opened with, see [`crate::deeplink`].
+    pub deep_link: Option<String>,
+    /// Arguments the runner doesn't recognize. They are reported once logging is set up.
+    pub unknown: Vec<String>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().map(Into::into);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--silent" => parsed.silent = true,
+                "--no-gui" => parsed.no_gui = true,
+                REPAIR_FLAG => parsed.repair = true,
+                FORCE_UPDATE_FLAG => parsed.force_update = true,
+                UNINSTALL_FLAG => parsed.uninstall = true,
+                "--daemon" => parsed.daemon = true,
+                "--check-update" => parsed.check_update = true,
+                "--version" | "-V" => parsed.version = true,
+                "--json" => parsed.json = true,
+                "--diagnose" => parsed.diagnose = true,
+                "--self-test" => parsed.self_test = true,
+                "--software-render" => parsed.software_render = true,
+                "--log-level" => match args.next().as_deref().and_then(LogLevel::parse) {
+                    Some(level) => parsed.log_level = Some(level),
+                    None => parsed.unknown.push(arg),
+                },
+                "--app" => match args.next().filter(|id| !id.starts_with('-')) {
+                    Some(id) => parsed.app = Some(id),
+                    None => parsed.unknown.push(arg),
+                },
+                "--pin-version" => match args.next().filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())) {
+                    Some(id) => parsed.pin_version = Some(id),
+                    None => parsed.unknown.push(arg),
+                },
+                "--scale" => match args.next().and_then(|value| value.parse::<f32>().ok()) {
+                    Some(scale) if scale > 0.0 => parsed.scale = Some(scale.clamp(MIN_SCALE, MAX_SCALE)),
+                    _ => parsed.unknown.push(arg),
+                },
+                _ if deeplink::is_link(&arg) => parsed.deep_link = Some(arg),
+                _ => parsed.unknown.push(arg),
+            }
+        }
+
+        parsed
+    }
+}
+
+/// Result printed by `--check-update`.
+#[derive(Debug, Serialize)]
+pub struct UpdateCheck {
+    pub update_available: bool,
+    pub installed_version: Option<String>,
+    pub latest_version: String,
+}
+
+/// What `--version` prints.
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub commit: &'static str,
+    /// When the runner was built, in RFC 3339.
+    pub build_date: String,
+    pub target: &'static str,
+    pub manifest_version: i32,
+    pub capabilities: &'static [&'static str],
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        let timestamp = env!("RUNNER_BUILD_TIMESTAMP").parse().unwrap_or_default();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: env!("RUNNER_GIT_COMMIT"),
+            build_date: format_timestamp(timestamp),
+            target: env!("RUNNER_TARGET"),
+            manifest_version: MANIFEST_VERSION,
+            capabilities: CAPABILITIES,
+        }
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "runner2 {} ({})", self.version, self.commit)?;
+        writeln!(f, "Built {} for {}", self.build_date, self.target)?;
+        writeln!(f, "Manifest version: {}", self.manifest_version)?;
+        write!(f, "Capabilities: {}", self.capabilities.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_defaults() {
+        let args = Args::parse_from(Vec::<String>::new());
+        assert_eq!(args, Args::default());
+    }
+
+    #[test]
+    fn test_parse_silent() {
+        let args = Args::parse_from(["--silent", "--bogus"]);
+        assert!(args.silent);
+        assert_eq!(args.unknown, vec!["--bogus".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_scale() {
+        assert_eq!(Args::parse_from(["--scale", "1.5"]).scale, Some(1.5));
+        assert_eq!(Args::parse_from(["--scale", "10"]).scale, Some(MAX_SCALE));
+
+        let args = Args::parse_from(["--scale", "big"]);
+        assert_eq!(args.scale, None);
+        assert_eq!(args.unknown, vec!["--scale".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_repair() {
+        let args = Args::parse_from(["--repair", "--silent"]);
+        assert!(args.repair);
+        assert!(args.silent);
+        assert!(args.unknown.is_empty());
+        assert!(!args.force_update);
+        assert!(Args::parse_from(["--force-update"]).force_update);
+    }
+
+    #[test]
+    fn test_build_info() {
+        let args = Args::parse_from(["--version", "--json"]);
+        assert!(args.version && args.json);
+        assert!(Args::parse_from(["-V"]).version);
+
+        let info = BuildInfo::current();
+        assert!(info.to_string().starts_with(&format!("runner2 {} (", env!("CARGO_PKG_VERSION"))));
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["manifest_version"], MANIFEST_VERSION);
+        assert!(json["build_date"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn test_parse_software_render() {
+        assert!(Args::parse_from(["--diagnose"]).diagnose);
+        assert!(Args::parse_from(["--software-render"]).software_render);
+        assert!(!Args::parse_from(["--silent"]).software_render);
+    }
+
+    #[test]
+    fn test_log_level() {
+        let args = Args::parse_from(["--log-level", "DEBUG"]);
+        assert_eq!(args.log_level, Some(LogLevel::Debug));
+        assert_eq!(Args::parse_from(["--log-level", "loud"]).unknown, vec!["--log-level"]);
+
+        assert_eq!(LogLevel::select(Some(LogLevel::Warn), Some("debug")), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::select(None, Some("debug")), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::select(None, Some("verbose")), None);
+        assert_eq!(LogLevel::select(None, None), None);
+    }
+
+    #[test]
+    fn test_update_check_json() {
+        let check = UpdateCheck {
+            update_available: true,
+            installed_version: None,
+            latest_version: "42".into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&check).unwrap(),
+            r#"{"update_available":true,"installed_version":null,"latest_version":"42"}"#
+        );
+        assert!(Args::parse_from(["--check-update"]).check_update);
+    }
+
+    #[test]
+    fn test_parse_self_test() {
+        let args = Args::parse_from(["--self-test"]);
+        assert!(args.self_test);
+        assert!(args.unknown.is_empty());
+    }
+
+    #[test]
+    fn test_parse_uninstall() {
+        let args = Args::parse_from(["--uninstall"]);
+        assert!(args.uninstall);
+        assert!(args.unknown.is_empty());
+    }
+
+    #[test]
+    fn test_parse_daemon() {
+        let args = Args::parse_from(["--daemon", "--app", "racer"]);
+        assert!(args.daemon);
+        assert_eq!(args.app.as_deref(), Some("racer"));
+        assert!(args.unknown.is_empty());
+    }
+
+    #[test]
+    fn test_parse_app() {
+        assert_eq!(Args::parse_from(["--app", "racer"]).app.as_deref(), Some("racer"));
+        assert_eq!(Args::parse_from(["--app", "--silent"]).unknown, vec!["--app"]);
+    }
+
+    #[test]
+    fn test_parse_pin_version() {
+        assert_eq!(Args::parse_from(["--pin-version", "41"]).pin_version.as_deref(), Some("41"));
+        assert_eq!(Args::parse_from(["--pin-version", "latest"]).unknown, vec!["--pin-version"]);
+    }
+
+    #[test]
+    fn test_parse_deep_link() {
+        let args = Args::parse_from(["mygame://join?invite=AB12CD", "--silent"]);
+        assert_eq!(args.deep_link.as_deref(), Some("mygame://join?invite=AB12CD"));
+        assert!(args.silent);
+        assert!(args.unknown.is_empty());
+    }
+
+    #[test]
+    fn test_parse_no_gui() {
+        let args = Args::parse_from(["--no-gui"]);
+        assert!(args.no_gui);
+        assert!(!args.silent);
+    }
+}
@@ -0,0 +1,82 @@
+//! Multi-app mode: one runner shipping several apps, each with its own dat in the `apps`
+//! directory. The apps share the runner's data root, each installed in a directory named after
+//! its id, the dat's file name without the extension.
+
+use super::LauncherData;
+use log::{debug, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const APPS_DIR_NAME: &str = "apps";
+
+/// An app the runner can install.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppEntry {
+    pub id: String,
+    /// The app's display name from its dat, or its id.
+    pub name: String,
+    pub dat_path: PathBuf,
+}
+
+/// Apps with a readable dat in `dir`, sorted by id. Unreadable dats are skipped with a warning.
+pub fn discover(dir: &Path) -> Vec<AppEntry> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        debug!("No apps directory at {}", dir.display());
+        return Vec::new();
+    };
+
+    let mut apps: Vec<AppEntry> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "dat"))
+        .filter_map(|dat_path| {
+            let id = dat_path.file_stem()?.to_string_lossy().into_owned();
+            match LauncherData::load(&dat_path) {
+                Ok(launcher_data) => Some(AppEntry {
+                    name: launcher_data.app_display_name.unwrap_or_else(|| id.clone()),
+                    id,
+                    dat_path,
+                }),
+                Err(e) => {
+                    warn!("Skipping app {}: {}", id, e);
+                    None
+                },
+            }
+        })
+        .collect();
+    apps.sort_by(|a, b| a.id.cmp(&b.id));
+    apps
+}
+
+/// The app with `id`, ignoring case.
+pub fn find<'a>(apps: &'a [AppEntry], id: &str) -> Option<&'a AppEntry> {
+    apps.iter().find(|app| app.id.eq_ignore_ascii_case(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::json_dat;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_discover() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("racer.dat"),
+            json_dat(r#"{"patcher_secret": "p1", "app_secret": "racersecret", "app_display_name": "Racer"}"#),
+        )
+        .unwrap();
+        fs::write(dir.path().join("puzzle.dat"), json_dat(r#"{"patcher_secret": "p2", "app_secret": "puzzlesecret"}"#))
+            .unwrap();
+        fs::write(dir.path().join("broken.dat"), "not a dat").unwrap();
+        fs::write(dir.path().join("readme.txt"), "").unwrap();
+
+        let apps = discover(dir.path());
+        let names: Vec<_> = apps.iter().map(|app| (app.id.as_str(), app.name.as_str())).collect();
+        assert_eq!(names, [("puzzle", "puzzle"), ("racer", "Racer")]);
+        assert_eq!(find(&apps, "RACER").map(|app| &app.dat_path), Some(&dir.path().join("racer.dat")));
+        assert!(find(&apps, "shooter").is_none());
+        assert!(discover(&dir.path().join("missing")).is_empty());
+    }
+}
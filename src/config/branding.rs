@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    #[default]
+    Dark,
+    Light,
+    /// Follow the operating system's light/dark preference.
+    Auto,
+}
+
+/// Look-and-feel overrides that can be shipped in the JSON dat file.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Branding {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<ThemeMode>,
+    /// Logical window size. The window still grows to fit long content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_width: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_height: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_changelog: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_news: Option<bool>,
+    /// Translated display strings, overriding the runner's own.
+    #[serde(default, skip_serializing_if = "LocalizedStrings::is_empty")]
+    pub strings: LocalizedStrings,
+}
+
+/// Display strings by language code and key, e.g. `{"de": {"app_display_name": "Rennspiel"}}`.
+/// Keys are `app_display_name`, an error code like `PKR-1101` for the explanation of the error,
+/// or the English text of a status message, e.g. `Downloading launcher...`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct LocalizedStrings(HashMap<String, HashMap<String, String>>);
+
+impl LocalizedStrings {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The string for `key` in `language`, or in its base language, e.g. `pt` for `pt-BR`.
+    pub fn get(&self, language: Option<&str>, key: &str) -> Option<&str> {
+        let language = language?;
+        let base = language.split(['-', '_']).next().unwrap_or(language);
+        [language, base]
+            .into_iter()
+            .find_map(|language| self.0.get(language)?.get(key))
+            .map(String::as_str)
+    }
+
+    /// `text` in `language`, or `text` itself if there's no translation of it.
+    pub fn translate<'a>(&'a self, language: Option<&str>, text: &'a str) -> &'a str {
+        self.get(language, text).unwrap_or(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branding_parsing() {
+        let branding: Branding = serde_json::from_str(r#"{"theme": "auto"}"#).unwrap();
+        assert_eq!(branding.theme, Some(ThemeMode::Auto));
+
+        let branding: Branding = serde_json::from_str("{}").unwrap();
+        assert_eq!(branding.theme, None);
+        assert_eq!(branding.theme.unwrap_or_default(), ThemeMode::Dark);
+    }
+
+    #[test]
+    fn test_localized_strings() {
+        let branding: Branding = serde_json::from_str(
+            r#"{"strings": {"de": {"app_display_name": "Rennspiel", "Downloading launcher...": "Lade den Launcher..."}}}"#,
+        )
+        .unwrap();
+        let strings = &branding.strings;
+        assert_eq!(strings.get(Some("de"), "app_display_name"), Some("Rennspiel"));
+        assert_eq!(strings.get(Some("de-AT"), "app_display_name"), Some("Rennspiel"));
+        assert_eq!(strings.get(Some("fr"), "app_display_name"), None);
+        assert_eq!(strings.get(None, "app_display_name"), None);
+        assert_eq!(strings.translate(Some("de"), "Downloading launcher..."), "Lade den Launcher...");
+        assert_eq!(strings.translate(Some("de"), "Extracting launcher..."), "Extracting launcher...");
+    }
+}
@@ -1,11 +1,27 @@
+use crate::network::pinning::CertificatePin;
 use crate::Result;
 use byteorder::{LittleEndian, ReadBytesExt};
 use serde::{Deserialize, Serialize};
-use std::io::{Read, Seek};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
 use log::{debug, error};
 
+pub mod apps;
+pub mod branding;
+pub mod paths;
 pub mod secret;
+pub mod settings;
+pub mod telemetry;
+pub mod update_policy;
 
+pub use branding::{Branding, LocalizedStrings, ThemeMode};
+pub use settings::Settings;
+pub use telemetry::TelemetryConfig;
+pub use update_policy::{UpdateMode, UpdatePolicy};
+
+pub const DAT_FILE_NAME: &str = "launcher.dat";
 const MAGIC_BYTES: [u8; 4] = [46, 98, 76, 97]; // ".bLa"
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -18,9 +34,59 @@ pub struct LauncherData {
     pub app_author: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub app_identifier: Option<String>,
+    #[serde(default)]
+    pub branding: Branding,
+    #[serde(default)]
+    pub update_policy: UpdatePolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<TelemetryConfig>,
+    /// Turns off the anonymous usage analytics sent to PatchKit.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_analytics: bool,
+    /// Adds the app to Windows' installed apps list on its first install.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub register_uninstall: bool,
+    /// URL scheme opening the runner, e.g. `mygame` for `mygame://join?invite=AB12CD` links.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_scheme: Option<String>,
+    /// Key the password of encrypted packages is derived from, see [`Self::package_password`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_key: Option<String>,
+    /// Signs API requests with a key derived from the app secret, for backends that require it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sign_requests: bool,
+    /// Delivers versions as loose files instead of packages, downloading only the changed ones.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub loose_files: bool,
+    /// Keys the PatchKit API server must present, see [`crate::network::pinning`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_certificate_pins: Vec<CertificatePin>,
+    /// Requires the API to sign the version and content responses, see
+    /// [`crate::network::verification`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub verify_responses: bool,
 }
 
 impl LauncherData {
+    /// Reads a dat file, detecting whether it was saved in the JSON or the binary format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path).map_err(|e| {
+            crate::Error::DatFile(format!("Failed to open {}: {}", path.display(), e))
+        })?;
+
+        let mut magic = [0u8; 4];
+        let is_json = file.read_exact(&mut magic).is_ok() && magic == MAGIC_BYTES;
+        file.seek(SeekFrom::Start(0))?;
+
+        if is_json {
+            debug!("Reading JSON DAT file");
+            Self::from_json(file)
+        } else {
+            Self::from_binary(file)
+        }
+    }
+
     pub fn from_binary<R: Read + Seek>(mut reader: R) -> Result<Self> {
         debug!("Reading binary DAT file");
         let patcher_secret = read_encoded_string(&mut reader)?;
@@ -34,9 +100,28 @@ impl LauncherData {
             app_display_name: None,
             app_author: None,
             app_identifier: None,
+            branding: Branding::default(),
+            update_policy: UpdatePolicy::default(),
+            telemetry: None,
+            disable_analytics: false,
+            register_uninstall: false,
+            url_scheme: None,
+            package_key: None,
+            sign_requests: false,
+            loose_files: false,
+            api_certificate_pins: Vec::new(),
+            verify_responses: false,
         })
     }
 
+    /// Password of the app's encrypted packages: the hex SHA-256 of `<app_secret>:<package_key>`,
+    /// so the key alone doesn't open the packages of another app.
+    pub fn package_password(&self) -> Option<String> {
+        let key = self.package_key.as_ref()?;
+        let digest = Sha256::digest(format!("{}:{}", self.app_secret, key));
+        Some(format!("{:x}", digest))
+    }
+
     pub fn from_json<R: Read + Seek>(mut reader: R) -> Result<Self> {
         let mut magic = [0u8; 4];
         reader.read_exact(&mut magic)?;
@@ -84,7 +169,7 @@ fn decode_byte_array(encoded_bytes: &[u8]) -> Vec<u8> {
             let lsb = b & 1;
             // Shift right by 1 and restore original MSB
             let mut decoded = b >> 1;
-            decoded = decoded | (lsb << 7);
+            decoded |= lsb << 7;
             // Invert all bits to complete the decoding
             decoded = !decoded;
             
@@ -101,6 +186,19 @@ fn decode_byte_array(encoded_bytes: &[u8]) -> Vec<u8> {
     result
 }
 
+/// A dat in the JSON format holding `json`, for tests.
+#[cfg(test)]
+pub(crate) fn json_dat(json: &str) -> Vec<u8> {
+    let mut dat = MAGIC_BYTES.to_vec();
+    dat.extend_from_slice(&((json.len() * 2) as u32).to_le_bytes());
+    for byte in json.bytes() {
+        // Each byte is rotated left, inverted and followed by a zero
+        dat.push(!byte.rotate_left(1));
+        dat.push(0);
+    }
+    dat
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,4 +246,55 @@ mod tests {
         let result = read_encoded_string(cursor).unwrap();
         assert_eq!(result, "test");
     }
+
+    #[test]
+    fn test_package_password() {
+        let launcher_data: LauncherData = serde_json::from_str(
+            r#"{"patcher_secret": "patchersecret", "app_secret": "appsecret", "package_key": "key"}"#,
+        ).unwrap();
+        assert_eq!(
+            launcher_data.package_password().as_deref(),
+            Some("8a6cee94908eb434255cb7f931b95f312da6156f7694fe87cbe4b2dd6ed9e156")
+        );
+        let launcher_data: LauncherData = serde_json::from_str(r#"{"patcher_secret": "a", "app_secret": "b"}"#).unwrap();
+        assert_eq!(launcher_data.package_password(), None);
+    }
+
+    fn encode_string(value: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&((value.len() * 2) as u32).to_le_bytes());
+        for &b in value.as_bytes() {
+            data.push(encode_byte(b));
+            data.push(0);
+        }
+        data
+    }
+
+    #[test]
+    fn test_load_detects_format() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let binary_path = dir.path().join("binary.dat");
+        let mut binary = encode_string("patcher_secret");
+        binary.extend(encode_string("app_secret"));
+        std::fs::write(&binary_path, binary).unwrap();
+
+        let data = LauncherData::load(&binary_path).unwrap();
+        assert_eq!(data.patcher_secret, "patcher_secret");
+        assert_eq!(data.app_secret, "app_secret");
+        assert!(data.branding.theme.is_none());
+
+        let json_path = dir.path().join("json.dat");
+        let mut json = MAGIC_BYTES.to_vec();
+        json.extend(encode_string(
+            r#"{"patcher_secret": "p", "app_secret": "a", "branding": {"theme": "light"}}"#,
+        ));
+        std::fs::write(&json_path, json).unwrap();
+
+        let data = LauncherData::load(&json_path).unwrap();
+        assert_eq!(data.patcher_secret, "p");
+        assert_eq!(data.branding.theme, Some(ThemeMode::Light));
+
+        assert!(LauncherData::load(dir.path().join("missing.dat")).is_err());
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,324 @@
+//! Where the runner's files are on each platform. On Windows and Linux everything is next to the
+//! runner. On macOS the runner is usually inside an app bundle, which is read-only once signed:
+//! the dat is one of its resources, logs go to `~/Library/Logs/PatchKit` and the rest to
+//! `~/Library/Application Support/PatchKit/Apps`. Apps installed for the current user instead,
+//! when next to the runner can't be written, keep everything in the user's local data directory.
+
+use super::apps::APPS_DIR_NAME;
+use super::DAT_FILE_NAME;
+use crate::Result;
+use directories::BaseDirs;
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory the runner executable is in.
+pub fn exe_dir() -> Result<PathBuf> {
+    let exe_path = std::env::current_exe()?;
+    exe_path
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| crate::Error::FileSystem("Failed to get parent directory of the current executable".into()))
+}
+
+/// The `.app` bundle the runner is in, if any.
+pub fn bundle_dir() -> Option<PathBuf> {
+    bundle_of(&std::env::current_exe().ok()?)
+}
+
+/// The bundle of an executable at `<name>.app/Contents/MacOS/<exe>`.
+fn bundle_of(exe: &Path) -> Option<PathBuf> {
+    let macos_dir = exe.parent()?;
+    let contents_dir = macos_dir.parent()?;
+    let bundle = contents_dir.parent()?;
+    let is_bundle = macos_dir.file_name()? == "MacOS"
+        && contents_dir.file_name()? == "Contents"
+        && bundle.extension().is_some_and(|extension| extension == "app");
+    is_bundle.then(|| bundle.to_path_buf())
+}
+
+/// The bundle's resources, with the dat and the apps directory.
+fn resources_dir() -> Option<PathBuf> {
+    bundle_dir().map(|bundle| bundle.join("Contents").join("Resources"))
+}
+
+/// The app's dat file.
+pub fn dat_path() -> PathBuf {
+    resources_dir().map_or_else(|| PathBuf::from(DAT_FILE_NAME), |resources| resources.join(DAT_FILE_NAME))
+}
+
+/// Where the dats of a multi-app runner are.
+pub fn apps_dir() -> PathBuf {
+    resources_dir().map_or_else(|| PathBuf::from(APPS_DIR_NAME), |resources| resources.join(APPS_DIR_NAME))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Windows,
+    MacOs,
+    /// Linux and the other Unix-likes.
+    Linux,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Linux
+        }
+    }
+}
+
+/// Marks an app installed for the current user, in its directory in the user's data directory.
+pub const INSTALL_SCOPE_FILE_NAME: &str = "installed-for-user";
+
+/// Whose directories an app is installed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallScope {
+    /// Next to the runner, or in Application Support on macOS.
+    Portable,
+    /// The current user's local data directory, which the user can always write to.
+    User,
+}
+
+/// The user's directories, unknown without a home directory.
+#[derive(Debug, Clone)]
+struct UserDirs {
+    home: PathBuf,
+    /// Roaming on Windows, Application Support on macOS.
+    data: PathBuf,
+    data_local: PathBuf,
+}
+
+/// Directories of the runner and its apps on one platform.
+#[derive(Debug, Clone)]
+pub struct PathProvider {
+    platform: Platform,
+    exe_dir: PathBuf,
+    user: Option<UserDirs>,
+}
+
+impl PathProvider {
+    /// Paths of the running runner on this platform.
+    pub fn current() -> Result<Self> {
+        let user = BaseDirs::new().map(|base_dirs| UserDirs {
+            home: base_dirs.home_dir().to_path_buf(),
+            data: base_dirs.data_dir().to_path_buf(),
+            data_local: base_dirs.data_local_dir().to_path_buf(),
+        });
+        Ok(Self { platform: Platform::current(), exe_dir: exe_dir()?, user })
+    }
+
+    fn user(&self) -> Result<&UserDirs> {
+        self.user
+            .as_ref()
+            .ok_or_else(|| crate::Error::FileSystem("Could not determine base directories".into()))
+    }
+
+    /// `PatchKit/Apps` in the user's data directory, holding a directory per app.
+    fn user_apps_dir(&self, scope: InstallScope) -> Result<PathBuf> {
+        let user = self.user()?;
+        let base = match scope {
+            InstallScope::Portable => &user.data,
+            InstallScope::User => &user.data_local,
+        };
+        Ok(base.join("PatchKit").join("Apps"))
+    }
+
+    /// Whether the app's files are in the user's data directory rather than next to the runner.
+    fn in_user_dirs(&self, scope: InstallScope) -> bool {
+        scope == InstallScope::User || self.platform == Platform::MacOs
+    }
+
+    /// The scope the app was installed with: the user scope once it had to move to the user's
+    /// directory, so it stays there even if the runner's directory becomes writable.
+    pub fn install_scope(&self, app_slug: &str) -> InstallScope {
+        match self.scope_marker(app_slug) {
+            Ok(marker) if marker.exists() => InstallScope::User,
+            _ => InstallScope::Portable,
+        }
+    }
+
+    /// Remembers the scope the app is installed with for the next runs.
+    pub fn record_install_scope(&self, app_slug: &str, scope: InstallScope) -> Result<()> {
+        let marker = self.scope_marker(app_slug)?;
+        match scope {
+            InstallScope::User => {
+                if let Some(parent) = marker.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&marker, "")?;
+            },
+            InstallScope::Portable if marker.exists() => fs::remove_file(&marker)?,
+            InstallScope::Portable => {},
+        }
+        Ok(())
+    }
+
+    fn scope_marker(&self, app_slug: &str) -> Result<PathBuf> {
+        Ok(self.user_apps_dir(InstallScope::User)?.join(app_slug).join(INSTALL_SCOPE_FILE_NAME))
+    }
+
+    /// Directory the app is installed to.
+    pub fn data_dir(&self, scope: InstallScope, app_slug: &str) -> Result<PathBuf> {
+        if self.in_user_dirs(scope) {
+            Ok(self.user_apps_dir(scope)?.join(app_slug).join("Data"))
+        } else {
+            Ok(self.exe_dir.join("app"))
+        }
+    }
+
+    /// Directory of the launcher, its downloads and the installed version.
+    pub fn patcher_dir(&self, scope: InstallScope, app_slug: &str) -> Result<PathBuf> {
+        if self.in_user_dirs(scope) {
+            Ok(self.user_apps_dir(scope)?.join(app_slug).join("Patcher"))
+        } else {
+            Ok(self.exe_dir.join("Patcher"))
+        }
+    }
+
+    /// What "Delete cache" removes: the launcher and its downloads, which are downloaded again
+    /// on the next start. The installed app is kept.
+    pub fn cache_dir(&self, scope: InstallScope, app_slug: &str) -> Result<PathBuf> {
+        self.patcher_dir(scope, app_slug)
+    }
+
+    /// Directory of the settings, shared by all apps of the runner.
+    pub fn config_dir(&self) -> Result<PathBuf> {
+        if self.platform == Platform::MacOs {
+            self.user_apps_dir(InstallScope::Portable)
+        } else {
+            Ok(self.exe_dir.clone())
+        }
+    }
+
+    /// Directory the log is written to. For the user scope it's the app's directory, if the app
+    /// is known yet.
+    pub fn log_dir(&self, scope: InstallScope, app_slug: Option<&str>) -> Result<PathBuf> {
+        match (scope, self.platform) {
+            (InstallScope::User, _) => {
+                let apps_dir = self.user_apps_dir(scope)?;
+                Ok(app_slug.map_or_else(|| apps_dir.clone(), |app_slug| apps_dir.join(app_slug)))
+            },
+            (InstallScope::Portable, Platform::MacOs) => {
+                Ok(self.user()?.home.join("Library").join("Logs").join("PatchKit"))
+            },
+            (InstallScope::Portable, _) => Ok(self.exe_dir.clone()),
+        }
+    }
+
+    /// Directory for staging files, cleaned up by the OS eventually. It's the same for every
+    /// platform and scope.
+    pub fn temp_dir() -> PathBuf {
+        std::env::temp_dir()
+    }
+}
+
+/// Sets the bundle's `CFBundleVersion` to the runner's version, so Finder and crash reports
+/// tell which runner is installed. Bundles signed with the right version are left untouched.
+pub fn sync_bundle_version() {
+    let Some(bundle) = bundle_dir() else {
+        return;
+    };
+    let plist_path = bundle.join("Contents").join("Info.plist");
+    let Ok(plist) = fs::read_to_string(&plist_path) else {
+        debug!("No readable {}", plist_path.display());
+        return;
+    };
+    let Some(updated) = with_bundle_version(&plist, env!("CARGO_PKG_VERSION")) else {
+        return;
+    };
+    match fs::write(&plist_path, updated) {
+        Ok(()) => info!("Updated the bundle version to {}", env!("CARGO_PKG_VERSION")),
+        Err(e) => warn!("Failed to update the bundle version in {}: {}", plist_path.display(), e),
+    }
+}
+
+/// `plist` with `CFBundleVersion` set to `version`, or `None` if it already is or the key is
+/// missing.
+fn with_bundle_version(plist: &str, version: &str) -> Option<String> {
+    const KEY: &str = "<key>CFBundleVersion</key>";
+    let after_key = plist.find(KEY)? + KEY.len();
+    let value_start = after_key + plist[after_key..].find("<string>")? + "<string>".len();
+    let value_end = value_start + plist[value_start..].find("</string>")?;
+    if !plist[after_key..value_start - "<string>".len()].trim().is_empty() || &plist[value_start..value_end] == version {
+        return None;
+    }
+    Some(format!("{}{}{}", &plist[..value_start], version, &plist[value_end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_of() {
+        assert_eq!(
+            bundle_of(Path::new("/Applications/My Game.app/Contents/MacOS/runner")),
+            Some(PathBuf::from("/Applications/My Game.app"))
+        );
+        assert_eq!(bundle_of(Path::new("/Applications/runner")), None);
+        assert_eq!(bundle_of(Path::new("/games/Contents/MacOS/runner")), None);
+    }
+
+    fn provider(platform: Platform) -> PathProvider {
+        PathProvider {
+            platform,
+            exe_dir: PathBuf::from("/games/racer"),
+            user: Some(UserDirs {
+                home: PathBuf::from("/home/me"),
+                data: PathBuf::from("/home/me/data"),
+                data_local: PathBuf::from("/home/me/local"),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_path_provider() {
+        let linux = provider(Platform::Linux);
+        assert_eq!(linux.data_dir(InstallScope::Portable, "abc").unwrap(), Path::new("/games/racer/app"));
+        assert_eq!(linux.patcher_dir(InstallScope::Portable, "abc").unwrap(), Path::new("/games/racer/Patcher"));
+        assert_eq!(linux.data_dir(InstallScope::User, "abc").unwrap(), Path::new("/home/me/local/PatchKit/Apps/abc/Data"));
+        assert_eq!(linux.cache_dir(InstallScope::User, "abc").unwrap(), Path::new("/home/me/local/PatchKit/Apps/abc/Patcher"));
+        assert_eq!(linux.log_dir(InstallScope::Portable, None).unwrap(), Path::new("/games/racer"));
+        assert_eq!(linux.log_dir(InstallScope::User, Some("abc")).unwrap(), Path::new("/home/me/local/PatchKit/Apps/abc"));
+        assert_eq!(linux.config_dir().unwrap(), Path::new("/games/racer"));
+
+        let macos = provider(Platform::MacOs);
+        assert_eq!(macos.data_dir(InstallScope::Portable, "abc").unwrap(), Path::new("/home/me/data/PatchKit/Apps/abc/Data"));
+        assert_eq!(macos.log_dir(InstallScope::Portable, None).unwrap(), Path::new("/home/me/Library/Logs/PatchKit"));
+        assert_eq!(macos.config_dir().unwrap(), Path::new("/home/me/data/PatchKit/Apps"));
+
+        // Without a home directory only the paths next to the runner are known
+        let homeless = PathProvider { user: None, ..provider(Platform::Windows) };
+        assert!(homeless.patcher_dir(InstallScope::Portable, "abc").is_ok());
+        assert!(homeless.patcher_dir(InstallScope::User, "abc").is_err());
+    }
+
+    #[test]
+    fn test_record_install_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = provider(Platform::Windows);
+        paths.user.as_mut().unwrap().data_local = dir.path().to_path_buf();
+
+        assert_eq!(paths.install_scope("abc"), InstallScope::Portable);
+        paths.record_install_scope("abc", InstallScope::User).unwrap();
+        assert_eq!(paths.install_scope("abc"), InstallScope::User);
+        assert_eq!(paths.install_scope("other"), InstallScope::Portable);
+        paths.record_install_scope("abc", InstallScope::Portable).unwrap();
+        assert_eq!(paths.install_scope("abc"), InstallScope::Portable);
+    }
+
+    #[test]
+    fn test_with_bundle_version() {
+        let plist = "<dict>\n\t<key>CFBundleVersion</key>\n\t<string>0.1.0</string>\n\t<key>CFBundleName</key>\n\t<string>Game</string>\n</dict>";
+        let updated = with_bundle_version(plist, "0.2.0").unwrap();
+        assert!(updated.contains("<key>CFBundleVersion</key>\n\t<string>0.2.0</string>"));
+        assert!(updated.contains("<string>Game</string>"));
+        assert_eq!(with_bundle_version(&updated, "0.2.0"), None);
+        assert_eq!(with_bundle_version("<dict></dict>", "0.2.0"), None);
+    }
+}
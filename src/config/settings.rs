@@ -0,0 +1,158 @@
+use crate::config::{paths::PathProvider, ThemeMode};
+use crate::network::schedule::BandwidthRule;
+use crate::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SETTINGS_FILE_NAME: &str = "runner.toml";
+
+/// Languages offered in the settings dialog, as (code, display name) pairs.
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("de", "Deutsch"),
+    ("es", "Español"),
+    ("fr", "Français"),
+    ("pl", "Polski"),
+    ("ru", "Русский"),
+    ("ja", "日本語"),
+    ("ko", "한국어"),
+    ("zh", "中文"),
+];
+
+/// User preferences persisted to `runner.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Overrides the default install directory passed to the patcher.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_dir: Option<PathBuf>,
+    /// Download speed limit in KB/s. `None` means unlimited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bandwidth_limit_kbps: Option<u64>,
+    /// Limits by the time of day, overriding `bandwidth_limit_kbps` while they apply.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bandwidth_schedule: Vec<BandwidthRule>,
+    /// Connections large downloads are split over. `None` means one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_connections: Option<usize>,
+    /// How often a failing request is attempted before the update fails. `None` means 3.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_attempts: Option<u32>,
+    /// URL of an HTTP or SOCKS5 proxy for all requests. `None` uses the proxy from the
+    /// environment or the system settings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Ignores the API certificate pins of the dat, for self-hosted deployments whose server
+    /// presents another key.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub disable_certificate_pinning: bool,
+    /// Preferred language code, e.g. `en`. `None` follows the system language.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Overrides the theme from the dat branding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<ThemeMode>,
+    /// Allows sending crash and error reports to the app's developer, if the dat configures it.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub send_error_reports: bool,
+    /// Allows sending anonymous failure statistics to PatchKit. `None` means allowed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_error_statistics: Option<bool>,
+    /// Allows sending download speeds per CDN mirror to PatchKit, which picks faster mirrors in
+    /// return.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub share_download_statistics: bool,
+    /// Minutes between update checks in `--daemon` mode. `None` means hourly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_check_interval_minutes: Option<u64>,
+    /// Version ID to install instead of the latest one. `None` follows the latest version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_version: Option<String>,
+}
+
+impl Settings {
+    /// Location of the settings file, next to the runner (or in Application Support on macOS).
+    pub fn path() -> Result<PathBuf> {
+        Ok(PathProvider::current()?.config_dir()?.join(SETTINGS_FILE_NAME))
+    }
+
+    /// Loads settings from `path`, falling back to defaults when the file doesn't exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            debug!("No settings file at {}", path.display());
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| crate::Error::Other(format!("Invalid settings file {}: {}", path.display(), e)))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| crate::Error::Other(format!("Failed to serialize settings: {}", e)))?;
+        fs::write(path, content)?;
+        debug!("Saved settings to {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_settings_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE_NAME);
+
+        // A missing file yields the defaults
+        assert_eq!(Settings::load(&path).unwrap(), Settings::default());
+
+        let settings = Settings {
+            install_dir: Some(PathBuf::from("/games/app")),
+            bandwidth_limit_kbps: Some(512),
+            bandwidth_schedule: vec![BandwidthRule {
+                from: "23:00".to_string().try_into().unwrap(),
+                to: "07:00".to_string().try_into().unwrap(),
+                limit_kbps: 0,
+            }],
+            download_connections: Some(4),
+            retry_attempts: Some(5),
+            proxy: Some("http://proxy.example.com:8080".into()),
+            disable_certificate_pinning: true,
+            language: Some("pl".into()),
+            theme: Some(ThemeMode::Light),
+            send_error_reports: true,
+            share_error_statistics: Some(false),
+            share_download_statistics: true,
+            update_check_interval_minutes: Some(30),
+            pinned_version: Some("41".into()),
+        };
+        settings.save(&path).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("bandwidth_limit_kbps = 512"));
+        assert!(content.contains("from = \"23:00\""));
+        assert!(content.contains("theme = \"light\""));
+        assert!(content.contains("send_error_reports = true"));
+        assert_eq!(Settings::load(&path).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_settings_invalid_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE_NAME);
+        fs::write(&path, "bandwidth_limit_kbps = \"fast\"").unwrap();
+        assert!(Settings::load(&path).is_err());
+    }
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// Where error reports are uploaded, set in the JSON dat file. Nothing is sent unless the user
+/// enabled error reports in the settings.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TelemetryConfig {
+    /// URL receiving each report as a JSON `POST`.
+    pub endpoint: String,
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const DEFAULT_COUNTDOWN_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    /// Always install the latest version before launching.
+    #[default]
+    Force,
+    /// Let the user launch the installed version instead of updating.
+    Prompt,
+}
+
+/// How updates are applied when a version is already installed, set in the JSON dat file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UpdatePolicy {
+    #[serde(default)]
+    pub mode: UpdateMode,
+    /// Seconds after which the prompt updates without an answer.
+    #[serde(default = "default_countdown_secs")]
+    pub countdown_secs: u64,
+}
+
+impl Default for UpdatePolicy {
+    fn default() -> Self {
+        Self {
+            mode: UpdateMode::default(),
+            countdown_secs: DEFAULT_COUNTDOWN_SECS,
+        }
+    }
+}
+
+impl UpdatePolicy {
+    pub fn countdown(&self) -> Duration {
+        Duration::from_secs(self.countdown_secs)
+    }
+}
+
+fn default_countdown_secs() -> u64 {
+    DEFAULT_COUNTDOWN_SECS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_policy_parsing() {
+        let policy: UpdatePolicy = serde_json::from_str(r#"{"mode": "prompt", "countdown_secs": 30}"#).unwrap();
+        assert_eq!(policy.mode, UpdateMode::Prompt);
+        assert_eq!(policy.countdown(), Duration::from_secs(30));
+
+        let policy: UpdatePolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(policy, UpdatePolicy::default());
+        assert_eq!(policy.mode, UpdateMode::Force);
+    }
+}
@@ -0,0 +1,88 @@
+//! Crash reports. A panic writes what happened to a file next to the log, so that a window that
+//! disappeared or an update that stopped can be diagnosed.
+
+use log::error;
+use std::backtrace::Backtrace;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CRASH_FILE_NAME: &str = "launcher-crash.txt";
+
+static LAST_STATUS: Mutex<String> = Mutex::new(String::new());
+
+/// Remembers the status shown to the user, for the crash report.
+pub fn set_last_status(status: &str) {
+    if let Ok(mut last_status) = LAST_STATUS.lock() {
+        status.clone_into(&mut last_status);
+    }
+}
+
+/// Installs a panic hook writing a crash report to `path`. The default hook still runs after it.
+pub fn install_hook(path: PathBuf) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Unknown panic");
+        let location = info.location().map(|location| location.to_string());
+        let thread = std::thread::current();
+        // A panicking thread may hold the lock, don't wait for it
+        let status = LAST_STATUS.try_lock().map(|status| status.clone()).unwrap_or_default();
+
+        let report = crash_report(
+            message,
+            location.as_deref(),
+            thread.name().unwrap_or("unnamed"),
+            &status,
+            &Backtrace::force_capture().to_string(),
+        );
+        match std::fs::write(&path, report) {
+            Ok(()) => error!("Runner panicked: {}, crash report written to {}", message, path.display()),
+            Err(e) => error!("Runner panicked: {}, failed to write crash report: {}", message, e),
+        }
+        default_hook(info);
+    }));
+}
+
+fn crash_report(message: &str, location: Option<&str>, thread: &str, status: &str, backtrace: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    format!(
+        "PatchKit Runner {} crashed\n\
+         Time: {} (Unix)\n\
+         Thread: {}\n\
+         Message: {}\n\
+         Location: {}\n\
+         Last status: {}\n\
+         \n\
+         Backtrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        timestamp,
+        thread,
+        message,
+        location.unwrap_or("unknown"),
+        status,
+        backtrace,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crash_report() {
+        let report = crash_report("boom", Some("src/main.rs:1:1"), "main", "Extracting launcher...", "0: main");
+        assert!(report.starts_with(&format!("PatchKit Runner {} crashed", env!("CARGO_PKG_VERSION"))));
+        assert!(report.contains("Message: boom\n"));
+        assert!(report.contains("Location: src/main.rs:1:1\n"));
+        assert!(report.contains("Last status: Extracting launcher...\n"));
+        assert!(report.ends_with("Backtrace:\n0: main\n"));
+    }
+}
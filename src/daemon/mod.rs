@@ -0,0 +1,124 @@
+//! `--daemon`: the runner stays in the background and downloads new versions as they're released,
+//! so starting the app later only needs to install them. The user is told once a version is
+//! ready to play.
+
+use crate::network::DownloadControl;
+use crate::runner::progress::LogProgress;
+use crate::runner::{Runner, RunnerConfig};
+use crate::Result;
+use log::{debug, info, warn};
+use std::time::Duration;
+
+#[cfg(all(feature = "tray", windows))]
+mod tray;
+
+/// Time between update checks when the settings don't say.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Checks for updates until cancelled.
+pub struct Daemon {
+    config: RunnerConfig,
+    interval: Duration,
+    control: DownloadControl,
+}
+
+impl Daemon {
+    /// Checks at the interval from the settings, hourly by default.
+    pub fn new(config: RunnerConfig) -> Self {
+        let interval = config
+            .settings
+            .update_check_interval_minutes
+            .map_or(DEFAULT_INTERVAL, |minutes| Duration::from_secs(minutes.max(1) * 60));
+        Self {
+            config,
+            interval,
+            control: DownloadControl::default(),
+        }
+    }
+
+    /// Cancelling stops the daemon, pausing pauses its downloads.
+    pub fn with_control(mut self, control: DownloadControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub async fn run(self) -> Result<()> {
+        info!("Checking for updates every {} minutes", self.interval.as_secs() / 60);
+        #[cfg(all(feature = "tray", windows))]
+        let tray = tray::DaemonTray::new(self.control.clone());
+
+        let cancellation = self.control.cancellation();
+        let mut ready: Option<String> = None;
+        loop {
+            let result = Runner::new(self.config.clone())
+                .with_control(self.control.clone())
+                .predownload(LogProgress)
+                .await;
+            match result {
+                Ok(Some(version)) if ready.as_ref() != Some(&version) => {
+                    let message = format!("Version {} is downloaded and ready to play", version);
+                    #[cfg(all(feature = "tray", windows))]
+                    if let Some(tray) = &tray {
+                        tray.set_status(&message);
+                    }
+                    notify(&message);
+                    ready = Some(version);
+                },
+                Ok(_) => debug!("No new version to download"),
+                Err(e) if matches!(e.root(), crate::Error::Cancelled(_)) => break,
+                Err(e) => warn!("Update check failed: {}", e.report()),
+            }
+
+            if cancellation.run(tokio::time::sleep(self.interval)).await.is_err() {
+                break;
+            }
+        }
+        info!("Daemon stopped");
+        Ok(())
+    }
+}
+
+/// Shows a desktop notification where the OS offers one from the command line. On Windows the
+/// tray icon's tooltip tells instead.
+fn notify(message: &str) {
+    info!("{}", message);
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        let mut command = notification_command(message);
+        match command.status() {
+            Ok(status) if status.success() => {},
+            Ok(status) => debug!("Notification command failed: {}", status),
+            Err(e) => debug!("Can't show notifications: {}", e),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn notification_command(message: &str) -> std::process::Command {
+    let mut command = std::process::Command::new("notify-send");
+    command.args(["PatchKit Runner", message]);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn notification_command(message: &str) -> std::process::Command {
+    let mut command = std::process::Command::new("osascript");
+    let script = format!("display notification {:?} with title \"PatchKit Runner\"", message);
+    command.args(["-e", &script]);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+
+    #[test]
+    fn test_interval() {
+        assert_eq!(Daemon::new(RunnerConfig::default()).interval, DEFAULT_INTERVAL);
+
+        let settings = Settings { update_check_interval_minutes: Some(15), ..Default::default() };
+        let config = RunnerConfig { settings, ..Default::default() };
+        assert_eq!(Daemon::new(config).interval, Duration::from_secs(15 * 60));
+    }
+}
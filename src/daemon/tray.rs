@@ -0,0 +1,102 @@
+//! Tray icon of the daemon, with Play and Quit actions. Without a window there's no event loop
+//! to drive it, so it runs on a thread of its own that pumps the Windows messages it needs.
+
+use crate::network::DownloadControl;
+use log::warn;
+use std::sync::mpsc::{channel, Sender, TryRecvError};
+use std::time::Duration;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::{Icon, TrayIconBuilder};
+
+const ICON_SIZE: u32 = 16;
+const ICON_COLOR: [u8; 4] = [0x2d, 0x8c, 0xf0, 0xff];
+const PUMP_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct DaemonTray {
+    status: Sender<String>,
+}
+
+impl DaemonTray {
+    /// Creates the tray icon. Failing to do so is not fatal, the daemon keeps working without it.
+    pub fn new(control: DownloadControl) -> Option<Self> {
+        let (status, statuses) = channel();
+        let (created, creation) = channel();
+        std::thread::spawn(move || {
+            let play = MenuItem::new("Play", true, None);
+            let quit = MenuItem::new("Quit", true, None);
+            let menu = Menu::new();
+            let icon = menu
+                .append_items(&[&play, &quit])
+                .map_err(|e| e.to_string())
+                .and_then(|()| Icon::from_rgba(ICON_COLOR.repeat((ICON_SIZE * ICON_SIZE) as usize), ICON_SIZE, ICON_SIZE).map_err(|e| e.to_string()))
+                .and_then(|icon| {
+                    TrayIconBuilder::new()
+                        .with_menu(Box::new(menu))
+                        .with_tooltip("PatchKit Runner - checking for updates")
+                        .with_icon(icon)
+                        .build()
+                        .map_err(|e| e.to_string())
+                });
+            let icon = match icon {
+                Ok(icon) => {
+                    let _ = created.send(Ok(()));
+                    icon
+                },
+                Err(e) => {
+                    let _ = created.send(Err(e));
+                    return;
+                },
+            };
+
+            loop {
+                pump_messages();
+                while let Ok(event) = MenuEvent::receiver().try_recv() {
+                    if event.id == *play.id() {
+                        // A normal run installs the downloaded version and launches it
+                        if let Err(e) = std::env::current_exe().and_then(|exe| std::process::Command::new(exe).spawn()) {
+                            warn!("Failed to start the app: {}", e);
+                        }
+                    } else if event.id == *quit.id() {
+                        control.cancel();
+                        return;
+                    }
+                }
+                match statuses.try_recv() {
+                    Ok(status) => {
+                        if let Err(e) = icon.set_tooltip(Some(format!("PatchKit Runner - {}", status))) {
+                            warn!("Failed to update tray tooltip: {}", e);
+                        }
+                    },
+                    Err(TryRecvError::Disconnected) => return,
+                    Err(TryRecvError::Empty) => {},
+                }
+                std::thread::sleep(PUMP_INTERVAL);
+            }
+        });
+
+        match creation.recv() {
+            Ok(Ok(())) => Some(Self { status }),
+            Ok(Err(e)) => {
+                warn!("Failed to create tray icon: {}", e);
+                None
+            },
+            Err(_) => None,
+        }
+    }
+
+    pub fn set_status(&self, status: &str) {
+        let _ = self.status.send(status.to_string());
+    }
+}
+
+/// Dispatches the messages of the tray icon's hidden window, which is how its clicks arrive.
+fn pump_messages() {
+    use winapi::um::winuser::{DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE};
+    unsafe {
+        let mut message: MSG = std::mem::zeroed();
+        while PeekMessageW(&mut message, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+            TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+}
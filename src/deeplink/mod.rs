@@ -0,0 +1,208 @@
+//! Deep links like `mygame://join?server=203.0.113.5:7777&invite=AB12CD`, which web pages use to
+//! start the app and have it join a friend. The app's URL scheme opens the runner, which updates
+//! as usual and hands the link's parameters to the launcher through the `{passthrough}` manifest
+//! variable.
+
+use crate::system::registry::RegistryValue;
+use crate::Result;
+use log::debug;
+use reqwest::Url;
+use std::path::Path;
+
+/// Manifest variable holding the parameters of the deep link the runner was started with.
+pub const PASSTHROUGH_VARIABLE: &str = "passthrough";
+
+/// Longest parameter value accepted, anything longer isn't a server address or invite code.
+const MAX_VALUE_LENGTH: usize = 256;
+
+/// The parameters of a deep link the launcher is told about.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeepLink {
+    pub server: Option<String>,
+    pub invite: Option<String>,
+}
+
+impl DeepLink {
+    /// Reads `link`, which must use `scheme`. Only known parameters are kept, and only with
+    /// values that can't be mistaken for options of the launcher.
+    pub fn parse(link: &str, scheme: &str) -> Result<Self> {
+        let url = Url::parse(link).map_err(|e| invalid(link, &e.to_string()))?;
+        if !url.scheme().eq_ignore_ascii_case(scheme) {
+            return Err(invalid(link, &format!("expected the {}:// scheme", scheme)));
+        }
+
+        let mut deep_link = Self::default();
+        for (name, value) in url.query_pairs() {
+            let field = match name.as_ref() {
+                "server" => &mut deep_link.server,
+                "invite" => &mut deep_link.invite,
+                _ => {
+                    debug!("Ignoring deep link parameter {}", name);
+                    continue;
+                },
+            };
+            if !is_safe_value(&value) {
+                return Err(invalid(link, &format!("unexpected {} value", name)));
+            }
+            *field = Some(value.into_owned());
+        }
+        Ok(deep_link)
+    }
+
+    /// The parameters as given to the launcher, e.g. `server=203.0.113.5:7777&invite=AB12CD`.
+    pub fn passthrough(&self) -> String {
+        [("server", &self.server), ("invite", &self.invite)]
+            .into_iter()
+            .filter_map(|(name, value)| value.as_ref().map(|value| format!("{}={}", name, value)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+fn invalid(link: &str, reason: &str) -> crate::Error {
+    crate::Error::Other(format!("Invalid deep link {}: {}", link, reason))
+}
+
+fn is_safe_value(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_VALUE_LENGTH
+        && !value.starts_with('-')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-' | '_' | '[' | ']'))
+}
+
+/// Whether a command line argument is a link rather than an option.
+pub fn is_link(arg: &str) -> bool {
+    !arg.starts_with('-') && arg.contains("://")
+}
+
+/// Whether `scheme` can be registered: letters first, then letters, digits, `+`, `-` or `.`.
+pub fn is_valid_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Makes links with `scheme` open the runner at `runner_path`. Repeated on every run, so the
+/// links keep working when the runner is moved.
+pub fn register_scheme(scheme: &str, display_name: &str, runner_path: &Path) -> Result<()> {
+    if !is_valid_scheme(scheme) {
+        return Err(crate::Error::Other(format!("Invalid URL scheme {}", scheme)));
+    }
+    debug!("Registering the {}:// URL scheme", scheme);
+    native::register(scheme, display_name, runner_path)
+}
+
+/// Registry keys making links with `scheme` open the runner, as (path, values) pairs.
+pub fn registry_keys(scheme: &str, display_name: &str, runner_path: &Path) -> Vec<(String, Vec<(&'static str, RegistryValue)>)> {
+    let key = format!(r"Software\Classes\{}", scheme);
+    let runner = runner_path.display();
+    vec![
+        (
+            key.clone(),
+            vec![
+                ("", RegistryValue::Text(format!("URL:{}", display_name))),
+                ("URL Protocol", RegistryValue::Text(String::new())),
+            ],
+        ),
+        (format!(r"{}\DefaultIcon", key), vec![("", RegistryValue::Text(format!("{},0", runner)))]),
+        (
+            format!(r"{}\shell\open\command", key),
+            vec![("", RegistryValue::Text(format!("\"{}\" \"%1\"", runner)))],
+        ),
+    ]
+}
+
+/// Desktop entry making links with `scheme` open the runner on Linux.
+pub fn desktop_entry(scheme: &str, display_name: &str, runner_path: &Path) -> String {
+    format!(
+        "[Desktop Entry]\nType=Application\nName={}\nExec=\"{}\" %u\nNoDisplay=true\nMimeType=x-scheme-handler/{};\n",
+        display_name,
+        runner_path.display(),
+        scheme
+    )
+}
+
+#[cfg(windows)]
+mod native {
+    use super::*;
+    use crate::system::registry;
+
+    pub fn register(scheme: &str, display_name: &str, runner_path: &Path) -> Result<()> {
+        for (path, values) in registry_keys(scheme, display_name, runner_path) {
+            registry::write_key(&path, &values)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod native {
+    use super::*;
+    use directories::BaseDirs;
+    use std::fs;
+    use std::process::Command;
+
+    pub fn register(scheme: &str, display_name: &str, runner_path: &Path) -> Result<()> {
+        let base_dirs = BaseDirs::new()
+            .ok_or_else(|| crate::Error::FileSystem("Could not determine base directories".into()))?;
+        let applications = base_dirs.data_dir().join("applications");
+        fs::create_dir_all(&applications)?;
+        let file_name = format!("patchkit-{}.desktop", scheme);
+        fs::write(applications.join(&file_name), desktop_entry(scheme, display_name, runner_path))?;
+
+        // Without xdg-mime the desktop picks the handler up from the entry's MimeType on its own
+        let handler = format!("x-scheme-handler/{}", scheme);
+        match Command::new("xdg-mime").args(["default", &file_name, &handler]).status() {
+            Ok(status) if status.success() => {},
+            Ok(status) => log::info!("xdg-mime couldn't set the {} handler: {}", handler, status),
+            Err(e) => debug!("xdg-mime unavailable: {}", e),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod native {
+    use super::*;
+
+    pub fn register(scheme: &str, _display_name: &str, _runner_path: &Path) -> Result<()> {
+        // macOS reads URL schemes from the app bundle's Info.plist, not at runtime
+        log::info!("The {}:// URL scheme must be declared in the app bundle on this platform", scheme);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let deep_link = DeepLink::parse("MyGame://join?server=203.0.113.5:7777&invite=AB12CD&utm=web", "mygame").unwrap();
+        assert_eq!(deep_link.server.as_deref(), Some("203.0.113.5:7777"));
+        assert_eq!(deep_link.invite.as_deref(), Some("AB12CD"));
+        assert_eq!(deep_link.passthrough(), "server=203.0.113.5:7777&invite=AB12CD");
+        assert_eq!(DeepLink::parse("mygame://join?invite=AB12CD", "mygame").unwrap().passthrough(), "invite=AB12CD");
+
+        // Values that could be read as options, or another app's links, are refused
+        assert!(DeepLink::parse("mygame://join?server=--config=evil", "mygame").is_err());
+        assert!(DeepLink::parse("mygame://join?invite=a%20b", "mygame").is_err());
+        assert!(DeepLink::parse("othergame://join?invite=AB12CD", "mygame").is_err());
+        assert!(DeepLink::parse("not a link", "mygame").is_err());
+    }
+
+    #[test]
+    fn test_registration() {
+        assert!(is_link("mygame://join"));
+        assert!(!is_link("--scale"));
+        assert!(is_valid_scheme("my-game2"));
+        assert!(!is_valid_scheme("2game"));
+        assert!(!is_valid_scheme("my game"));
+
+        let runner = Path::new("games/runner.exe");
+        let keys = registry_keys("mygame", "My Game", runner);
+        assert_eq!(keys[0].0, r"Software\Classes\mygame");
+        assert!(keys[0].1.contains(&("URL Protocol", RegistryValue::Text(String::new()))));
+        assert_eq!(keys[2].1[0].1, RegistryValue::Text(format!("\"{}\" \"%1\"", runner.display())));
+        assert!(desktop_entry("mygame", "My Game", runner).contains("MimeType=x-scheme-handler/mygame;\n"));
+    }
+}
@@ -0,0 +1,139 @@
+//! Support bundles: everything support needs to look into a failed update, in one zip file.
+
+pub mod self_test;
+
+use crate::config::LauncherData;
+use crate::system::SystemInfo;
+use crate::Result;
+use log::{debug, info};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Runner state kept in the patcher directory.
+const PATCHER_FILES: &[&str] = &["version.txt", "installed_files.txt", "file_hashes.txt"];
+/// Characters of a secret kept in the bundle, enough to identify the app.
+const SECRET_PREFIX_LEN: usize = 8;
+
+/// Files collected into a support bundle. Missing files are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct BundleSources {
+    pub log_path: Option<PathBuf>,
+    pub crash_path: Option<PathBuf>,
+    pub settings_path: Option<PathBuf>,
+    pub dat_path: Option<PathBuf>,
+    pub patcher_dir: Option<PathBuf>,
+    /// Install directory, whose volume the free space is reported for.
+    pub install_dir: Option<PathBuf>,
+}
+
+impl BundleSources {
+    /// Writes a new bundle to `output_dir` and returns its path.
+    pub fn create(&self, output_dir: &Path) -> Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let path = output_dir.join(format!("support-bundle-{}.zip", timestamp));
+
+        let mut zip = ZipWriter::new(File::create(&path)?);
+        let options = FileOptions::default();
+
+        for source in [&self.log_path, &self.crash_path, &self.settings_path].into_iter().flatten() {
+            add_file(&mut zip, options, source)?;
+        }
+        if let Some(patcher_dir) = &self.patcher_dir {
+            for name in PATCHER_FILES {
+                add_file(&mut zip, options, &patcher_dir.join(name))?;
+            }
+        }
+        if let Some(dat_path) = &self.dat_path {
+            let config = match LauncherData::load(dat_path) {
+                Ok(launcher_data) => serde_json::to_string_pretty(&redacted(launcher_data))?,
+                Err(e) => format!("Failed to read {}: {}", dat_path.display(), e),
+            };
+            zip.start_file("config.json", options)?;
+            zip.write_all(config.as_bytes())?;
+        }
+        zip.start_file("system_info.txt", options)?;
+        let install_dir = self.install_dir.as_deref().unwrap_or(output_dir);
+        zip.write_all(system_info(install_dir).as_bytes())?;
+
+        zip.finish()?;
+        info!("Support bundle written to {}", path.display());
+        Ok(path)
+    }
+}
+
+fn add_file(zip: &mut ZipWriter<File>, options: FileOptions, path: &Path) -> Result<()> {
+    let Ok(content) = fs::read(path) else {
+        debug!("Not adding missing {} to the support bundle", path.display());
+        return Ok(());
+    };
+    let name = path.file_name().map_or_else(|| "unnamed".into(), |name| name.to_string_lossy());
+    zip.start_file(name, options)?;
+    zip.write_all(&content)?;
+    Ok(())
+}
+
+/// The dat contents without the full secrets.
+fn redacted(mut launcher_data: LauncherData) -> LauncherData {
+    launcher_data.patcher_secret = redact_secret(&launcher_data.patcher_secret);
+    launcher_data.app_secret = redact_secret(&launcher_data.app_secret);
+    launcher_data.package_key = launcher_data.package_key.as_deref().map(redact_secret);
+    launcher_data
+}
+
+fn redact_secret(secret: &str) -> String {
+    let prefix: String = secret.chars().take(SECRET_PREFIX_LEN).collect();
+    format!("{}...", prefix)
+}
+
+fn system_info(install_dir: &Path) -> String {
+    format!("Runner version: {}\n{}", env!("CARGO_PKG_VERSION"), SystemInfo::collect(install_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_redacted() {
+        let launcher_data: LauncherData = serde_json::from_str(
+            r#"{"patcher_secret": "patcher-secret-value", "app_secret": "short"}"#,
+        ).unwrap();
+        let launcher_data = redacted(launcher_data);
+        assert_eq!(launcher_data.patcher_secret, "patcher-...");
+        assert_eq!(launcher_data.app_secret, "short...");
+    }
+
+    #[test]
+    fn test_create_bundle() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("launcher-log.txt");
+        fs::write(&log_path, "log line").unwrap();
+
+        let sources = BundleSources {
+            log_path: Some(log_path),
+            crash_path: Some(dir.path().join("missing-crash.txt")),
+            dat_path: Some(dir.path().join("launcher.dat")),
+            ..Default::default()
+        };
+        let bundle = sources.create(dir.path()).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(bundle).unwrap()).unwrap();
+        let mut names: Vec<_> = archive.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, ["config.json", "launcher-log.txt", "system_info.txt"]);
+
+        let mut log = String::new();
+        archive.by_name("launcher-log.txt").unwrap().read_to_string(&mut log).unwrap();
+        assert_eq!(log, "log line");
+    }
+}
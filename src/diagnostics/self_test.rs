@@ -0,0 +1,146 @@
+//! `--self-test`: checks the conditions an update needs, for studio QA and first-line support.
+
+use crate::config::paths::PathProvider;
+use crate::config::LauncherData;
+use crate::format::format_size;
+use crate::network::NetworkManager;
+use crate::system;
+use std::error::Error as _;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Free space needed on the install volume for a download and its extraction.
+const MIN_FREE_SPACE: u64 = 500 * 1024 * 1024;
+
+/// Outcome of one check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, result: std::result::Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Self { name, passed: true, detail },
+            Err(detail) => Self { name, passed: false, detail },
+        }
+    }
+}
+
+pub struct SelfTest {
+    pub dat_path: PathBuf,
+    pub install_dir: PathBuf,
+    pub patcher_dir: Option<PathBuf>,
+    pub network: NetworkManager,
+}
+
+impl SelfTest {
+    pub async fn run(&self) -> Vec<Check> {
+        let launcher_data = LauncherData::load(&self.dat_path);
+        let mut checks = vec![Check::new(
+            "Launcher data",
+            launcher_data
+                .as_ref()
+                .map(|_| format!("{} is readable", self.dat_path.display()))
+                .map_err(|e| format!("{}: {}", self.dat_path.display(), e)),
+        )];
+
+        let api = match &launcher_data {
+            Ok(launcher_data) => match self.network.get_app_info(&launcher_data.app_secret).await {
+                Ok(_) => Ok("App info fetched".into()),
+                Err(e) => Err(describe(&e)),
+            },
+            Err(_) => Err("Needs the launcher data".into()),
+        };
+        checks.push(Check::new("API", api));
+        checks.push(Check::new(
+            "TLS certificates",
+            self.network
+                .check_tls()
+                .await
+                .map(|()| "HTTPS connection verified".into())
+                .map_err(|e| describe(&e)),
+        ));
+
+        checks.push(Check::new("Install directory", writable(&self.install_dir)));
+        match &self.patcher_dir {
+            Some(patcher_dir) => checks.push(Check::new("Patcher directory", writable(patcher_dir))),
+            None => checks.push(Check::new("Patcher directory", Err("Needs the launcher data".into()))),
+        }
+        checks.push(Check::new("Temp directory", writable(&PathProvider::temp_dir())));
+        checks.push(Check::new("Disk space", enough_space(&self.install_dir)));
+        checks
+    }
+}
+
+/// Pass/fail table of `checks`, one line each.
+pub fn table(checks: &[Check]) -> String {
+    let width = checks.iter().map(|check| check.name.len()).max().unwrap_or(0);
+    let mut table = String::new();
+    for check in checks {
+        let status = if check.passed { "PASS" } else { "FAIL" };
+        let _ = writeln!(table, "{:<width$}  {}  {}", check.name, status, check.detail, width = width);
+    }
+    table
+}
+
+/// Whether files can be created in `dir`, or in the directory it would be created in.
+fn writable(dir: &Path) -> std::result::Result<String, String> {
+    let Some(existing) = dir.ancestors().find(|ancestor| ancestor.exists()) else {
+        return Err(format!("{} has no existing parent", dir.display()));
+    };
+    if system::is_writable(existing) {
+        Ok(format!("{} is writable", existing.display()))
+    } else {
+        Err(format!("{} isn't writable", existing.display()))
+    }
+}
+
+fn enough_space(dir: &Path) -> std::result::Result<String, String> {
+    match system::free_space(dir) {
+        Some(free) if free >= MIN_FREE_SPACE => Ok(format!("{} free", format_size(free))),
+        Some(free) => Err(format!("{} free, {} needed", format_size(free), format_size(MIN_FREE_SPACE))),
+        None => Err(format!("Couldn't determine free space on {}", dir.display())),
+    }
+}
+
+/// The error with its causes, which hold the useful part of HTTP client errors.
+fn describe(error: &crate::Error) -> String {
+    let mut description = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        // Wrapping variants show their source's message already
+        let cause_text = cause.to_string();
+        if !description.contains(&cause_text) {
+            description.push_str(&format!(": {}", cause_text));
+        }
+        source = cause.source();
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_local_checks() {
+        let dir = tempdir().unwrap();
+        assert!(writable(&dir.path().join("app").join("data")).is_ok());
+
+        let checks = [
+            Check::new("Temp directory", writable(dir.path())),
+            Check::new("API", Err("Needs the launcher data".into())),
+        ];
+        assert_eq!(
+            table(&checks),
+            format!(
+                "Temp directory  PASS  {} is writable\nAPI             FAIL  Needs the launcher data\n",
+                dir.path().display()
+            )
+        );
+    }
+}
@@ -1,9 +1,31 @@
 use std::fmt;
 
+// Process exit codes, listed in the README
+pub const EXIT_SUCCESS: u8 = 0;
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_NETWORK: u8 = 2;
+pub const EXIT_DAT_FILE: u8 = 3;
+pub const EXIT_LAUNCH: u8 = 4;
+pub const EXIT_CANCELLED: u8 = 5;
+
 #[derive(Debug)]
 pub enum Error {
     Io(std::io::Error),
     Network(reqwest::Error),
+    /// A certificate was rejected as expired or not yet valid, which mostly means the computer's
+    /// clock is wrong.
+    SystemClock(reqwest::Error),
+    /// The CDN refused a download URL, e.g. a signed one that expired during a slow download.
+    ExpiredUrl(String),
+    /// The PatchKit API server presented a key other than the pinned ones, see
+    /// [`crate::network::pinning`].
+    PinnedCertificate(String),
+    /// An API response that must be signed wasn't, or not by a known key, see
+    /// [`crate::network::verification`].
+    InvalidSignature(String),
+    /// The PatchKit API answered 429 Too Many Requests, with how long its `Retry-After` header
+    /// asked to wait.
+    RateLimited(Option<std::time::Duration>),
     Json(serde_json::Error),
     Zip(zip::result::ZipError),
     DatFile(String),
@@ -11,8 +33,13 @@ pub enum Error {
     Manifest(String),
     Lockfile(String),
     Permission(String),
+    Connection(String),
+    Launch(String),
+    Cancelled(String),
     Other(String),
     Which(which::Error),
+    /// What was being done when `source` happened, e.g. "Downloading launcher".
+    Context { context: String, source: Box<Error> },
 }
 
 impl fmt::Display for Error {
@@ -20,6 +47,11 @@ impl fmt::Display for Error {
         match self {
             Error::Io(e) => write!(f, "I/O error: {}", e),
             Error::Network(e) => write!(f, "Network error: {}", e),
+            Error::SystemClock(e) => write!(f, "Certificate rejected, check the system date and time: {}", e),
+            Error::ExpiredUrl(url) => write!(f, "Download URL refused, it may have expired: {}", url),
+            Error::PinnedCertificate(s) => write!(f, "Untrusted server certificate: {}", s),
+            Error::InvalidSignature(s) => write!(f, "Invalid API response signature: {}", s),
+            Error::RateLimited(_) => write!(f, "Servers busy: the PatchKit API answered 429 Too Many Requests"),
             Error::Json(e) => write!(f, "JSON error: {}", e),
             Error::Zip(e) => write!(f, "ZIP error: {}", e),
             Error::DatFile(s) => write!(f, "DAT file error: {}", s),
@@ -27,12 +59,133 @@ impl fmt::Display for Error {
             Error::Manifest(s) => write!(f, "Manifest error: {}", s),
             Error::Lockfile(s) => write!(f, "Lockfile error: {}", s),
             Error::Permission(s) => write!(f, "Permission error: {}", s),
+            Error::Connection(s) => write!(f, "Connection error: {}", s),
+            Error::Launch(s) => write!(f, "Launch error: {}", s),
+            Error::Cancelled(s) => write!(f, "{}", s),
             Error::Other(s) => write!(f, "{}", s),
             Error::Which(e) => write!(f, "Which error: {}", e),
+            Error::Context { context, source } => write!(f, "{}: {}", context, source),
         }
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Network(e) | Error::SystemClock(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Zip(e) => Some(e),
+            Error::Which(e) => Some(e),
+            Error::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Stable code identifying the kind of error, listed in the README. Codes must never be
+    /// reused, support triages reports by them.
+    pub fn code(&self) -> &'static str {
+        match self.root() {
+            Error::Connection(_) => "PKR-1101",
+            Error::Network(_) => "PKR-1201",
+            Error::ExpiredUrl(_) => "PKR-1202",
+            Error::RateLimited(_) => "PKR-1203",
+            Error::SystemClock(_) => "PKR-1301",
+            Error::PinnedCertificate(_) => "PKR-1302",
+            Error::InvalidSignature(_) => "PKR-1303",
+            Error::DatFile(_) => "PKR-2101",
+            Error::Json(_) => "PKR-2201",
+            Error::Manifest(_) => "PKR-2301",
+            Error::Zip(_) => "PKR-2401",
+            Error::Io(_) => "PKR-3101",
+            Error::FileSystem(_) => "PKR-3201",
+            Error::Permission(_) => "PKR-3301",
+            Error::Lockfile(_) => "PKR-3401",
+            Error::Launch(_) => "PKR-4101",
+            Error::Which(_) => "PKR-4201",
+            Error::Cancelled(_) => "PKR-5101",
+            Error::Other(_) | Error::Context { .. } => "PKR-9001",
+        }
+    }
+
+    /// The underlying error, without the context added on the way up.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// Whether trying again may succeed, e.g. after a dropped connection or a server error.
+    /// Configuration and content problems, and cancellation, are permanent.
+    pub fn is_transient(&self) -> bool {
+        match self.root() {
+            Error::Connection(_) | Error::RateLimited(_) => true,
+            Error::Network(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.is_request()
+                    || e.is_body()
+                    || e.status().is_some_and(|status| {
+                        status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    })
+            },
+            Error::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+
+    /// The message with its code, as shown to the user and written to the log.
+    pub fn report(&self) -> String {
+        format!("{} [{}]", self, self.code())
+    }
+
+    /// Exit code the runner reports when it stops because of this error.
+    pub fn exit_code(&self) -> u8 {
+        match self.root() {
+            Error::Network(_)
+            | Error::Connection(_)
+            | Error::SystemClock(_)
+            | Error::ExpiredUrl(_)
+            | Error::RateLimited(_)
+            | Error::PinnedCertificate(_)
+            | Error::InvalidSignature(_) => EXIT_NETWORK,
+            Error::DatFile(_) => EXIT_DAT_FILE,
+            Error::Launch(_) | Error::Manifest(_) | Error::Which(_) => EXIT_LAUNCH,
+            Error::Cancelled(_) => EXIT_CANCELLED,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
+/// Adds context to errors, see [`Error::Context`].
+pub trait ResultExt<T> {
+    fn context(self, context: &str) -> crate::Result<T>;
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> crate::Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn context(self, context: &str) -> crate::Result<T> {
+        self.with_context(|| context.to_string())
+    }
+
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> crate::Result<T> {
+        self.map_err(|e| Error::Context {
+            context: context(),
+            source: Box::new(e.into()),
+        })
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
         Error::Io(err)
@@ -41,10 +194,44 @@ impl From<std::io::Error> for Error {
 
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Self {
-        Error::Network(err)
+        if is_certificate_date_error(&err) {
+            Error::SystemClock(err)
+        } else {
+            Error::Network(err)
+        }
     }
 }
 
+/// Whether `err` or one of its causes is a TLS certificate rejected for its validity dates.
+fn is_certificate_date_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if is_certificate_date_message(&err.to_string()) {
+            return true;
+        }
+        current = err.source();
+    }
+    false
+}
+
+/// Messages of OpenSSL, Schannel, Secure Transport and rustls for certificates that are
+/// expired or not yet valid.
+fn is_certificate_date_message(message: &str) -> bool {
+    const PATTERNS: [&str; 9] = [
+        "certificate has expired",
+        "certificate is not yet valid",
+        "not within its validity period",
+        "peer certificate: expired",
+        "notvalidyet",
+        "errsslcertexpired",
+        "errsslcertnotyetvalid",
+        "osstatus -9814",
+        "osstatus -9815",
+    ];
+    let message = message.to_ascii_lowercase();
+    PATTERNS.iter().any(|pattern| message.contains(pattern))
+}
+
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Error::Json(err)
@@ -61,4 +248,72 @@ impl From<which::Error> for Error {
     fn from(err: which::Error) -> Self {
         Error::Which(err)
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(Error::Connection("No internet connection".into()).exit_code(), EXIT_NETWORK);
+        assert_eq!(Error::DatFile("Invalid magic bytes".into()).exit_code(), EXIT_DAT_FILE);
+        assert_eq!(Error::Launch("Not found".into()).exit_code(), EXIT_LAUNCH);
+        assert_eq!(Error::Cancelled("Update cancelled".into()).exit_code(), EXIT_CANCELLED);
+        assert_eq!(Error::Other("Unexpected".into()).exit_code(), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn test_context() {
+        let result: std::result::Result<(), std::io::Error> =
+            Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset"));
+        let error = result.context("Downloading launcher").unwrap_err();
+        assert_eq!(error.to_string(), "Downloading launcher: I/O error: connection reset");
+
+        let error = Err::<(), _>(Error::Cancelled("Download cancelled".into()))
+            .with_context(|| "Downloading launcher".to_string())
+            .context("Updating")
+            .unwrap_err();
+        assert!(matches!(error.root(), Error::Cancelled(_)));
+        assert_eq!(error.exit_code(), EXIT_CANCELLED);
+        assert_eq!(error.code(), "PKR-5101");
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(Error::Connection("No internet connection".into()).is_transient());
+        let reset = Error::from(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset"));
+        assert!(Err::<(), _>(reset).context("Downloading launcher").unwrap_err().is_transient());
+        let full = Error::from(std::io::Error::new(std::io::ErrorKind::Other, "disk full"));
+        assert!(!full.is_transient());
+        assert!(!Error::Manifest("No target".into()).is_transient());
+        assert!(Error::RateLimited(None).is_transient());
+        assert!(!Error::Cancelled("Download cancelled".into()).is_transient());
+    }
+
+    #[test]
+    fn test_certificate_date_message() {
+        assert!(is_certificate_date_message(
+            "error:0A000086:SSL routines:tls_post_process_server_certificate:certificate verify failed:../ssl/statem/statem_clnt.c:1889: (certificate has expired)"
+        ));
+        assert!(is_certificate_date_message("A required certificate is not within its validity period when verifying against the current system clock"));
+        assert!(is_certificate_date_message("invalid peer certificate: NotValidYet"));
+        assert!(is_certificate_date_message("errSSLCertExpired (OSStatus -9814)"));
+        assert!(!is_certificate_date_message("Downloaded 1-9814 of 20000 bytes"));
+        assert!(!is_certificate_date_message("error trying to connect: dns error: failed to lookup address information"));
+        assert!(!is_certificate_date_message("certificate verify failed: unable to get local issuer certificate"));
+    }
+
+    #[test]
+    fn test_codes_and_source() {
+        use std::error::Error as _;
+
+        let error = Error::from(std::io::Error::new(std::io::ErrorKind::NotFound, "missing"));
+        assert_eq!(error.code(), "PKR-3101");
+        assert_eq!(error.report(), "I/O error: missing [PKR-3101]");
+        assert_eq!(error.source().map(|e| e.to_string()).as_deref(), Some("missing"));
+
+        let error = Error::Manifest("No target".into());
+        assert_eq!(error.code(), "PKR-2301");
+        assert!(error.source().is_none());
+    }
+}
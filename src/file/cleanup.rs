@@ -0,0 +1,117 @@
+//! Removes what crashed or killed runs left behind: partial downloads nobody resumed, write-test
+//! files, and the app's staging directories in the system temp directory.
+
+use super::{PARTIAL_PREFIX, PARTIAL_SUFFIX};
+use crate::config::paths::PathProvider;
+use crate::format::format_size;
+use crate::system::WRITE_TEST_PREFIX;
+use log::{debug, info, warn};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Leftovers younger than this may belong to a run still going, or a download worth resuming.
+pub const STALE_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Prefix of the app's entries in the system temp directory.
+pub fn temp_prefix(app_slug: &str) -> String {
+    format!("patchkit-{}-", app_slug)
+}
+
+/// Removes the app's leftovers older than [`STALE_AGE`] from its patcher directory and the system
+/// temp directory. Returns the bytes reclaimed.
+pub fn remove_stale(patcher_dir: &Path, app_slug: &str) -> u64 {
+    let now = SystemTime::now();
+    let prefix = temp_prefix(app_slug);
+    let (patcher_count, patcher_bytes) = remove_stale_in(patcher_dir, is_patcher_leftover, now);
+    let (temp_count, temp_bytes) = remove_stale_in(&PathProvider::temp_dir(), |name| name.starts_with(&prefix), now);
+
+    let (count, bytes) = (patcher_count + temp_count, patcher_bytes + temp_bytes);
+    if count > 0 {
+        info!("Removed {} stale temporary files, reclaimed {}", count, format_size(bytes));
+    }
+    bytes
+}
+
+fn is_patcher_leftover(name: &str) -> bool {
+    (name.starts_with(PARTIAL_PREFIX) && name.contains(PARTIAL_SUFFIX)) || name.starts_with(WRITE_TEST_PREFIX)
+}
+
+/// Removes the entries of `dir` whose name `matches` and that weren't modified for [`STALE_AGE`]
+/// before `now`. Returns how many were removed and their size.
+fn remove_stale_in(dir: &Path, matches: impl Fn(&str) -> bool, now: SystemTime) -> (usize, u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (0, 0);
+    };
+
+    let mut removed = (0, 0);
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !matches(&name) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let age = metadata.modified().ok().and_then(|modified| now.duration_since(modified).ok());
+        if age.map_or(true, |age| age < STALE_AGE) {
+            continue;
+        }
+
+        let path = entry.path();
+        let size = entry_size(&path);
+        let result = if metadata.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match result {
+            Ok(()) => {
+                debug!("Removed stale {} ({})", path.display(), format_size(size));
+                removed.0 += 1;
+                removed.1 += size;
+            },
+            Err(e) => warn!("Failed to remove stale {}: {}", path.display(), e),
+        }
+    }
+    removed
+}
+
+/// Size of a file, or of everything in a directory.
+fn entry_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::read_dir(path)
+            .map(|entries| entries.flatten().map(|entry| entry_size(&entry.path())).sum())
+            .unwrap_or(0),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_remove_stale_in() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("launcher-1.zip.part"), "partial").unwrap();
+        fs::write(dir.path().join(".write-test1234"), "").unwrap();
+        fs::write(dir.path().join("version.txt"), "secret:1").unwrap();
+
+        // Fresh leftovers are kept
+        assert_eq!(remove_stale_in(dir.path(), is_patcher_leftover, SystemTime::now()), (0, 0));
+
+        let later = SystemTime::now() + STALE_AGE + Duration::from_secs(60);
+        assert_eq!(remove_stale_in(dir.path(), is_patcher_leftover, later), (2, 7));
+        assert!(!dir.path().join("launcher-1.zip.part").exists());
+        assert!(dir.path().join("version.txt").exists());
+
+        // Staging directories go with their content
+        let prefix = temp_prefix("abcd1234");
+        let staging = dir.path().join(format!("{}extract", prefix));
+        fs::create_dir_all(staging.join("bin")).unwrap();
+        fs::write(staging.join("bin/app"), "app").unwrap();
+        fs::create_dir(dir.path().join("patchkit-other123-extract")).unwrap();
+        assert_eq!(remove_stale_in(dir.path(), |name| name.starts_with(&prefix), later), (1, 3));
+        assert!(!staging.exists());
+        assert!(dir.path().join("patchkit-other123-extract").exists());
+    }
+}
@@ -0,0 +1,310 @@
+//! Delta updates: instead of the whole package of a version, only what changed since the installed
+//! version is downloaded. A delta is a zip holding:
+//!
+//! - `delta.json`, listing the changed files with their new hashes, and the removed ones
+//! - `files/<path>` for each file that's new or replaced as a whole
+//! - `patches/<path>` for each file changed in place, as a patch of the installed file
+//!
+//! A patch is a series of operations building the new file: `0x00` followed by the offset and the
+//! length of bytes to copy from the installed file, or `0x01` followed by the length of the bytes
+//! to insert and the bytes themselves. Numbers are little-endian u64.
+//!
+//! Every changed file is built next to its target and checked against its hash before any
+//! installed file is replaced, so a delta that doesn't fit leaves the installation as it was.
+
+use super::{open_archive, FileManager, HashingWriter};
+use crate::error::ResultExt;
+use crate::Result;
+use byteorder::{LittleEndian, ReadBytesExt};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::read::ZipFile;
+use zip::ZipArchive;
+
+#[cfg(target_os = "macos")]
+use std::os::unix::fs::PermissionsExt;
+
+const MANIFEST_NAME: &str = "delta.json";
+const COPY: u8 = 0;
+const INSERT: u8 = 1;
+
+/// What a delta changes, from its `delta.json`.
+#[derive(Debug, Deserialize)]
+struct DeltaManifest {
+    #[serde(default)]
+    files: Vec<DeltaFile>,
+    /// Paths of installed files the new version no longer has.
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaFile {
+    /// Relative to the patcher directory, with `/` separators.
+    path: String,
+    /// Hex SHA-256 of the file in the new version.
+    sha256: String,
+    /// The file comes as a patch of the installed one rather than whole.
+    #[serde(default)]
+    patch: bool,
+}
+
+impl FileManager {
+    /// Applies the delta at `delta_path` to the version installed in `destination`, and updates
+    /// the installed files list and the hash manifest. Fails without changing anything installed
+    /// when the delta doesn't fit the installed files.
+    pub fn apply_delta(&mut self, delta_path: &Path, destination: &Path) -> Result<()> {
+        let hashes = self.load_file_hashes().context("Reading the hash manifest")?;
+        let password = self.package_password.clone();
+        let password = password.as_deref().map(str::as_bytes);
+        let mut archive = open_archive(delta_path)?;
+        let manifest: DeltaManifest = serde_json::from_reader(delta_entry(&mut archive, MANIFEST_NAME, password)?)
+            .with_context(|| format!("Reading {}", MANIFEST_NAME))?;
+        info!("Applying delta: {} changed and {} removed files", manifest.files.len(), manifest.removed.len());
+
+        let removed = manifest
+            .removed
+            .iter()
+            .map(|path| Ok(destination.join(checked_path(path)?)))
+            .collect::<Result<HashSet<_>>>()?;
+        let mut staged = Vec::with_capacity(manifest.files.len());
+        let result = manifest.files.iter().try_for_each(|file| {
+            self.cancellation.check()?;
+            let target = destination.join(checked_path(&file.path)?);
+            let staging = staged_path(&target);
+            staged.push(staging.clone());
+            let hash = self.stage_file(&mut archive, file, &target, &staging, password)
+                .with_context(|| format!("Applying delta to {}", file.path))?;
+            if !hash.eq_ignore_ascii_case(&file.sha256) {
+                return Err(crate::Error::Other(format!("Patched {} doesn't match its hash", file.path)));
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            for staging in &staged {
+                let _ = fs::remove_file(staging);
+            }
+            return Err(e);
+        }
+
+        // Everything fits, the installed files are replaced
+        let mut changed = Vec::with_capacity(manifest.files.len());
+        for (file, staging) in manifest.files.iter().zip(&staged) {
+            let target = destination.join(checked_path(&file.path)?);
+            fs::rename(staging, &target).with_context(|| format!("Replacing {}", target.display()))?;
+            debug!("Patched: {}", target.display());
+            changed.push((target, file.sha256.to_ascii_lowercase()));
+        }
+        for path in &removed {
+            if path.is_file() {
+                debug!("Removing file the version no longer has: {}", path.display());
+                if let Err(e) = fs::remove_file(path) {
+                    warn!("Failed to remove file {}: {}", path.display(), e);
+                }
+            }
+        }
+
+        let changed_paths: HashSet<&PathBuf> = changed.iter().map(|(path, _)| path).collect();
+        self.file_hashes = hashes
+            .into_iter()
+            .filter(|(path, _)| !removed.contains(path) && !changed_paths.contains(path))
+            .chain(changed.iter().cloned())
+            .collect();
+        self.installed_files.retain(|path| !removed.contains(path));
+        let mut installed: HashSet<PathBuf> = self.installed_files.iter().cloned().collect();
+        for (path, _) in &changed {
+            // New directories are recorded before their files, so they're removed after them
+            let mut new_paths: Vec<&Path> = path
+                .ancestors()
+                .take_while(|ancestor| ancestor.starts_with(destination) && *ancestor != destination)
+                .filter(|ancestor| !installed.contains(*ancestor))
+                .collect();
+            new_paths.reverse();
+            for new_path in new_paths {
+                installed.insert(new_path.to_path_buf());
+                self.installed_files.push(new_path.to_path_buf());
+            }
+        }
+        self.save_installed_files().context("Saving the installed files list")?;
+        self.save_file_hashes().context("Saving file hashes")?;
+        Ok(())
+    }
+
+    /// Builds the new version of `file` at `staging`, from the delta or by patching `target`.
+    /// Returns its hash.
+    fn stage_file(
+        &self,
+        archive: &mut ZipArchive<File>,
+        file: &DeltaFile,
+        target: &Path,
+        staging: &Path,
+        password: Option<&[u8]>,
+    ) -> Result<String> {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut output = HashingWriter::new(File::create(staging)?);
+        if file.patch {
+            let mut patch = delta_entry(archive, &format!("patches/{}", file.path), password)?;
+            let mut source = File::open(target)?;
+            apply_patch(&mut source, &mut patch, &mut output)?;
+        } else {
+            let mut entry = delta_entry(archive, &format!("files/{}", file.path), password)?;
+            io::copy(&mut entry, &mut output)?;
+        }
+        output.flush()?;
+        let hash = output.finish();
+
+        // The new file keeps the permissions of the one it replaces, e.g. being executable
+        if let Ok(metadata) = fs::metadata(target) {
+            fs::set_permissions(staging, metadata.permissions())?;
+        } else {
+            #[cfg(target_os = "macos")]
+            if target.to_string_lossy().contains("Contents/MacOS") {
+                fs::set_permissions(staging, fs::Permissions::from_mode(0o755))?;
+            }
+        }
+        Ok(hash)
+    }
+}
+
+/// The entry `name` of a delta, decrypted with `password` if it's encrypted.
+fn delta_entry<'a>(archive: &'a mut ZipArchive<File>, name: &str, password: Option<&[u8]>) -> Result<ZipFile<'a>> {
+    let entry = match password {
+        Some(password) => archive.by_name_decrypt(name, password)?.map_err(|_| {
+            crate::Error::DatFile("The package key doesn't decrypt the delta".into())
+        })?,
+        None => archive.by_name(name)?,
+    };
+    Ok(entry)
+}
+
+/// `path` from a delta, refused if it's absolute or leaves the patcher directory.
+fn checked_path(path: &str) -> Result<PathBuf> {
+    let path = Path::new(path);
+    if path.as_os_str().is_empty() || !path.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(crate::Error::Other(format!("Invalid path {:?} in delta", path)));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Where the new version of `target` is built before it replaces it.
+fn staged_path(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_owned();
+    name.push(".delta");
+    PathBuf::from(name)
+}
+
+/// Writes the file `patch` builds from `source` to `output`.
+fn apply_patch(source: &mut (impl Read + Seek), patch: &mut impl Read, output: &mut impl Write) -> Result<()> {
+    let invalid = |message: &str| crate::Error::Other(format!("Invalid patch: {}", message));
+    loop {
+        let mut operation = [0];
+        if patch.read(&mut operation)? == 0 {
+            return Ok(());
+        }
+        match operation[0] {
+            COPY => {
+                let offset = patch.read_u64::<LittleEndian>()?;
+                let length = patch.read_u64::<LittleEndian>()?;
+                source.seek(SeekFrom::Start(offset))?;
+                if io::copy(&mut source.by_ref().take(length), output)? != length {
+                    return Err(invalid("copies past the end of the installed file"));
+                }
+            },
+            INSERT => {
+                let length = patch.read_u64::<LittleEndian>()?;
+                if io::copy(&mut patch.by_ref().take(length), output)? != length {
+                    return Err(invalid("inserted bytes are cut off"));
+                }
+            },
+            operation => return Err(invalid(&format!("unknown operation {}", operation))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use sha2::{Digest, Sha256};
+    use tempfile::tempdir;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn patch() -> Vec<u8> {
+        let mut patch = vec![COPY];
+        patch.write_u64::<LittleEndian>(0).unwrap();
+        patch.write_u64::<LittleEndian>(9).unwrap();
+        patch.push(INSERT);
+        patch.write_u64::<LittleEndian>(3).unwrap();
+        patch.extend_from_slice(b"2.0");
+        patch
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let mut output = Vec::new();
+        apply_patch(&mut io::Cursor::new(b"launcher 1.0"), &mut patch().as_slice(), &mut output).unwrap();
+        assert_eq!(output, b"launcher 2.0");
+
+        let mut past_end = vec![COPY];
+        past_end.write_u64::<LittleEndian>(8).unwrap();
+        past_end.write_u64::<LittleEndian>(9).unwrap();
+        assert!(apply_patch(&mut io::Cursor::new(b"launcher 1.0"), &mut past_end.as_slice(), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let dir = tempdir().unwrap();
+        let patcher_dir = dir.path().to_path_buf();
+        let mut manager = FileManager::new("test_delta").unwrap().with_patcher_dir(patcher_dir.clone());
+        let installed = dir.path().join("installed.zip");
+        let mut zip = ZipWriter::new(File::create(&installed).unwrap());
+        zip.start_file("app.txt", FileOptions::default()).unwrap();
+        zip.write_all(b"launcher 1.0").unwrap();
+        zip.start_file("old.txt", FileOptions::default()).unwrap();
+        zip.write_all(b"old").unwrap();
+        zip.finish().unwrap();
+        manager.extract_zips(&[installed], &patcher_dir).unwrap();
+
+        let hash = |content: &[u8]| format!("{:x}", Sha256::digest(content));
+        let write_delta = |path: &Path, new_hash: &str| {
+            let mut zip = ZipWriter::new(File::create(path).unwrap());
+            zip.start_file(MANIFEST_NAME, FileOptions::default()).unwrap();
+            write!(
+                zip,
+                r#"{{"files": [{{"path": "data/new.txt", "sha256": "{}"}}, {{"path": "app.txt", "sha256": "{}", "patch": true}}], "removed": ["old.txt"]}}"#,
+                hash(b"new"),
+                new_hash
+            )
+            .unwrap();
+            zip.start_file("files/data/new.txt", FileOptions::default()).unwrap();
+            zip.write_all(b"new").unwrap();
+            zip.start_file("patches/app.txt", FileOptions::default()).unwrap();
+            zip.write_all(&patch()).unwrap();
+            zip.finish().unwrap();
+        };
+
+        // A patch that doesn't produce the expected file changes nothing
+        let delta = dir.path().join("broken.zip");
+        write_delta(&delta, &hash(b"something else"));
+        assert!(manager.apply_delta(&delta, &patcher_dir).is_err());
+        assert_eq!(fs::read_to_string(patcher_dir.join("app.txt")).unwrap(), "launcher 1.0");
+        assert!(patcher_dir.join("old.txt").exists());
+        assert!(!patcher_dir.join("data/new.txt.delta").exists());
+
+        let delta = dir.path().join("delta.zip");
+        write_delta(&delta, &hash(b"launcher 2.0"));
+        manager.apply_delta(&delta, &patcher_dir).unwrap();
+        assert_eq!(fs::read_to_string(patcher_dir.join("app.txt")).unwrap(), "launcher 2.0");
+        assert_eq!(fs::read_to_string(patcher_dir.join("data/new.txt")).unwrap(), "new");
+        assert!(!patcher_dir.join("old.txt").exists());
+        assert!(manager.verify_files().unwrap().is_empty());
+        assert!(manager.installed_files.contains(&patcher_dir.join("data")));
+    }
+}
@@ -0,0 +1,53 @@
+//! Keeps a lockfile fresh during long operations. A lockfile is stale once it hasn't been
+//! modified for the lockfile timeout, so whoever holds one touches it more often than that.
+
+use log::{debug, warn};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+/// Touches a lockfile periodically until it's dropped.
+pub struct LockfileHeartbeat {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LockfileHeartbeat {
+    /// Touches `path` every `interval`. A lockfile removed in the meantime isn't recreated.
+    pub fn start(path: &Path, interval: Duration) -> Self {
+        let path = path.to_path_buf();
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if let Err(e) = touch(&path) {
+                    warn!("Failed to refresh lockfile {}: {}", path.display(), e);
+                }
+            }
+            debug!("Stopped refreshing lockfile {}", path.display());
+        });
+        Self { stop: Some(stop), thread: Some(thread) }
+    }
+}
+
+impl Drop for LockfileHeartbeat {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Marks the lockfile at `path` as modified now, keeping it if it's gone.
+fn touch(path: &PathBuf) -> io::Result<()> {
+    let mut file = match OpenOptions::new().write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    file.set_modified(SystemTime::now())?;
+    file.flush()
+}
@@ -1,18 +1,55 @@
+pub mod cleanup;
+pub mod delta;
+pub mod lockfile;
+pub mod volumes;
+
+use crate::cancel::CancellationToken;
+use crate::config::paths::{InstallScope, PathProvider};
+use crate::error::ResultExt;
+use crate::network::ContentFile;
 use crate::Result;
-use directories::BaseDirs;
+use lockfile::LockfileHeartbeat;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::{self, File};
 use std::io::{self, Write, Read, BufReader, BufWriter};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
 use std::time::{Duration, SystemTime};
+use sha2::{Digest, Sha256};
 use zip::ZipArchive;
 #[cfg(target_os = "macos")]
 use std::os::unix::fs::PermissionsExt;
 use log::{debug, info, warn};
 
+const PARTIAL_PREFIX: &str = "launcher-";
+const PARTIAL_SUFFIX: &str = ".zip.part";
+
+/// Most threads extracting an archive at once, more only contend for the disk.
+const MAX_EXTRACT_WORKERS: usize = 8;
+
+/// Size of the chunks extracted files are written in. Most files fit in one, which keeps the
+/// number of write calls per file low.
+const COPY_BUFFER_SIZE: usize = 256 * 1024;
+
+/// How long a lockfile that isn't refreshed stays valid.
+/// Written to the install and patcher directories the runner creates, so uninstalling knows
+/// everything in them is the app's.
+pub const CREATED_MARKER: &str = ".patchkit-runner";
+pub const DEFAULT_LOCKFILE_TIMEOUT: Duration = Duration::from_secs(60);
+
 pub struct FileManager {
     install_dir: PathBuf,
     installed_files: Vec<PathBuf>,
-    secret_slug: String,
+    file_hashes: Vec<(PathBuf, String)>,
+    patcher_dir: PathBuf,
+    cancellation: CancellationToken,
+    /// Decrypts encrypted packages.
+    package_password: Option<String>,
+    /// Age after which a lockfile is stale.
+    lockfile_timeout: Duration,
 }
 
 #[derive(Debug)]
@@ -41,57 +78,33 @@ impl VersionInfo {
         }
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{}:{}", self.patcher_secret, self.version)
+}
+
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.patcher_secret, self.version)
     }
 }
 
 impl FileManager {
     pub fn get_patcher_dir(secret_slug: &str) -> Result<PathBuf> {
-        if cfg!(target_os = "macos") {
-            let base_dirs = BaseDirs::new()
-                .ok_or_else(|| crate::Error::FileSystem("Could not determine base directories".into()))?;
-            
-            Ok(base_dirs
-                .data_dir()
-                .join("PatchKit")
-                .join("Apps")
-                .join(secret_slug)
-                .join("Patcher"))
-        } else {
-            // Get the directory where the runner executable is located
-            let exe_path = std::env::current_exe()?;
-            let runner_dir = exe_path.parent().ok_or_else(|| {
-                crate::Error::FileSystem("Failed to get parent directory of the current executable".into())
-            })?;
-            
-            Ok(runner_dir.join("Patcher"))
-        }
+        let paths = PathProvider::current()?;
+        paths.patcher_dir(paths.install_scope(secret_slug), secret_slug)
     }
 
+    /// File manager of the app's default directories, in the user's data directory if an earlier
+    /// run installed the app there.
     pub fn new(secret_slug: &str) -> Result<Self> {
-        let install_dir = if cfg!(target_os = "macos") {
-            let base_dirs = BaseDirs::new()
-                .ok_or_else(|| crate::Error::FileSystem("Could not determine base directories".into()))?;
-            
-            base_dirs
-                .data_dir()
-                .join("PatchKit")
-                .join("Apps")
-                .join(secret_slug)
-                .join("Data")
-        } else {
-            // For Windows and Linux, use current directory and create app directory
-            std::env::current_exe()?
-                .parent()
-                .ok_or_else(|| crate::Error::FileSystem("Failed to get parent directory of the current executable".into()))?
-                .join("app")
-        };
-
+        let paths = PathProvider::current()?;
+        let scope = paths.install_scope(secret_slug);
         let mut manager = Self {
-            install_dir,
+            install_dir: paths.data_dir(scope, secret_slug)?,
             installed_files: Vec::new(),
-            secret_slug: secret_slug.to_string(),
+            file_hashes: Vec::new(),
+            patcher_dir: paths.patcher_dir(scope, secret_slug)?,
+            cancellation: CancellationToken::default(),
+            package_password: None,
+            lockfile_timeout: DEFAULT_LOCKFILE_TIMEOUT,
         };
         
         // Try to load the list of installed files, but it's fine if it doesn't exist
@@ -103,7 +116,7 @@ impl FileManager {
     }
 
     fn get_installed_files_path(&self) -> PathBuf {
-        Self::get_patcher_dir(&self.secret_slug).unwrap().join("installed_files.txt")
+        self.patcher_dir.join("installed_files.txt")
     }
 
     fn load_installed_files(&mut self) -> Result<()> {
@@ -117,7 +130,7 @@ impl FileManager {
         let reader = BufReader::new(file);
         self.installed_files.clear();
         
-        let patcher_dir = Self::get_patcher_dir(&self.secret_slug)?;
+        let patcher_dir = &self.patcher_dir;
         for line in io::BufRead::lines(reader) {
             let line = line?;
             self.installed_files.push(patcher_dir.join(line));
@@ -132,9 +145,9 @@ impl FileManager {
         let file = File::create(path)?;
         let mut writer = BufWriter::new(file);
         
-        let patcher_dir = Self::get_patcher_dir(&self.secret_slug)?;
+        let patcher_dir = &self.patcher_dir;
         for path in &self.installed_files {
-            if let Ok(relative) = path.strip_prefix(&patcher_dir) {
+            if let Ok(relative) = path.strip_prefix(patcher_dir) {
                 writeln!(writer, "{}", relative.to_string_lossy())?;
             } else {
                 warn!("Failed to make path relative: {}", path.display());
@@ -145,17 +158,200 @@ impl FileManager {
         Ok(())
     }
 
+    fn get_file_hashes_path(&self) -> PathBuf {
+        self.patcher_dir.join("file_hashes.txt")
+    }
+
+    /// Writes the SHA-256 of every extracted file as `<hash>  <relative path>` lines.
+    fn save_file_hashes(&self) -> Result<()> {
+        let path = self.get_file_hashes_path();
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+
+        let patcher_dir = &self.patcher_dir;
+        for (path, hash) in &self.file_hashes {
+            if let Ok(relative) = path.strip_prefix(patcher_dir) {
+                writeln!(writer, "{}  {}", hash, relative.to_string_lossy())?;
+            } else {
+                warn!("Failed to make path relative: {}", path.display());
+            }
+        }
+
+        debug!("Saved {} file hashes", self.file_hashes.len());
+        Ok(())
+    }
+
+    /// The `(path, hash)` entries of the hash manifest written during extraction.
+    fn load_file_hashes(&self) -> Result<Vec<(PathBuf, String)>> {
+        let path = self.get_file_hashes_path();
+        if !path.exists() {
+            return Err(crate::Error::FileSystem("No file hash manifest found".into()));
+        }
+
+        let reader = BufReader::new(File::open(path)?);
+        let patcher_dir = &self.patcher_dir;
+        let mut hashes = Vec::new();
+        for line in io::BufRead::lines(reader) {
+            let line = line?;
+            let Some((expected, relative)) = line.split_once("  ") else {
+                warn!("Skipping malformed hash manifest line: {}", line);
+                continue;
+            };
+            hashes.push((patcher_dir.join(relative), expected.to_string()));
+        }
+        Ok(hashes)
+    }
+
+    /// Checks the installed files against the hash manifest written during extraction
+    /// and returns the ones that are missing or modified.
+    pub fn verify_files(&self) -> Result<Vec<PathBuf>> {
+        let mut damaged = Vec::new();
+        for (file_path, expected) in self.load_file_hashes()? {
+            match hash_file(&file_path) {
+                Ok(actual) if actual == expected => {}
+                Ok(_) => {
+                    debug!("Hash mismatch: {}", file_path.display());
+                    damaged.push(file_path);
+                }
+                Err(e) => {
+                    debug!("Failed to hash {}: {}", file_path.display(), e);
+                    damaged.push(file_path);
+                }
+            }
+        }
+
+        Ok(damaged)
+    }
+
+    /// The loose files of a version that aren't installed in `destination` as the hash manifest
+    /// records them: new, changed or missing ones. Without a manifest, all of them.
+    pub fn outdated_files(&self, files: &[ContentFile], destination: &Path) -> Result<Vec<ContentFile>> {
+        let installed: HashMap<PathBuf, String> = self.load_file_hashes().unwrap_or_default().into_iter().collect();
+        let mut outdated = Vec::new();
+        for file in files {
+            let path = destination.join(file.relative_path()?);
+            let current = installed.get(&path).is_some_and(|hash| hash.eq_ignore_ascii_case(&file.sha256)) && path.is_file();
+            if !current {
+                outdated.push(file.clone());
+            }
+        }
+        debug!("{} of {} files are outdated", outdated.len(), files.len());
+        Ok(outdated)
+    }
+
+    /// Records the loose files of a version, already downloaded to `destination`, as installed,
+    /// removing installed files the version no longer has.
+    pub fn install_files(&mut self, files: &[ContentFile], destination: &Path) -> Result<()> {
+        let mut hashes = Vec::with_capacity(files.len());
+        for file in files {
+            hashes.push((destination.join(file.relative_path()?), file.sha256.to_ascii_lowercase()));
+        }
+        let kept: HashSet<&PathBuf> = hashes.iter().map(|(path, _)| path).collect();
+        for path in self.installed_files.iter().rev().filter(|path| !kept.contains(path)) {
+            if path.is_file() {
+                debug!("Removing file the version no longer has: {}", path.display());
+                if let Err(e) = fs::remove_file(path) {
+                    warn!("Failed to remove file {}: {}", path.display(), e);
+                }
+            } else if path.is_dir() && fs::read_dir(path)?.next().is_none() {
+                let _ = fs::remove_dir(path);
+            }
+        }
+
+        self.installed_files = hashes.iter().map(|(path, _)| path.clone()).collect();
+        self.file_hashes = hashes;
+        self.save_installed_files().context("Saving the installed files list")?;
+        self.save_file_hashes().context("Saving file hashes")?;
+        Ok(())
+    }
+
+    /// Install and patcher directories in the user's local data directory, which the user can
+    /// always write to.
+    pub fn per_user_dirs(secret_slug: &str) -> Result<(PathBuf, PathBuf)> {
+        let paths = PathProvider::current()?;
+        Ok((paths.data_dir(InstallScope::User, secret_slug)?, paths.patcher_dir(InstallScope::User, secret_slug)?))
+    }
+
+    /// Creates the install and patcher directories and checks files can be written to them. New
+    /// or empty directories get the [`CREATED_MARKER`].
+    pub fn check_writable(&self) -> Result<()> {
+        for dir in [&self.install_dir, &self.patcher_dir] {
+            let created = fs::read_dir(dir).map_or(true, |mut entries| entries.next().is_none());
+            let writable = fs::create_dir_all(dir).is_ok() && crate::system::is_writable(dir);
+            if !writable {
+                return Err(crate::Error::Permission(format!("Can't write to {}", dir.display())));
+            }
+            if created {
+                if let Err(e) = fs::write(dir.join(CREATED_MARKER), "") {
+                    warn!("Failed to mark {} as created by the runner: {}", dir.display(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Overrides the default install directory, e.g. with the one chosen in the settings.
+    pub fn with_install_dir(mut self, install_dir: PathBuf) -> Self {
+        self.install_dir = install_dir;
+        self
+    }
+
+    /// Overrides the patcher directory next to the runner, e.g. in tests. The installed files
+    /// list is read from the new directory.
+    pub fn with_patcher_dir(mut self, patcher_dir: PathBuf) -> Self {
+        self.patcher_dir = patcher_dir;
+        self.installed_files.clear();
+        if let Err(e) = self.load_installed_files() {
+            debug!("Failed to load installed files list: {}", e);
+        }
+        self
+    }
+
+    /// Stops extraction between files once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Decrypts the entries of encrypted packages with `password`. Packages without encryption
+    /// are extracted as before.
+    pub fn with_package_password(mut self, password: Option<String>) -> Self {
+        self.package_password = password;
+        self
+    }
+
+    /// Age after which a lockfile that isn't refreshed is stale, 60 seconds by default.
+    pub fn with_lockfile_timeout(mut self, timeout: Duration) -> Self {
+        self.lockfile_timeout = timeout;
+        self
+    }
+
+    /// Removes the downloaded launcher so that it's downloaded again on next start.
+    pub fn clear_cache<P: AsRef<Path>>(patcher_dir: P) -> Result<()> {
+        let patcher_dir = patcher_dir.as_ref();
+        if patcher_dir.exists() {
+            info!("Removing cached launcher at {}", patcher_dir.display());
+            fs::remove_dir_all(patcher_dir)?;
+        }
+        Ok(())
+    }
+
     pub fn get_install_dir(&self) -> &Path {
         &self.install_dir
     }
 
+    /// Where the launcher is extracted and the update's state is kept.
+    pub fn patcher_dir(&self) -> &Path {
+        &self.patcher_dir
+    }
+
     pub fn create_install_dir(&self) -> Result<()> {
         fs::create_dir_all(&self.install_dir)?;
         Ok(())
     }
 
     pub fn get_current_version(&self) -> Result<Option<VersionInfo>> {
-        let version_file = Self::get_patcher_dir(&self.secret_slug)?.join("version.txt");
+        let version_file = self.patcher_dir.join("version.txt");
         debug!("Checking version file: {}", version_file.display());
         
         if !version_file.exists() {
@@ -174,14 +370,46 @@ impl FileManager {
         }
         
         // If parsing failed, treat it as old format (version only)
-        // In this case, we return None to force redownload
+        // In this case, we return None to force redownload unless the record is repaired
         debug!("Version file in old format, will force redownload");
         Ok(None)
     }
 
+    /// Writes the version record of `version` when it's missing or in the old format but the
+    /// installed files all match the hash manifest, so upgrading doesn't download them again.
+    /// An old-format record of another version is left alone. Returns whether it was repaired.
+    pub fn repair_version_record(&self, version: &str, patcher_secret: &str) -> Result<bool> {
+        if self.get_current_version()?.is_some() {
+            return Ok(false);
+        }
+        let version_file = self.patcher_dir.join("version.txt");
+        if let Ok(content) = fs::read_to_string(&version_file) {
+            if content.trim() != version {
+                debug!("Old-format version file is of version {}, not {}", content.trim(), version);
+                return Ok(false);
+            }
+        }
+
+        match self.verify_files() {
+            Ok(damaged) if damaged.is_empty() => {},
+            Ok(damaged) => {
+                debug!("{} installed files don't match the hash manifest", damaged.len());
+                return Ok(false);
+            },
+            Err(e) => {
+                debug!("Can't verify installed files: {}", e);
+                return Ok(false);
+            },
+        }
+
+        info!("Installed files of version {} are intact, repairing the version record", version);
+        self.save_version(version, patcher_secret)?;
+        Ok(true)
+    }
+
     pub fn save_version(&self, version: &str, patcher_secret: &str) -> Result<()> {
         let version_info = VersionInfo::new(version.to_string(), patcher_secret.to_string());
-        let version_file = Self::get_patcher_dir(&self.secret_slug)?.join("version.txt");
+        let version_file = self.patcher_dir.join("version.txt");
         debug!("Saving version to file: {}", version_file.display());
         
         // Make sure the Patcher directory exists
@@ -195,6 +423,63 @@ impl FileManager {
         Ok(())
     }
 
+    /// Removes the version record, e.g. once the files of that version are removed.
+    pub fn remove_version(&self) -> Result<()> {
+        match fs::remove_file(self.patcher_dir.join("version.txt")) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Caches the app's news so it can be shown when offline. `None` removes the cached news.
+    pub fn save_news(&self, news: Option<&str>) -> Result<()> {
+        let news_file = self.patcher_dir.join("news.txt");
+        match news {
+            Some(news) => {
+                if let Some(parent) = news_file.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(news_file, news)?;
+            },
+            None if news_file.exists() => fs::remove_file(news_file)?,
+            None => {},
+        }
+        Ok(())
+    }
+
+    pub fn load_news(&self) -> Option<String> {
+        let news_file = self.patcher_dir.join("news.txt");
+        fs::read_to_string(news_file).ok()
+    }
+
+    /// Where part `part` of the package of `version` is downloaded. It's kept when the download is
+    /// interrupted, so the next run can resume it; partial downloads of other versions are removed
+    /// along with their chunks.
+    pub fn partial_download_path(&self, version: &str, part: usize) -> Result<PathBuf> {
+        let patcher_dir = &self.patcher_dir;
+        fs::create_dir_all(patcher_dir)?;
+        let file_name = match part {
+            0 => format!("{}{}{}", PARTIAL_PREFIX, version, PARTIAL_SUFFIX),
+            _ => format!("{}{}.{}{}", PARTIAL_PREFIX, version, part, PARTIAL_SUFFIX),
+        };
+
+        for entry in fs::read_dir(patcher_dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            // Chunks of a download are named after it, e.g. `launcher-1.zip.part.chunk0`
+            let Some((stem, _)) = name.strip_prefix(PARTIAL_PREFIX).and_then(|name| name.split_once(PARTIAL_SUFFIX)) else {
+                continue;
+            };
+            if !is_part_of(stem, version) {
+                debug!("Removing stale partial download {}", name);
+                if let Err(e) = fs::remove_file(patcher_dir.join(&name)) {
+                    warn!("Failed to remove partial download {}: {}", name, e);
+                }
+            }
+        }
+
+        Ok(patcher_dir.join(file_name))
+    }
+
     pub fn needs_update(&self, new_version: &str, new_patcher_secret: &str) -> Result<bool> {
         match self.get_current_version()? {
             Some(current_version) => Ok(
@@ -206,46 +491,109 @@ impl FileManager {
     }
 
     pub fn extract_zip<P: AsRef<Path>>(&mut self, zip_path: P, destination: P) -> Result<()> {
-        let file = File::open(&zip_path)?;
-        let mut archive = ZipArchive::new(file)?;
+        self.extract_zips(&[zip_path.as_ref().to_path_buf()], destination.as_ref())
+    }
 
+    /// Extracts the parts of a package in order, so a later part overwrites files of an earlier
+    /// one. The installed files list covers all of them. A cancelled extraction removes the files
+    /// it extracted.
+    pub fn extract_zips(&mut self, zip_paths: &[PathBuf], destination: &Path) -> Result<()> {
         // Clear the installed files list before new extraction
         self.installed_files.clear();
+        self.file_hashes.clear();
 
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let outpath = destination.as_ref().join(file.mangled_name());
-
-            if file.name().ends_with('/') {
-                fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    fs::create_dir_all(p)?;
-                }
-                let mut outfile = File::create(&outpath)?;
-                io::copy(&mut file, &mut outfile)?;
-
-                #[cfg(target_os = "macos")]
-                {
-                    // Check if the file is in Contents/MacOS directory
-                    if outpath.to_string_lossy().contains("Contents/MacOS") {
-                        // Set executable permissions (read/write/execute for owner, read/execute for group and others)
-                        let perms = fs::Permissions::from_mode(0o755);
-                        fs::set_permissions(&outpath, perms)?;
-                    }
+        for zip_path in zip_paths {
+            if let Err(e) = self.extract_archive(zip_path, destination) {
+                if matches!(e.root(), crate::Error::Cancelled(_)) {
+                    info!("Extraction cancelled, removing the extracted files");
+                    self.remove_old_files()?;
+                    self.installed_files.clear();
+                    self.file_hashes.clear();
                 }
+                return Err(e);
             }
-
-            debug!("Extracted: {}", outpath.display());
-            self.installed_files.push(outpath);
+        }
+        if zip_paths.len() > 1 {
+            // Parts may share directories, and a later part may replace a file and its hash
+            let mut seen = HashSet::new();
+            self.installed_files.retain(|path| seen.insert(path.clone()));
+            let mut seen = HashSet::new();
+            self.file_hashes.reverse();
+            self.file_hashes.retain(|(path, _)| seen.insert(path.clone()));
+            self.file_hashes.reverse();
         }
 
         // Save the list of installed files
-        self.save_installed_files()?;
+        self.save_installed_files().context("Saving the installed files list")?;
+        self.save_file_hashes().context("Saving file hashes")?;
 
         Ok(())
     }
 
+    /// Extracts the entries of one archive with a pool of workers, each reading the archive
+    /// through its own handle. The installed files keep the order of the archive, and include what
+    /// was extracted before a failure.
+    fn extract_archive(&mut self, zip_path: &Path, destination: &Path) -> Result<()> {
+        let count = open_archive(zip_path)?.len();
+        let workers = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .clamp(1, MAX_EXTRACT_WORKERS)
+            .min(count.max(1));
+        debug!("Extracting {} entries of {} with {} workers", count, zip_path.display(), workers);
+
+        let cancellation = &self.cancellation;
+        let password = self.package_password.as_deref().map(str::as_bytes);
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let worker = || -> (Vec<ExtractedEntry>, Result<()>) {
+            let mut extracted = Vec::new();
+            let mut archive = match open_archive(zip_path) {
+                Ok(archive) => archive,
+                Err(e) => return (extracted, Err(e)),
+            };
+            let mut writer = EntryWriter::new();
+            while !failed.load(Ordering::Relaxed) {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count {
+                    break;
+                }
+                let result = cancellation.check().and_then(|_| writer.extract(&mut archive, index, destination, password));
+                match result {
+                    Ok((path, hash)) => extracted.push((index, path, hash)),
+                    Err(e) => {
+                        failed.store(true, Ordering::Relaxed);
+                        return (extracted, Err(e));
+                    },
+                }
+            }
+            (extracted, Ok(()))
+        };
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect()
+        });
+
+        let mut extracted = Vec::with_capacity(count);
+        let mut error = None;
+        for (entries, result) in results {
+            extracted.extend(entries);
+            if let Err(e) = result {
+                error.get_or_insert(e);
+            }
+        }
+        extracted.sort_by_key(|(index, _, _)| *index);
+        for (_, path, hash) in extracted {
+            if let Some(hash) = hash {
+                self.file_hashes.push((path.clone(), hash));
+            }
+            self.installed_files.push(path);
+        }
+        error.map_or(Ok(()), Err)
+    }
+
     pub fn remove_old_files(&self) -> Result<()> {
         if self.installed_files.is_empty() {
             debug!("No list of installed files, skipping cleanup");
@@ -282,6 +630,12 @@ impl FileManager {
         Ok(())
     }
 
+    /// Keeps the lockfile at `path` from going stale until the returned heartbeat is dropped.
+    pub fn lockfile_heartbeat<P: AsRef<Path>>(&self, path: P) -> LockfileHeartbeat {
+        LockfileHeartbeat::start(path.as_ref(), self.lockfile_timeout / 3)
+    }
+
+    /// Whether a valid lockfile exists at `path`. One older than the lockfile timeout is removed.
     pub fn check_lockfile<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
         let path = path.as_ref();
         
@@ -292,7 +646,8 @@ impl FileManager {
         if let Ok(metadata) = fs::metadata(path) {
             if let Ok(modified) = metadata.modified() {
                 if let Ok(duration) = SystemTime::now().duration_since(modified) {
-                    if duration > Duration::from_secs(60) {
+                    if duration > self.lockfile_timeout {
+                        debug!("Removing stale lockfile {}", path.display());
                         fs::remove_file(path)?;
                         return Ok(false);
                     }
@@ -309,6 +664,239 @@ impl FileManager {
     }
 }
 
+/// An entry extracted by a worker: its index in the archive, its path and, for a file, its hash.
+type ExtractedEntry = (usize, PathBuf, Option<String>);
+
+fn open_archive(zip_path: &Path) -> Result<ZipArchive<File>> {
+    let file = File::open(zip_path).with_context(|| format!("Opening {}", zip_path.display()))?;
+    ZipArchive::new(file).with_context(|| format!("Reading {}", zip_path.display()))
+}
+
+/// State a worker reuses across the entries it extracts.
+struct EntryWriter {
+    buffer: Vec<u8>,
+    /// Directories known to exist, creating them again costs a system call per level.
+    created_dirs: HashSet<PathBuf>,
+}
+
+impl EntryWriter {
+    fn new() -> Self {
+        Self { buffer: vec![0; COPY_BUFFER_SIZE], created_dirs: HashSet::new() }
+    }
+
+    fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+        if !self.created_dirs.contains(dir) {
+            fs::create_dir_all(dir)?;
+            self.created_dirs.insert(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Extracts entry `index` of `archive` into `destination`, decrypting it with `password` if
+    /// it's encrypted. Returns its path and, for a file, its hash.
+    fn extract(
+        &mut self,
+        archive: &mut ZipArchive<File>,
+        index: usize,
+        destination: &Path,
+        password: Option<&[u8]>,
+    ) -> Result<(PathBuf, Option<String>)> {
+        let mut file = match password {
+            Some(password) => archive.by_index_decrypt(index, password)?.map_err(|_| {
+                crate::Error::DatFile("The package key doesn't decrypt the package".into())
+            })?,
+            None => archive.by_index(index)?,
+        };
+        let outpath = destination.join(file.mangled_name());
+        let name = file.name().to_string();
+        let context = || format!("Extracting {}", name);
+
+        if name.ends_with('/') {
+            self.create_dir_all(&outpath).with_context(context)?;
+            debug!("Extracted: {}", outpath.display());
+            return Ok((outpath, None));
+        }
+
+        if let Some(p) = outpath.parent() {
+            self.create_dir_all(p).with_context(context)?;
+        }
+        let outfile = File::create(&outpath).with_context(context)?;
+        if file.size() > self.buffer.len() as u64 {
+            // Allocating the whole file up front avoids growing it with every write
+            outfile.set_len(file.size()).with_context(context)?;
+        }
+        let mut outfile = HashingWriter::new(outfile);
+        copy_chunked(&mut file, &mut outfile, &mut self.buffer).with_context(context)?;
+        let hash = outfile.finish();
+
+        #[cfg(target_os = "macos")]
+        {
+            // Check if the file is in Contents/MacOS directory
+            if outpath.to_string_lossy().contains("Contents/MacOS") {
+                // Set executable permissions (read/write/execute for owner, read/execute for group and others)
+                let perms = fs::Permissions::from_mode(0o755);
+                fs::set_permissions(&outpath, perms).with_context(context)?;
+            }
+        }
+
+        debug!("Extracted: {}", outpath.display());
+        Ok((outpath, Some(hash)))
+    }
+}
+
+/// Copies `reader` to `writer` in chunks of the size of `buffer`, unlike `io::copy` which uses
+/// small ones.
+fn copy_chunked(reader: &mut impl Read, writer: &mut impl Write, buffer: &mut [u8]) -> io::Result<u64> {
+    let mut copied = 0;
+    loop {
+        // Fill the buffer so a chunk is one write, the decompressor returns less per read
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+        if filled == 0 {
+            return Ok(copied);
+        }
+        writer.write_all(&buffer[..filled])?;
+        copied += filled as u64;
+    }
+}
+
+/// Hashes the data as it's written so that extraction doesn't need a second pass.
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new() }
+    }
+
+    fn finish(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hex SHA-256 of the file at `path`, as in the hash manifest.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Whether the partial download named `stem` belongs to `version`: `<version>` for the first part,
+/// `<version>.<part>` for the others.
+fn is_part_of(stem: &str, version: &str) -> bool {
+    match stem.strip_prefix(version) {
+        Some("") => true,
+        Some(part) => part.strip_prefix('.').is_some_and(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())),
+        None => false,
+    }
+}
+
+/// The installed files and update state the update pipeline works with, so it can run against a
+/// fake in tests.
+#[cfg_attr(test, mockall::automock)]
+pub trait PackageStore {
+    fn get_install_dir(&self) -> &Path;
+    fn get_current_version(&self) -> Result<Option<VersionInfo>>;
+    fn needs_update(&self, new_version: &str, new_patcher_secret: &str) -> Result<bool>;
+    fn verify_files(&self) -> Result<Vec<PathBuf>>;
+    fn repair_version_record(&self, version: &str, patcher_secret: &str) -> Result<bool>;
+    fn load_news(&self) -> Option<String>;
+    // Mockall needs the lifetime named
+    #[allow(clippy::needless_lifetimes)]
+    fn save_news<'a>(&self, news: Option<&'a str>) -> Result<()>;
+    fn partial_download_path(&self, version: &str, part: usize) -> Result<PathBuf>;
+    fn remove_old_files(&self) -> Result<()>;
+    fn extract_zips(&mut self, zip_paths: &[PathBuf], destination: &Path) -> Result<()>;
+    fn apply_delta(&mut self, delta_path: &Path, destination: &Path) -> Result<()>;
+    fn outdated_files(&self, files: &[ContentFile], destination: &Path) -> Result<Vec<ContentFile>>;
+    fn install_files(&mut self, files: &[ContentFile], destination: &Path) -> Result<()>;
+    fn save_version(&self, version: &str, patcher_secret: &str) -> Result<()>;
+    fn remove_version(&self) -> Result<()>;
+}
+
+impl PackageStore for FileManager {
+    fn get_install_dir(&self) -> &Path {
+        FileManager::get_install_dir(self)
+    }
+
+    fn get_current_version(&self) -> Result<Option<VersionInfo>> {
+        FileManager::get_current_version(self)
+    }
+
+    fn needs_update(&self, new_version: &str, new_patcher_secret: &str) -> Result<bool> {
+        FileManager::needs_update(self, new_version, new_patcher_secret)
+    }
+
+    fn verify_files(&self) -> Result<Vec<PathBuf>> {
+        FileManager::verify_files(self)
+    }
+
+    fn repair_version_record(&self, version: &str, patcher_secret: &str) -> Result<bool> {
+        FileManager::repair_version_record(self, version, patcher_secret)
+    }
+
+    fn load_news(&self) -> Option<String> {
+        FileManager::load_news(self)
+    }
+
+    fn save_news(&self, news: Option<&str>) -> Result<()> {
+        FileManager::save_news(self, news)
+    }
+
+    fn partial_download_path(&self, version: &str, part: usize) -> Result<PathBuf> {
+        FileManager::partial_download_path(self, version, part)
+    }
+
+    fn remove_old_files(&self) -> Result<()> {
+        FileManager::remove_old_files(self)
+    }
+
+    fn extract_zips(&mut self, zip_paths: &[PathBuf], destination: &Path) -> Result<()> {
+        FileManager::extract_zips(self, zip_paths, destination)
+    }
+
+    fn apply_delta(&mut self, delta_path: &Path, destination: &Path) -> Result<()> {
+        FileManager::apply_delta(self, delta_path, destination)
+    }
+
+    fn outdated_files(&self, files: &[ContentFile], destination: &Path) -> Result<Vec<ContentFile>> {
+        FileManager::outdated_files(self, files, destination)
+    }
+
+    fn install_files(&mut self, files: &[ContentFile], destination: &Path) -> Result<()> {
+        FileManager::install_files(self, files, destination)
+    }
+
+    fn save_version(&self, version: &str, patcher_secret: &str) -> Result<()> {
+        FileManager::save_version(self, version, patcher_secret)
+    }
+
+    fn remove_version(&self) -> Result<()> {
+        FileManager::remove_version(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -342,6 +930,31 @@ mod tests {
         assert!(!lockfile_path.exists());
     }
 
+    #[test]
+    fn test_lockfile_timeout_and_heartbeat() {
+        let manager = FileManager::new("test123").unwrap()
+            .with_lockfile_timeout(Duration::from_millis(300));
+        let temp_dir = tempdir().unwrap();
+        let lockfile_path = temp_dir.path().join("test.lock");
+        let age = |path: &Path| {
+            let file = File::options().write(true).open(path).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(1)).unwrap();
+        };
+
+        manager.create_lockfile(&lockfile_path).unwrap();
+        {
+            let _heartbeat = manager.lockfile_heartbeat(&lockfile_path);
+            age(&lockfile_path);
+            thread::sleep(Duration::from_millis(250));
+            assert!(manager.check_lockfile(&lockfile_path).unwrap());
+        }
+
+        age(&lockfile_path);
+        thread::sleep(Duration::from_millis(250));
+        assert!(!manager.check_lockfile(&lockfile_path).unwrap());
+        assert!(!lockfile_path.exists());
+    }
+
     #[test]
     fn test_extract_zip() {
         let temp_dir = tempdir().unwrap();
@@ -369,6 +982,225 @@ mod tests {
         assert!(extract_dir.join("test.txt").exists());
     }
 
+    #[test]
+    fn test_check_writable() {
+        let temp_dir = tempdir().unwrap();
+        let manager = FileManager::new("writetest")
+            .unwrap()
+            .with_install_dir(temp_dir.path().join("app"))
+            .with_patcher_dir(temp_dir.path().join("Patcher"));
+        manager.check_writable().unwrap();
+        assert!(temp_dir.path().join("app").is_dir());
+
+        // A file in the way can't be turned into a directory, whatever the rights
+        let blocked = temp_dir.path().join("blocked");
+        fs::write(&blocked, "").unwrap();
+        let manager = manager.with_install_dir(blocked.join("app"));
+        assert!(matches!(manager.check_writable(), Err(crate::Error::Permission(_))));
+    }
+
+    #[test]
+    fn test_extract_zips() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = FileManager::new("partstest").unwrap();
+        manager.patcher_dir = temp_dir.path().join("patcher");
+        let extract_dir = manager.patcher_dir.clone();
+
+        let parts: Vec<PathBuf> = [("a.txt", "first"), ("a.txt", "second")]
+            .iter()
+            .enumerate()
+            .map(|(i, (name, content))| {
+                let zip_path = temp_dir.path().join(format!("part{}.zip", i));
+                let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+                zip.start_file(format!("bin/{}", name), zip::write::FileOptions::default()).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+                zip.finish().unwrap();
+                zip_path
+            })
+            .collect();
+
+        // The later part wins, and the file is listed and hashed once
+        manager.extract_zips(&parts, &extract_dir).unwrap();
+        assert_eq!(fs::read_to_string(extract_dir.join("bin/a.txt")).unwrap(), "second");
+        assert_eq!(manager.installed_files.len(), 1);
+        assert_eq!(manager.file_hashes.len(), 1);
+        assert!(manager.verify_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_extraction_removes_extracted_files() {
+        let temp_dir = tempdir().unwrap();
+        let cancellation = CancellationToken::default();
+        let mut manager = FileManager::new("canceltest").unwrap().with_cancellation(cancellation.clone());
+        manager.patcher_dir = temp_dir.path().join("patcher");
+        let extract_dir = manager.patcher_dir.clone();
+
+        let parts: Vec<PathBuf> = [("first", 10), ("second", 500)]
+            .iter()
+            .map(|(dir, files)| {
+                let zip_path = temp_dir.path().join(format!("{}.zip", dir));
+                let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+                for file in 0..*files {
+                    zip.start_file(format!("{}/file{}.txt", dir, file), Default::default()).unwrap();
+                    zip.write_all(b"content").unwrap();
+                }
+                zip.finish().unwrap();
+                zip_path
+            })
+            .collect();
+
+        // The first part is extracted completely, the second is cancelled after its first files
+        let started = extract_dir.join("second/file0.txt");
+        let canceller = thread::spawn(move || {
+            let deadline = std::time::Instant::now() + Duration::from_secs(10);
+            while !started.exists() && std::time::Instant::now() < deadline {
+                thread::yield_now();
+            }
+            cancellation.cancel();
+        });
+        let result = manager.extract_zips(&parts, &extract_dir);
+        canceller.join().unwrap();
+
+        assert!(matches!(result.unwrap_err().root(), crate::Error::Cancelled(_)));
+        assert!(manager.installed_files.is_empty());
+        assert!(manager.file_hashes.is_empty());
+        assert!((0..10).all(|file| !extract_dir.join(format!("first/file{}.txt", file)).exists()));
+        assert!((0..500).all(|file| !extract_dir.join(format!("second/file{}.txt", file)).exists()));
+    }
+
+    #[test]
+    fn test_parallel_extraction_keeps_order() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = FileManager::new("paralleltest").unwrap();
+        manager.patcher_dir = temp_dir.path().join("patcher");
+        let extract_dir = manager.patcher_dir.clone();
+
+        let zip_path = temp_dir.path().join("package.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        let mut expected = Vec::new();
+        for dir in 0..4 {
+            zip.add_directory(format!("dir{}", dir), Default::default()).unwrap();
+            expected.push(extract_dir.join(format!("dir{}", dir)));
+            for file in 0..50 {
+                let name = format!("dir{}/file{}.txt", dir, file);
+                zip.start_file(&name, Default::default()).unwrap();
+                zip.write_all(name.as_bytes()).unwrap();
+                expected.push(extract_dir.join(name));
+            }
+        }
+        zip.finish().unwrap();
+
+        manager.extract_zip(&zip_path, &extract_dir).unwrap();
+        assert_eq!(manager.installed_files, expected);
+        assert_eq!(manager.file_hashes.len(), 200);
+        assert_eq!(fs::read_to_string(extract_dir.join("dir3/file49.txt")).unwrap(), "dir3/file49.txt");
+        assert!(manager.verify_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_copy_chunked() {
+        let data: Vec<u8> = (0..COPY_BUFFER_SIZE * 2 + 17).map(|i| (i % 251) as u8).collect();
+        let mut buffer = vec![0; COPY_BUFFER_SIZE];
+        let mut copied = Vec::new();
+        let mut reader = io::Cursor::new(&data);
+        assert_eq!(copy_chunked(&mut reader, &mut copied, &mut buffer).unwrap(), data.len() as u64);
+        assert_eq!(copied, data);
+    }
+
+    #[test]
+    fn test_extract_encrypted() {
+        let temp_dir = tempdir().unwrap();
+        let zip_path = temp_dir.path().join("encrypted.zip");
+        fs::write(&zip_path, include_bytes!("testdata/encrypted.zip")).unwrap();
+        let mut manager = FileManager::new("encryptedtest").unwrap();
+        manager.patcher_dir = temp_dir.path().join("patcher");
+        let extract_dir = manager.patcher_dir.clone();
+
+        assert!(manager.extract_zip(&zip_path, &extract_dir).is_err());
+        let mut manager = manager.with_package_password(Some("wrong-password".into()));
+        assert!(matches!(manager.extract_zip(&zip_path, &extract_dir).unwrap_err().root(), crate::Error::DatFile(_)));
+
+        let mut manager = manager.with_package_password(Some("package-password".into()));
+        manager.extract_zip(&zip_path, &extract_dir).unwrap();
+        assert_eq!(fs::read_to_string(extract_dir.join("bin/game")).unwrap(), "encrypted game");
+        assert!(manager.verify_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_repair_version_record() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = FileManager::new("repairtest").unwrap();
+        manager.patcher_dir = temp_dir.path().join("patcher");
+        let extract_dir = manager.patcher_dir.clone();
+
+        let zip_path = temp_dir.path().join("package.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        zip.start_file("bin/game", zip::write::FileOptions::default()).unwrap();
+        zip.write_all(b"game").unwrap();
+        zip.finish().unwrap();
+        manager.extract_zip(&zip_path, &extract_dir).unwrap();
+
+        // An old-format record of another version isn't trusted
+        let version_file = manager.patcher_dir.join("version.txt");
+        fs::write(&version_file, "1").unwrap();
+        assert!(!manager.repair_version_record("2", "secret").unwrap());
+        assert!(manager.get_current_version().unwrap().is_none());
+
+        fs::write(&version_file, "2").unwrap();
+        assert!(manager.repair_version_record("2", "secret").unwrap());
+        assert!(!manager.needs_update("2", "secret").unwrap());
+
+        // Damaged files still need the download
+        fs::remove_file(&version_file).unwrap();
+        fs::write(extract_dir.join("bin/game"), "modified").unwrap();
+        assert!(!manager.repair_version_record("2", "secret").unwrap());
+        fs::write(extract_dir.join("bin/game"), "game").unwrap();
+        assert!(manager.repair_version_record("2", "secret").unwrap());
+    }
+
+    #[test]
+    fn test_install_files() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = FileManager::new("loosetest").unwrap();
+        manager.patcher_dir = temp_dir.path().join("patcher");
+        let destination = manager.patcher_dir.clone();
+        let file = |path: &str, content: &str| ContentFile {
+            path: path.into(),
+            url: format!("https://example.com/{}", path),
+            sha256: format!("{:x}", Sha256::digest(content)),
+            size: content.len() as u64,
+        };
+        let install = |manager: &mut FileManager, files: &[(ContentFile, &str)]| {
+            for (file, content) in files {
+                let path = destination.join(file.relative_path().unwrap());
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+            let files: Vec<_> = files.iter().map(|(file, _)| file.clone()).collect();
+            manager.install_files(&files, &destination).unwrap();
+        };
+
+        let version_1 = [(file("bin/game", "game 1"), "game 1"), (file("data/old.pak", "old"), "old")];
+        let files: Vec<_> = version_1.iter().map(|(file, _)| file.clone()).collect();
+        assert_eq!(manager.outdated_files(&files, &destination).unwrap(), files);
+        install(&mut manager, &version_1);
+        assert!(manager.outdated_files(&files, &destination).unwrap().is_empty());
+
+        // Changed and new files are downloaded, the one the version dropped is removed
+        let version_2 = [(file("bin/game", "game 2"), "game 2"), (file("data/old.pak", "old"), "old"), (file("lib/core", "core"), "core")];
+        let files: Vec<_> = version_2.iter().map(|(file, _)| file.clone()).collect();
+        assert_eq!(manager.outdated_files(&files, &destination).unwrap(), [files[0].clone(), files[2].clone()]);
+        let version_2 = [version_2[0].clone(), version_2[2].clone()];
+        let files: Vec<_> = version_2.iter().map(|(file, _)| file.clone()).collect();
+        install(&mut manager, &version_2);
+        assert!(!destination.join("data/old.pak").exists());
+        assert!(manager.verify_files().unwrap().is_empty());
+
+        // A missing file is downloaded again
+        fs::remove_file(destination.join("lib/core")).unwrap();
+        assert_eq!(manager.outdated_files(&files, &destination).unwrap(), [files[1].clone()]);
+    }
+
     #[test]
     fn test_version_management() {
         let temp_dir = tempdir().unwrap();
@@ -420,6 +1252,61 @@ mod tests {
         assert!(manager.needs_update("2.0.0", "new_secret").unwrap());
     }
 
+    #[test]
+    fn test_clear_cache() {
+        let temp_dir = tempdir().unwrap();
+        let patcher_dir = temp_dir.path().join("Patcher");
+        fs::create_dir_all(patcher_dir.join("nested")).unwrap();
+        File::create(patcher_dir.join("version.txt")).unwrap();
+
+        FileManager::clear_cache(&patcher_dir).unwrap();
+        assert!(!patcher_dir.exists());
+
+        // Clearing a missing cache is not an error
+        assert!(FileManager::clear_cache(&patcher_dir).is_ok());
+    }
+
+    #[test]
+    fn test_hashing_writer_matches_hash_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("hashed.bin");
+
+        let mut writer = HashingWriter::new(File::create(&path).unwrap());
+        writer.write_all(b"hello").unwrap();
+        let hash = writer.finish();
+
+        assert_eq!(hash, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        assert_eq!(hash_file(&path).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_news_cache() {
+        let manager = FileManager::new("newstest").unwrap();
+        manager.save_news(Some("Double XP weekend")).unwrap();
+        assert_eq!(manager.load_news().as_deref(), Some("Double XP weekend"));
+
+        manager.save_news(None).unwrap();
+        assert!(manager.load_news().is_none());
+    }
+
+    #[test]
+    fn test_partial_download_path() {
+        let manager = FileManager::new("partialtest").unwrap();
+        let old_partial = manager.partial_download_path("1", 0).unwrap();
+        fs::write(&old_partial, b"partial").unwrap();
+        let old_second_part = manager.partial_download_path("1", 1).unwrap();
+        fs::write(&old_second_part, b"partial").unwrap();
+        let old_chunk = old_partial.with_file_name("launcher-1.zip.part.chunk0");
+        fs::write(&old_chunk, b"partial").unwrap();
+        assert_eq!(manager.partial_download_path("1", 0).unwrap(), old_partial);
+        assert!(old_partial.exists() && old_second_part.exists() && old_chunk.exists());
+
+        let new_partial = manager.partial_download_path("2", 0).unwrap();
+        assert_ne!(new_partial, old_partial);
+        assert!(!old_partial.exists() && !old_second_part.exists() && !old_chunk.exists());
+        assert!(!is_part_of("12", "1"));
+    }
+
     #[test]
     fn test_version_info_parsing() {
         // Test valid format
@@ -522,6 +1409,12 @@ mod tests {
             let installed_files_path = manager.get_installed_files_path();
             debug!("Installed files path: {}", installed_files_path.display());
             assert!(installed_files_path.exists());
+
+            // Freshly extracted files match the hash manifest, modified ones don't
+            assert!(manager.verify_files().unwrap().is_empty());
+            let modified = extract_dir.join("test2.txt");
+            fs::write(&modified, b"modified content").unwrap();
+            assert_eq!(manager.verify_files().unwrap(), vec![modified]);
         }
 
         // Create second instance and verify files are loaded
@@ -0,0 +1,197 @@
+//! Split archives, for CDNs that cap the size of a single object. The volumes are either a zip
+//! cut into pieces, or the `.z01`..`.zNN` + `.zip` volumes of `zip -s`, whose central directory
+//! points into each volume separately and is rewritten to point into the joined archive.
+
+use crate::error::ResultExt;
+use crate::Result;
+use log::{debug, info};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const EOCD_SIZE: usize = 22;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const CENTRAL_HEADER_SIZE: usize = 46;
+/// End of central directory plus the longest comment, where the record is searched for.
+const MAX_EOCD_SEARCH: u64 = (EOCD_SIZE + u16::MAX as usize) as u64;
+
+/// Joins `volumes` into the first one, removing the others, and checks the result is a zip.
+/// Returns the path of the joined archive.
+pub fn assemble(volumes: &[PathBuf]) -> Result<PathBuf> {
+    let (first, rest) = volumes.split_first().ok_or_else(|| crate::Error::Other("No volumes to assemble".into()))?;
+    info!("Joining {} volumes into {}", volumes.len(), first.display());
+
+    let mut starts = vec![0];
+    let mut output = OpenOptions::new().append(true).open(first).with_context(|| format!("Opening {}", first.display()))?;
+    let mut end = output.metadata()?.len();
+    for volume in rest {
+        starts.push(end);
+        let mut input = File::open(volume).with_context(|| format!("Opening {}", volume.display()))?;
+        end += io::copy(&mut input, &mut output).with_context(|| format!("Joining {}", volume.display()))?;
+    }
+    output.sync_all()?;
+    drop(output);
+
+    let mut archive = OpenOptions::new().read(true).write(true).open(first)?;
+    join_central_directory(&mut archive, &starts).with_context(|| format!("Joining {}", first.display()))?;
+    ZipArchive::new(&mut archive).with_context(|| format!("Reading {}", first.display()))?;
+
+    for volume in rest {
+        if let Err(e) = fs::remove_file(volume) {
+            debug!("Failed to remove volume {}: {}", volume.display(), e);
+        }
+    }
+    Ok(first.clone())
+}
+
+/// Rewrites the central directory of a `zip -s` archive, whose offsets are per volume, to point
+/// into the joined archive. `starts` are where the volumes begin in it. Archives cut into pieces
+/// are left as they are.
+fn join_central_directory<F: Read + Write + Seek>(archive: &mut F, starts: &[u64]) -> Result<()> {
+    let eocd_offset = find_eocd(archive)?;
+    archive.seek(SeekFrom::Start(eocd_offset))?;
+    let mut eocd = [0; EOCD_SIZE];
+    archive.read_exact(&mut eocd)?;
+
+    let (disk, cd_disk) = (u16_at(&eocd, 4), u16_at(&eocd, 6));
+    if disk == 0 && cd_disk == 0 {
+        return Ok(());
+    }
+    let (entries, cd_size, cd_offset) = (u16_at(&eocd, 10), u32_at(&eocd, 12), u32_at(&eocd, 16));
+    if disk == u16::MAX || entries == u16::MAX || cd_offset == u32::MAX {
+        return Err(unsupported("volumes in the ZIP64 format"));
+    }
+    let volume_start = |disk: u16| starts.get(usize::from(disk)).copied().ok_or_else(|| {
+        crate::Error::Zip(zip::result::ZipError::InvalidArchive("Volume missing from split archive"))
+    });
+    let cd_start = volume_start(cd_disk)? + u64::from(cd_offset);
+
+    archive.seek(SeekFrom::Start(cd_start))?;
+    let mut cd = vec![0; cd_size as usize];
+    archive.read_exact(&mut cd)?;
+    let mut pos = 0;
+    for _ in 0..entries {
+        if cd.len() < pos + CENTRAL_HEADER_SIZE || u32_at(&cd[pos..], 0) != CENTRAL_HEADER_SIGNATURE {
+            return Err(crate::Error::Zip(zip::result::ZipError::InvalidArchive("Invalid central directory")));
+        }
+        let header = &mut cd[pos..];
+        let (entry_disk, local_offset) = (u16_at(header, 34), u32_at(header, 42));
+        if entry_disk == u16::MAX || local_offset == u32::MAX {
+            return Err(unsupported("volumes in the ZIP64 format"));
+        }
+        let offset = volume_start(entry_disk)? + u64::from(local_offset);
+        header[34..36].copy_from_slice(&0u16.to_le_bytes());
+        header[42..46].copy_from_slice(&to_u32(offset)?.to_le_bytes());
+        pos += CENTRAL_HEADER_SIZE + usize::from(u16_at(header, 28)) + usize::from(u16_at(header, 30)) + usize::from(u16_at(header, 32));
+    }
+    archive.seek(SeekFrom::Start(cd_start))?;
+    archive.write_all(&cd)?;
+
+    eocd[4..8].fill(0);
+    eocd[8..10].copy_from_slice(&entries.to_le_bytes());
+    eocd[16..20].copy_from_slice(&to_u32(cd_start)?.to_le_bytes());
+    archive.seek(SeekFrom::Start(eocd_offset))?;
+    archive.write_all(&eocd)?;
+    debug!("Rewrote the central directory of {} volumes", starts.len());
+    Ok(())
+}
+
+fn find_eocd<F: Read + Seek>(archive: &mut F) -> Result<u64> {
+    let len = archive.seek(SeekFrom::End(0))?;
+    let search_start = len.saturating_sub(MAX_EOCD_SEARCH);
+    archive.seek(SeekFrom::Start(search_start))?;
+    let mut tail = Vec::new();
+    archive.read_to_end(&mut tail)?;
+    (0..tail.len().saturating_sub(EOCD_SIZE - 1))
+        .rev()
+        .find(|&pos| u32_at(&tail[pos..], 0) == EOCD_SIGNATURE)
+        .map(|pos| search_start + pos as u64)
+        .ok_or(crate::Error::Zip(zip::result::ZipError::InvalidArchive("No end of central directory")))
+}
+
+fn to_u32(offset: u64) -> Result<u32> {
+    u32::try_from(offset).map_err(|_| unsupported("volumes of archives over 4 GB, cut the zip into pieces instead"))
+}
+
+fn unsupported(what: &str) -> crate::Error {
+    crate::Error::Other(format!("Split archives with {} aren't supported", what))
+}
+
+fn u16_at(bytes: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+/// Whether `path` is a volume of a split archive other than the last, e.g. `game.z01`.
+pub fn is_volume_name(path: &str) -> bool {
+    let extension = Path::new(path).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    extension.len() >= 3
+        && extension.as_bytes()[0].eq_ignore_ascii_case(&b'z')
+        && extension[1..].bytes().all(|byte| byte.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    fn zip_of(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Volumes like `zip -s` makes: the central directory gives offsets within the volumes.
+    fn split_volumes(archive: &[u8], cut: usize) -> (Vec<u8>, Vec<u8>) {
+        let first = archive[..cut].to_vec();
+        let mut second = archive[cut..].to_vec();
+        let eocd = find_eocd(&mut Cursor::new(&second)).unwrap() as usize;
+        let cd_offset = u32_at(&second, eocd + 16) as usize - cut;
+        let mut pos = cd_offset;
+        while u32_at(&second, pos) == CENTRAL_HEADER_SIGNATURE {
+            let offset = u32_at(&second, pos + 42) as usize;
+            let (disk, offset) = if offset < cut { (0u16, offset) } else { (1, offset - cut) };
+            second[pos + 34..pos + 36].copy_from_slice(&disk.to_le_bytes());
+            second[pos + 42..pos + 46].copy_from_slice(&(offset as u32).to_le_bytes());
+            pos += CENTRAL_HEADER_SIZE + [28, 30, 32].iter().map(|field| u16_at(&second, pos + field) as usize).sum::<usize>();
+        }
+        second[eocd + 4..eocd + 6].copy_from_slice(&1u16.to_le_bytes());
+        second[eocd + 6..eocd + 8].copy_from_slice(&1u16.to_le_bytes());
+        second[eocd + 16..eocd + 20].copy_from_slice(&(cd_offset as u32).to_le_bytes());
+        (first, second)
+    }
+
+    #[test]
+    fn test_assemble() {
+        let dir = tempdir().unwrap();
+        let archive = zip_of(&[("a.txt", "first file"), ("b.txt", "second file")]);
+        // The second file starts in the second volume
+        let cut = archive.windows(5).position(|window| window == b"b.txt").unwrap() - 30;
+
+        for (first, second) in [(archive[..cut].to_vec(), archive[cut..].to_vec()), split_volumes(&archive, cut)] {
+            let volumes = vec![dir.path().join("game.z01"), dir.path().join("game.zip")];
+            fs::write(&volumes[0], first).unwrap();
+            fs::write(&volumes[1], second).unwrap();
+
+            let joined = assemble(&volumes).unwrap();
+            assert!(!volumes[1].exists());
+            let mut zip = ZipArchive::new(File::open(&joined).unwrap()).unwrap();
+            let mut content = String::new();
+            zip.by_name("b.txt").unwrap().read_to_string(&mut content).unwrap();
+            assert_eq!(content, "second file");
+        }
+
+        assert!(is_volume_name("https://cdn.example.com/game.z01"));
+        assert!(is_volume_name("game.Z12"));
+        assert!(!is_volume_name("game.zip"));
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+
+/// Formats a byte count using the largest unit that keeps the value above 1, e.g. `1.50 GB`.
+pub fn format_size(bytes: u64) -> String {
+    let mut value = bytes as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+/// Formats a transfer speed given in KB/s, switching to MB/s (and GB/s) on fast connections.
+pub fn format_speed(speed_kbps: f64) -> String {
+    let mut value = speed_kbps.max(0.0);
+    let mut unit = 1;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.2} {}/s", value, UNITS[unit])
+}
+
+/// Formats a duration as a short remaining-time string, e.g. `45s`, `3m 05s` or `1h 02m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC time, e.g. `2024-03-01T12:00:00Z`.
+pub fn format_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // Civil date from days since 1970-01-01, in eras of 400 years starting on March 1st
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.50 KB");
+        assert_eq!(format_size(300 * 1024 * 1024), "300.00 MB");
+        assert_eq!(format_size(1563832028), "1.46 GB");
+    }
+
+    #[test]
+    fn test_format_speed() {
+        assert_eq!(format_speed(0.0), "0.00 KB/s");
+        assert_eq!(format_speed(512.0), "512.00 KB/s");
+        assert_eq!(format_speed(2048.0), "2.00 MB/s");
+        assert_eq!(format_speed(12.3 * 1024.0), "12.30 MB/s");
+        assert_eq!(format_speed(-1.0), "0.00 KB/s");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h 02m");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_709_294_400), "2024-03-01T12:00:00Z");
+        assert_eq!(format_timestamp(4_102_444_799), "2099-12-31T23:59:59Z");
+    }
+}
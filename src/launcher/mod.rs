@@ -3,8 +3,18 @@ use std::path::Path;
 use std::process::Command;
 use log::info;
 
+/// Exit code with which a monitored launcher asks to be run again, e.g. after updating itself or
+/// changing settings. The runner then checks for updates and launches it anew.
+pub const RESTART_EXIT_CODE: i32 = 75;
+
 pub struct Launcher;
 
+impl Default for Launcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Launcher {
     pub fn new() -> Self {
         Self
@@ -13,6 +23,37 @@ impl Launcher {
     pub fn launch_executable<P: AsRef<Path>>(&self, executable: P, arguments: &[String]) -> Result<()> {
         let executable = executable.as_ref();
         info!("Launching executable: {:?}", executable);
+        let (mut cmd, is_app) = self.command(executable, arguments, false)?;
+        if is_app || !cfg!(target_os = "windows") {
+            // Wait for completion as before
+            let status = cmd.spawn()?.wait()?;
+            if !is_app && !status.success() {
+                return Err(crate::Error::Other(format!(
+                    "Launcher exited with status: {}",
+                    status
+                )));
+            }
+        } else {
+            // On Windows, just spawn and don't wait
+            cmd.spawn()?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs the executable until it exits on every platform, returning its exit code.
+    pub fn run_executable<P: AsRef<Path>>(&self, executable: P, arguments: &[String]) -> Result<i32> {
+        let executable = executable.as_ref();
+        info!("Running executable until it exits: {:?}", executable);
+        let (mut cmd, _) = self.command(executable, arguments, true)?;
+        let status = cmd.spawn()?.wait()?;
+        info!("Launcher exited with status: {}", status);
+        status.code().ok_or_else(|| crate::Error::Other(format!("Launcher was stopped: {}", status)))
+    }
+
+    /// The command starting `executable`, and whether it's a macOS app bundle started with
+    /// `open`. With `wait`, `open` waits for the app to quit.
+    fn command(&self, executable: &Path, arguments: &[String], wait: bool) -> Result<(Command, bool)> {
         let absolute_path = if executable.is_absolute() {
             executable.to_path_buf()
         } else {
@@ -27,13 +68,12 @@ impl Launcher {
         if cfg!(target_os = "macos") && absolute_path.extension().map_or(false, |ext| ext == "app") {
             // For macOS .app bundles, we need to use the 'open' command
             let mut cmd = Command::new("/usr/bin/open");
+            if wait {
+                cmd.arg("-W");
+            }
             
             // Convert the path to a string, keeping it relative if it was relative
-            let app_path = if executable.is_absolute() {
-                executable.to_string_lossy().to_string()
-            } else {
-                executable.to_string_lossy().to_string()
-            };
+            let app_path = executable.to_string_lossy().to_string();
             
             cmd.arg(&app_path);
             
@@ -43,39 +83,41 @@ impl Launcher {
             }
             
             info!("Launching /usr/bin/open with arguments: {:?}", cmd.get_args().collect::<Vec<_>>());
-            cmd.spawn()?.wait()?;
-        } else {
-            // For regular executables, run them directly
-            let mut cmd = Command::new(&absolute_path);
-            cmd.args(arguments);
-            
-            // Get the current executable's directory
-            let exe_path = std::env::current_exe()?;
-            let current_dir = exe_path.parent().ok_or_else(|| {
-                crate::Error::Other("Failed to get parent directory of the current executable".into())
-            })?;
-            
-            info!("Setting current directory to {}", current_dir.display());
-            cmd.current_dir(current_dir);
-            
-            info!("Launching {} with arguments: {:?}", absolute_path.display(), arguments);
-            
-            if cfg!(target_os = "windows") {
-                // On Windows, just spawn and don't wait
-                cmd.spawn()?;
-            } else {
-                // On other platforms, wait for completion as before
-                let status = cmd.spawn()?.wait()?;
-                if !status.success() {
-                    return Err(crate::Error::Other(format!(
-                        "Launcher exited with status: {}",
-                        status
-                    )));
-                }
-            }
+            return Ok((cmd, true));
         }
 
-        Ok(())
+        // For regular executables, run them directly
+        let mut cmd = Command::new(&absolute_path);
+        cmd.args(arguments);
+        
+        // Get the current executable's directory
+        let exe_path = std::env::current_exe()?;
+        let current_dir = exe_path.parent().ok_or_else(|| {
+            crate::Error::Other("Failed to get parent directory of the current executable".into())
+        })?;
+        
+        info!("Setting current directory to {}", current_dir.display());
+        cmd.current_dir(current_dir);
+        
+        info!("Launching {} with arguments: {:?}", absolute_path.display(), arguments);
+        Ok((cmd, false))
+    }
+}
+
+/// Starts the launcher, so the update pipeline can run without starting processes in tests.
+#[cfg_attr(test, mockall::automock)]
+pub trait ProcessLauncher {
+    fn launch_executable(&self, executable: &Path, arguments: &[String]) -> Result<()>;
+    fn run_executable(&self, executable: &Path, arguments: &[String]) -> Result<i32>;
+}
+
+impl ProcessLauncher for Launcher {
+    fn launch_executable(&self, executable: &Path, arguments: &[String]) -> Result<()> {
+        Launcher::launch_executable(self, executable, arguments)
+    }
+
+    fn run_executable(&self, executable: &Path, arguments: &[String]) -> Result<i32> {
+        Launcher::run_executable(self, executable, arguments)
     }
 }
 
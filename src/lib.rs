@@ -1,10 +1,22 @@
+pub mod cancel;
+pub mod cli;
 pub mod config;
+pub mod crash;
+pub mod daemon;
+pub mod deeplink;
+pub mod diagnostics;
 pub mod network;
+pub mod runner;
 pub mod file;
 pub mod launcher;
+pub mod logging;
 pub mod manifest;
 pub mod error;
+pub mod format;
+pub mod system;
+pub mod telemetry;
 pub mod ui;
+pub mod uninstall;
 
 pub use error::Error;
 pub type Result<T> = std::result::Result<T, Error>; 
\ No newline at end of file
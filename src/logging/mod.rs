@@ -0,0 +1,113 @@
+//! Log output. Records go to the log file and to stderr, so running the runner from a terminal
+//! still shows what it's doing. Update phases are timed with `tracing` spans, see [`phases`].
+
+pub mod phases;
+pub mod soft_failures;
+
+use crate::telemetry::analytics::random_id;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+/// Characters of the run ID in each log line, enough to tell runs apart in one log.
+const RUN_ID_PREFIX_LEN: usize = 8;
+
+static RUN_ID: OnceLock<String> = OnceLock::new();
+
+/// Random ID of this run, sent with API requests so they can be matched to the log.
+pub fn run_id() -> &'static str {
+    RUN_ID.get_or_init(random_id)
+}
+
+/// Log line format: timestamp, level, the start of the run ID and the target.
+pub fn format_record(out: &mut env_logger::fmt::Formatter, record: &Record) -> io::Result<()> {
+    writeln!(
+        out,
+        "[{} {:<5} {} {}] {}",
+        out.timestamp_millis(),
+        record.level(),
+        &run_id()[..RUN_ID_PREFIX_LEN],
+        record.target(),
+        record.args()
+    )
+}
+
+/// Logger passing each record to several env_logger sinks, each with its own filter and target.
+/// Warnings are also collected for the end-of-run summary.
+pub struct TeeLogger {
+    sinks: Vec<env_logger::Logger>,
+}
+
+impl TeeLogger {
+    pub fn new(sinks: Vec<env_logger::Logger>) -> Self {
+        Self { sinks }
+    }
+
+    /// Most verbose level any of the sinks accepts.
+    pub fn max_level(&self) -> LevelFilter {
+        self.sinks.iter().map(env_logger::Logger::filter).max().unwrap_or(LevelFilter::Off)
+    }
+
+    /// Installs this as the global logger.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.max_level());
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.sinks.iter().any(|sink| sink.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() == Level::Warn {
+            soft_failures::record(record.args().to_string());
+        }
+        for sink in &self.sinks {
+            if sink.matches(record) {
+                sink.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        for sink in &self.sinks {
+            sink.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::log_view::LogBuffer;
+
+    fn sink(filter: &str, buffer: &LogBuffer) -> env_logger::Logger {
+        env_logger::Builder::new()
+            .parse_filters(filter)
+            .format(|out, record| writeln!(out, "{}", record.args()))
+            .target(env_logger::Target::Pipe(Box::new(buffer.writer(io::sink()))))
+            .build()
+    }
+
+    #[test]
+    fn test_sinks_have_independent_filters() {
+        let file = LogBuffer::new(10);
+        let stderr = LogBuffer::new(10);
+        let logger = TeeLogger::new(vec![sink("debug", &file), sink("warn", &stderr)]);
+        assert_eq!(logger.max_level(), LevelFilter::Debug);
+
+        for (level, message) in [(Level::Debug, "details"), (Level::Warn, "problem")] {
+            logger.log(&Record::builder().level(level).target("runner2").args(format_args!("{}", message)).build());
+        }
+        assert_eq!(file.text(), "details\nproblem");
+        assert_eq!(stderr.text(), "problem");
+    }
+
+    #[test]
+    fn test_run_id_is_stable() {
+        assert_eq!(run_id().len(), 36);
+        assert_eq!(run_id(), run_id());
+    }
+}
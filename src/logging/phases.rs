@@ -0,0 +1,127 @@
+//! Timing of the update phases. The runner opens a `tracing` span per phase (connect, api,
+//! download, extract, launch); when a span closes its duration is logged and kept for error reports.
+
+use log::info;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Subscriber;
+use tracing::{Event, Metadata};
+
+static TIMINGS: Mutex<Vec<PhaseTiming>> = Mutex::new(Vec::new());
+
+/// How long a finished phase took.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub millis: u64,
+}
+
+/// Phases finished so far, in the order they finished.
+pub fn timings() -> Vec<PhaseTiming> {
+    TIMINGS.lock().map(|timings| timings.clone()).unwrap_or_default()
+}
+
+fn record_timing(phase: &'static str, duration: Duration) {
+    info!("Phase {} took {:.2}s", phase, duration.as_secs_f64());
+    if let Ok(mut timings) = TIMINGS.lock() {
+        timings.push(PhaseTiming { phase, millis: duration.as_millis() as u64 });
+    }
+}
+
+struct OpenSpan {
+    name: &'static str,
+    started: Instant,
+    handles: usize,
+}
+
+/// Subscriber measuring spans from creation until their last handle is dropped, so an instrumented
+/// future counts the time it waited as well. Events are left to the `log` macros.
+#[derive(Default)]
+pub struct PhaseSubscriber {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, OpenSpan>>,
+}
+
+impl PhaseSubscriber {
+    /// Installs this as the global subscriber.
+    pub fn init() -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
+        tracing::subscriber::set_global_default(Self::default())
+    }
+}
+
+impl Subscriber for PhaseSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span()
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        // Ids must not be zero
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let span = OpenSpan {
+            name: attributes.metadata().name(),
+            started: Instant::now(),
+            handles: 1,
+        };
+        self.spans.lock().unwrap().insert(id, span);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+            span.handles += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let closed = {
+            let mut spans = self.spans.lock().unwrap();
+            match spans.get_mut(&id.into_u64()) {
+                Some(span) if span.handles > 1 => {
+                    span.handles -= 1;
+                    None
+                },
+                Some(_) => spans.remove(&id.into_u64()),
+                None => None,
+            }
+        };
+        match closed {
+            Some(span) => {
+                record_timing(span.name, span.started.elapsed());
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_timed_until_last_handle_closes() {
+        tracing::subscriber::with_default(PhaseSubscriber::default(), || {
+            let span = tracing::info_span!("test_phase");
+            let handle = span.clone();
+            drop(span);
+            assert!(!timings().iter().any(|timing| timing.phase == "test_phase"));
+            drop(handle);
+        });
+        assert!(timings().iter().any(|timing| timing.phase == "test_phase"));
+    }
+}
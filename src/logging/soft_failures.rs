@@ -0,0 +1,87 @@
+//! Non-fatal problems of a run. Every warning logged is collected here, so they can be summed up
+//! once at the end instead of being scattered through the log.
+
+use std::sync::Mutex;
+
+/// Distinct warnings kept. Further ones are only counted.
+const MAX_DISTINCT: usize = 50;
+
+static SOFT_FAILURES: Mutex<SoftFailures> = Mutex::new(SoftFailures::new());
+
+/// Warnings with how often each occurred, in the order they first occurred.
+#[derive(Debug, Default)]
+pub struct SoftFailures {
+    warnings: Vec<(String, usize)>,
+    dropped: usize,
+}
+
+impl SoftFailures {
+    pub const fn new() -> Self {
+        Self { warnings: Vec::new(), dropped: 0 }
+    }
+
+    pub fn add(&mut self, warning: String) {
+        if let Some((_, count)) = self.warnings.iter_mut().find(|(existing, _)| *existing == warning) {
+            *count += 1;
+        } else if self.warnings.len() < MAX_DISTINCT {
+            self.warnings.push((warning, 1));
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.warnings.iter().map(|(_, count)| count).sum::<usize>() + self.dropped
+    }
+
+    /// One line per distinct warning, or `None` if there were none.
+    pub fn summary(&self) -> Option<String> {
+        if self.count() == 0 {
+            return None;
+        }
+
+        let mut summary = format!("Run finished with {} warning(s):", self.count());
+        for (warning, count) in &self.warnings {
+            summary.push_str("\n  - ");
+            summary.push_str(warning);
+            if *count > 1 {
+                summary.push_str(&format!(" (x{})", count));
+            }
+        }
+        if self.dropped > 0 {
+            summary.push_str(&format!("\n  - {} more", self.dropped));
+        }
+        Some(summary)
+    }
+}
+
+/// Adds a warning of this run.
+pub fn record(warning: String) {
+    if let Ok(mut soft_failures) = SOFT_FAILURES.lock() {
+        soft_failures.add(warning);
+    }
+}
+
+/// Summary of this run's warnings, see [`SoftFailures::summary`].
+pub fn summary() -> Option<String> {
+    SOFT_FAILURES.lock().ok().and_then(|soft_failures| soft_failures.summary())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_groups_repeats() {
+        let mut soft_failures = SoftFailures::new();
+        assert_eq!(soft_failures.summary(), None);
+
+        soft_failures.add("Failed to fetch news: timeout".into());
+        soft_failures.add("Attempt 1 of 3 failed: reset".into());
+        soft_failures.add("Attempt 1 of 3 failed: reset".into());
+        assert_eq!(
+            soft_failures.summary().unwrap(),
+            "Run finished with 3 warning(s):\n  - Failed to fetch news: timeout\n  - Attempt 1 of 3 failed: reset (x2)"
+        );
+    }
+}
@@ -1,374 +1,646 @@
 #![cfg_attr(windows, windows_subsystem = "windows")]
 
 use runner2::{
-    config::{self, LauncherData},
+    cancel::cancel_on_shutdown,
+    cli::{Args, BuildInfo, LogLevel, UpdateCheck, LOG_LEVEL_ENV, STDERR_LOG_LEVEL_ENV},
+    config::{paths::{self, InstallScope, PathProvider}, LauncherData, LocalizedStrings, Settings, ThemeMode, DAT_FILE_NAME},
+    crash::{self, CRASH_FILE_NAME},
+    daemon::Daemon,
+    diagnostics::{self_test::{self, SelfTest}, BundleSources},
+    error::{EXIT_FAILURE, EXIT_SUCCESS},
     file::FileManager,
-    launcher::Launcher,
-    manifest::ManifestManager,
-    network::NetworkManager,
-    ui::{RunnerApp, UiMessage},
+    logging::{self, phases::PhaseSubscriber, TeeLogger},
+    network::{verification::ResponseVerifier, DownloadControl, NetworkManager},
+    runner::{
+        app_slug,
+        progress::{LogProgress, ProgressSink},
+        Runner, RunnerConfig,
+    },
+    system::{self, elevation, steamos, SystemInfo},
+    telemetry::{ErrorEvent, ErrorReport, Telemetry},
+    ui::{
+        self, error_catalog::DisplayError, layout::Layout, log_view::LogBuffer, splash::Splash,
+        terminal::TerminalProgress, RunnerApp, UiMessage,
+    },
+    uninstall,
     Result,
 };
 
 use eframe::egui::ViewportBuilder;
-use log::{info, warn, error};
-use std::path::{PathBuf, Path};
-use std::sync::mpsc::Sender;
+use eframe::HardwareAcceleration;
+use log::{debug, info, warn, error};
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
-use tempfile;
-use std::fs::OpenOptions;
+use std::fs::{self, File, OpenOptions};
 use std::env;
-use directories::BaseDirs;
+use std::io::IsTerminal;
 
-const WINDOW_WIDTH: f32 = 400.0;
-const WINDOW_HEIGHT: f32 = 100.0;
+/// How long to wait for the runner logic to finish after the window closed.
+const WORKER_EXIT_GRACE: Duration = Duration::from_secs(2);
 
+const LOG_FILE_NAME: &str = "launcher-log.txt";
+
+/// Number of log lines kept for the window's log view.
+const LOG_BUFFER_LINES: usize = 500;
+
+/// The log is next to the runner, or in `~/Library/Logs/PatchKit` on macOS.
 fn get_log_file_path() -> Result<PathBuf> {
-    if cfg!(target_os = "macos") {
-        let base_dirs = BaseDirs::new()
-            .ok_or_else(|| runner2::Error::FileSystem("Could not determine base directories".into()))?;
-        
-        let log_dir = base_dirs
-            .data_dir()
-            .join("PatchKit")
-            .join("Apps");
-            
-        // Create the directory if it doesn't exist
-        std::fs::create_dir_all(&log_dir)?;
-        
-        Ok(log_dir.join("launcher-log.txt"))
-    } else {
-        // For Windows and Linux, use the directory where the executable is located
-        let exe_dir = env::current_exe()?
-            .parent()
-            .ok_or_else(|| runner2::Error::Other("Failed to get executable directory".into()))?
-            .to_path_buf();
-            
-        Ok(exe_dir.join("launcher-log.txt"))
+    Ok(PathProvider::current()?.log_dir(InstallScope::Portable, None)?.join(LOG_FILE_NAME))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let result = run().await;
+    if let Some(summary) = logging::soft_failures::summary() {
+        info!("{}", summary);
+    }
+    match result {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
+        Err(e) => {
+            error!("Exiting with error: {}", e.report());
+            if !matches!(e.root(), runner2::Error::Cancelled(_)) {
+                report_fatal_error(&e).await;
+            }
+            ExitCode::from(e.exit_code())
+        }
     }
 }
 
-#[cfg(windows)]
-fn is_elevated() -> bool {
-    use winapi::um::winnt::TOKEN_ELEVATION;
-    use winapi::um::securitybaseapi::GetTokenInformation;
-    use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
-    use winapi::um::winnt::TOKEN_QUERY;
-    use std::ptr::null_mut;
-
-    unsafe {
-        let mut token = null_mut();
-        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
-            return false;
+async fn run() -> Result<()> {
+    let args = Args::parse();
+
+    // Printed before logging starts, so support scripts don't leave log files behind
+    if args.version {
+        let info = BuildInfo::current();
+        if args.json {
+            println!("{}", serde_json::to_string(&info)?);
+        } else {
+            println!("{}", info);
         }
+        return Ok(());
+    }
 
-        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
-        let mut size = std::mem::size_of::<TOKEN_ELEVATION>() as u32;
-        
-        GetTokenInformation(
-            token,
-            winapi::um::winnt::TokenElevation,
-            &mut elevation as *mut _ as *mut _,
-            size,
-            &mut size,
-        ) != 0 && elevation.TokenIsElevated != 0
+    // The directory next to the runner isn't writable without elevation, e.g. under
+    // Program Files, so the log moves to the user's data directory in that case
+    let preferred_log_path = get_log_file_path()?;
+    let mut log_path = preferred_log_path.clone();
+    let mut log_file = open_log_file(&log_path);
+    if log_file.is_err() {
+        if let Some(fallback_path) = fallback_log_file_path() {
+            log_file = open_log_file(&fallback_path);
+            if log_file.is_ok() {
+                log_path = fallback_path;
+            }
+        }
     }
-}
 
-#[cfg(not(windows))]
-fn is_elevated() -> bool {
-    // On Unix systems, we don't need elevation for writing next to the executable
-    true
-}
+    let log_level_env = env::var(LOG_LEVEL_ENV).ok();
+    let stderr_level_env = env::var(STDERR_LOG_LEVEL_ENV).ok();
+    let log_buffer = init_logging(
+        LogLevel::select(args.log_level, log_level_env.as_deref()),
+        // Terminal mode already prints the progress, info records would break up its lines
+        stderr_level_env
+            .as_deref()
+            .and_then(LogLevel::parse)
+            .unwrap_or(if args.no_gui { LogLevel::Warn } else { LogLevel::Info }),
+        log_file,
+    );
 
-#[cfg(windows)]
-fn restart_as_admin() -> Result<()> {
-    use winapi::um::shellapi::ShellExecuteW;
-    use winapi::um::winuser::SW_NORMAL;
-    use std::os::windows::ffi::OsStrExt;
-    use std::ffi::OsStr;
-
-    let exe_path = env::current_exe()
-        .map_err(|e| runner2::Error::Other(format!("Failed to get executable path: {}", e)))?;
-
-    let operation: Vec<u16> = OsStr::new("runas\0").encode_wide().collect();
-    let file: Vec<u16> = exe_path.as_os_str().encode_wide().chain(Some(0)).collect();
-    let parameters: Vec<u16> = OsStr::new("\0").encode_wide().collect();
-    let directory: Vec<u16> = exe_path
-        .parent()
-        .unwrap_or_else(|| Path::new(""))
-        .as_os_str()
-        .encode_wide()
-        .chain(Some(0))
-        .collect();
+    crash::install_hook(log_path.with_file_name(CRASH_FILE_NAME));
+    if let Err(e) = PhaseSubscriber::init() {
+        warn!("Failed to set up phase timing: {}", e);
+    }
 
-    unsafe {
-        ShellExecuteW(
-            std::ptr::null_mut(),
-            operation.as_ptr(),
-            file.as_ptr(),
-            parameters.as_ptr(),
-            directory.as_ptr(),
-            SW_NORMAL,
-        );
+    info!("Starting PatchKit Runner, run ID {}", logging::run_id());
+    paths::sync_bundle_version();
+    if log_path != preferred_log_path {
+        info!("{} isn't writable, logging to {}", preferred_log_path.display(), log_path.display());
+    }
+    for (name, value) in [(LOG_LEVEL_ENV, log_level_env), (STDERR_LOG_LEVEL_ENV, stderr_level_env)] {
+        if let Some(value) = value.filter(|value| LogLevel::parse(value).is_none()) {
+            warn!("Ignoring invalid {} value: {}", name, value);
+        }
+    }
+    for arg in &args.unknown {
+        warn!("Ignoring unknown argument: {}", arg);
     }
 
-    std::process::exit(0);
-}
+    let settings_path = Settings::path()?;
+    let settings = load_settings(&settings_path);
+    let install_dir = install_dir(&settings);
+    for line in SystemInfo::collect(&install_dir).to_string().lines() {
+        info!("{}", line);
+    }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Get the log file path
-    let log_path = get_log_file_path()?;
-    let log_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_path);
-
-    // If we failed to create/open the log file and we're on Windows and not elevated
-    #[cfg(windows)]
-    if log_file.is_err() && !is_elevated() {
-        // Can't use info! here as logger isn't initialized yet
-        eprintln!("Failed to create log file, attempting to restart with admin privileges");
-        restart_as_admin()?;
+    // Crash reports are written by the panic hook and sent on the next start
+    if let Some(telemetry) = configured_telemetry() {
+        let crash_path = log_path.with_file_name(CRASH_FILE_NAME);
+        tokio::spawn(async move {
+            if let Err(e) = telemetry.upload_crash_report(&crash_path).await {
+                warn!("Failed to send crash report: {}", e);
+            }
+        });
+    }
+    let run_options = RunnerConfig {
+        settings: settings.clone(),
+        repair: args.repair,
+        force_update: args.force_update,
+        deep_link: args.deep_link.clone(),
+        app: args.app.clone(),
+        pinned_version: args.pin_version.clone(),
+        ..Default::default()
+    };
+
+    if args.uninstall {
+        info!("Uninstalling");
+        return uninstall(&settings);
+    }
+
+    if args.check_update {
+        info!("Checking for updates");
+        let check = check_update(args.pin_version.clone().or_else(|| settings.pinned_version.clone()), &settings).await?;
+        println!("{}", serde_json::to_string(&check)?);
         return Ok(());
     }
 
-    // Set up logging to both stderr and file if available
-    let mut builder = env_logger::Builder::from_env(
-        env_logger::Env::default().default_filter_or("info"),
-    );
-    builder.format_timestamp_millis();
+    if args.daemon {
+        info!("Running as a daemon");
+        let control = DownloadControl::default();
+        let shutdown = tokio::spawn(cancel_on_shutdown(control.cancellation()));
+        let result = Daemon::new(run_options).with_control(control).run().await;
+        shutdown.abort();
+        return result;
+    }
 
-    // If we successfully opened the log file, add it as a target
-    if let Ok(log_file) = log_file {
-        builder.target(env_logger::Target::Pipe(Box::new(log_file)));
+    let bundle_dir = log_path.parent().map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+    let bundle_sources = bundle_sources(&log_path, &settings_path, &install_dir);
+    if args.diagnose {
+        let bundle = bundle_sources.create(&bundle_dir)?;
+        println!("{}", bundle.display());
+        return Ok(());
     }
 
-    builder.init();
+    if args.self_test {
+        let self_test = SelfTest {
+            dat_path: paths::dat_path(),
+            install_dir,
+            patcher_dir: bundle_sources.patcher_dir.clone(),
+            network: proxied_network(&settings),
+        };
+        let checks = self_test.run().await;
+        print!("{}", self_test::table(&checks));
+        if checks.iter().any(|check| !check.passed) {
+            warn!("Self-test failed");
+            log::logger().flush();
+            std::process::exit(EXIT_FAILURE.into());
+        }
+        info!("Self-test passed");
+        return Ok(());
+    }
 
-    info!("Starting PatchKit Runner");
+    if args.no_gui {
+        return run_without_window(run_options).await;
+    }
 
-    let options = eframe::NativeOptions {
-        default_theme: eframe::Theme::Dark,
-        viewport: ViewportBuilder::default()
-            .with_inner_size([WINDOW_WIDTH, WINDOW_HEIGHT])
-            .with_resizable(false),
-        centered: true,
-        ..Default::default()
+    if args.silent {
+        info!("Running in silent mode");
+        return run_headless(run_options, LogProgress).await;
+    }
+
+    if !system::has_display() {
+        warn!("No display available, continuing without a window");
+        return run_without_window(run_options).await;
+    }
+
+    // The dat is read again by the runner, which reports errors in the UI
+    let launcher_data = match LauncherData::load(paths::dat_path()) {
+        Ok(launcher_data) => Some(launcher_data),
+        Err(e) => {
+            debug!("Dat file unavailable before UI start: {}", e);
+            None
+        }
     };
+    let branding = launcher_data.as_ref().map(|data| data.branding.clone()).unwrap_or_default();
+    let cache_dir = launcher_data.as_ref().and_then(|data| {
+        PathProvider::current()
+            .and_then(|paths| paths.cache_dir(paths.install_scope(app_slug(data)), app_slug(data)))
+            .ok()
+    });
+    let theme = settings.theme.or(branding.theme).unwrap_or_default();
+    let layout = Layout::from_branding(&branding);
+    let steamos = steamos::detect();
+    if let Some(session) = steamos {
+        info!("Running on SteamOS ({:?})", session);
+    }
+    let scale = args.scale.or(steamos.map(|_| steamos::SCALE));
+    let gaming_mode = steamos == Some(steamos::Session::Gaming);
+
+    // Window and GL context creation can take seconds on slow machines, cover it with a splash
+    let splash = Splash::show("PatchKit Runner", layout.width as i32, layout.height as i32);
+
+    let worker: Arc<Mutex<Option<JoinHandle<Result<()>>>>> = Arc::default();
+    let control = DownloadControl::default();
+    let setup = Rc::new(RefCell::new(Some(AppSetup {
+        scale,
+        gaming_mode,
+        layout,
+        settings,
+        settings_path,
+        theme,
+        strings: branding.strings,
+        splash,
+        log_buffer,
+        cache_dir,
+        bundle_sources,
+        bundle_dir,
+        run_options,
+        control: control.clone(),
+        worker: worker.clone(),
+    })));
 
     info!("Initializing UI");
+    let mut result = start_window(window_options(scale, layout, theme, args.software_render, gaming_mode), setup.clone());
+    if let Err(e) = &result {
+        // The app is only created once the renderer is up, so it's still pending if that failed
+        if !args.software_render && setup.borrow().is_some() {
+            warn!("Failed to start the renderer ({}), retrying with software rendering", e);
+            result = start_window(window_options(scale, layout, theme, true, gaming_mode), setup.clone());
+        }
+    }
+    if let Err(e) = &result {
+        // Without a renderer, e.g. over some remote desktops, the update can still run
+        let pending = setup.borrow_mut().take();
+        if let Some(pending) = pending {
+            warn!("Failed to open the window ({}), continuing without it", e);
+            return run_without_window(pending.run_options).await;
+        }
+    }
+    result.map_err(|e| runner2::Error::Other(e.to_string()))?;
+
+    let worker = worker.lock().unwrap().take();
+    worker_outcome(worker, &control)
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Log location in the user's local data directory, per app if the dat is readable.
+fn fallback_log_file_path() -> Option<PathBuf> {
+    let launcher_data = LauncherData::load(paths::dat_path()).ok();
+    let log_dir = PathProvider::current()
+        .and_then(|paths| paths.log_dir(InstallScope::User, launcher_data.as_ref().map(app_slug)))
+        .ok()?;
+    Some(log_dir.join(LOG_FILE_NAME))
+}
+
+/// Sets up logging to the log file, or stderr if it couldn't be opened. Without a `level`,
+/// `RUST_LOG` is respected for development. The latest lines are also kept in memory for
+/// the window's log view.
+fn init_logging(level: Option<LogLevel>, stderr_level: LogLevel, log_file: std::io::Result<File>) -> LogBuffer {
+    let file_builder = || match level {
+        Some(level) => {
+            let mut builder = env_logger::Builder::new();
+            builder.parse_filters(level.filter());
+            builder
+        },
+        None => env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")),
+    };
+    let mut stderr_builder = env_logger::Builder::new();
+    stderr_builder.parse_filters(stderr_level.filter());
+
+    // The buffer shown in the window follows the file, or stderr when there's no file
+    let log_buffer = LogBuffer::new(LOG_BUFFER_LINES);
+    let mut sinks = Vec::new();
+    match log_file {
+        Ok(log_file) => {
+            let mut builder = file_builder();
+            builder.target(env_logger::Target::Pipe(Box::new(log_buffer.writer(log_file))));
+            sinks.push(builder);
+            stderr_builder.target(env_logger::Target::Stderr);
+        },
+        Err(_) => {
+            stderr_builder.target(env_logger::Target::Pipe(Box::new(log_buffer.writer(std::io::stderr()))));
+        },
+    };
+    sinks.push(stderr_builder);
+
+    let sinks = sinks
+        .into_iter()
+        .map(|mut builder| builder.format(logging::format_record).build())
+        .collect();
+    if let Err(e) = TeeLogger::new(sinks).init() {
+        eprintln!("Failed to initialize logging: {}", e);
+    }
+    log_buffer
+}
+
+/// Everything needed to create the app once the window is up.
+struct AppSetup {
+    scale: Option<f32>,
+    gaming_mode: bool,
+    layout: Layout,
+    settings: Settings,
+    settings_path: PathBuf,
+    theme: ThemeMode,
+    strings: LocalizedStrings,
+    splash: Option<Splash>,
+    log_buffer: LogBuffer,
+    cache_dir: Option<PathBuf>,
+    bundle_sources: BundleSources,
+    bundle_dir: PathBuf,
+    run_options: RunnerConfig,
+    control: DownloadControl,
+    worker: Arc<Mutex<Option<JoinHandle<Result<()>>>>>,
+}
+
+fn window_options(
+    scale: Option<f32>,
+    layout: Layout,
+    theme: ThemeMode,
+    software_render: bool,
+    fullscreen: bool,
+) -> eframe::NativeOptions {
+    let scale = scale.unwrap_or(1.0);
+    let viewport = ViewportBuilder::default()
+        // Logical size; the OS scale factor is applied by the windowing backend
+        .with_inner_size([layout.width * scale, layout.height * scale])
+        .with_resizable(false)
+        // The update continues while minimized, with progress on the taskbar button
+        .with_minimize_button(true);
+    let mut options = eframe::NativeOptions {
+        // gamescope shows every window fullscreen without a title bar, ask for that up front
+        viewport: if fullscreen { viewport.with_fullscreen(true).with_decorations(false) } else { viewport },
+        centered: !fullscreen,
+        ..Default::default()
+    };
+    if software_render {
+        info!("Using software rendering");
+        // Old or broken drivers fail to create a hardware context, or render a blank window
+        options.hardware_acceleration = HardwareAcceleration::Off;
+        options.multisampling = 0;
+        #[cfg(target_os = "linux")]
+        env::set_var("LIBGL_ALWAYS_SOFTWARE", "1");
+    }
+    ui::apply_theme(&mut options, theme);
+    options
+}
+
+/// Runs the window until it's closed. The app is taken out of `setup` once the renderer
+/// started, so a failed attempt leaves it there for a retry.
+fn start_window(options: eframe::NativeOptions, setup: Rc<RefCell<Option<AppSetup>>>) -> eframe::Result<()> {
     eframe::run_native(
         "PatchKit Runner",
         options,
-        Box::new(|cc| {
-            let app = RunnerApp::new(cc);
-            let sender = app.sender();
-            
+        Box::new(move |cc| {
+            let setup = setup.borrow_mut().take().expect("the app is created only once");
+            let control = setup.control;
+            let mut app = RunnerApp::new(cc, setup.scale)
+                .with_download_control(control.clone())
+                .with_settings(setup.settings, setup.settings_path, setup.theme)
+                .with_layout(setup.layout)
+                .with_strings(setup.strings)
+                .with_gaming_mode(setup.gaming_mode)
+                .with_diagnostics(setup.bundle_sources, setup.bundle_dir)
+                .with_splash(setup.splash)
+                .with_log(setup.log_buffer);
+            if let Some(cache_dir) = setup.cache_dir {
+                app = app.with_cache_dir(cache_dir);
+            }
+            let sender = app.relay_sender(&cc.egui_ctx);
+            let run_options = setup.run_options;
+
             info!("Spawning runner logic thread");
-            std::thread::spawn(move || {
-                if let Err(e) = Runtime::new()
-                    .unwrap()
-                    .block_on(run_launcher(sender.clone()))
-                {
-                    error!("Runner error: {}", e);
-                    let _ = sender.send(UiMessage::ShowError(e.to_string()));
+            let handle = std::thread::spawn(move || {
+                // A panic is reported in the window, which would otherwise keep showing progress
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    Runtime::new()
+                        .unwrap()
+                        .block_on(Runner::new(run_options).with_control(control).run(sender.clone()))
+                }))
+                .unwrap_or_else(|_| {
+                    Err(runner2::Error::Other(format!(
+                        "The runner crashed unexpectedly. Details were saved to {}",
+                        CRASH_FILE_NAME
+                    )))
+                });
+                if let Err(e) = &result {
+                    error!("Runner error: {}", e.report());
+                    let _ = sender.send(UiMessage::ShowError(DisplayError::new(e)));
                 }
+                result
             });
-            
+            *setup.worker.lock().unwrap() = Some(handle);
+
             Box::new(app)
         }),
     )
-    .map_err(|e| runner2::Error::Other(e.to_string()))?;
+}
+
+/// Result of the runner logic once the window is closed. The logic closes the window right
+/// before it finishes, so it gets a moment to return; otherwise the user closed the window early
+/// and the update is cancelled.
+fn worker_outcome(worker: Option<JoinHandle<Result<()>>>, control: &DownloadControl) -> Result<()> {
+    let Some(worker) = worker else {
+        return Ok(());
+    };
+    if !worker.is_finished() {
+        control.cancel();
+    }
+
+    let deadline = Instant::now() + WORKER_EXIT_GRACE;
+    while !worker.is_finished() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    if worker.is_finished() {
+        worker.join().unwrap_or_else(|_| Err(runner2::Error::Other("Runner logic panicked".into())))
+    } else {
+        Err(runner2::Error::Cancelled("Window closed before the update finished".into()))
+    }
+}
 
-    Ok(())
+/// Files that go into a support bundle.
+fn bundle_sources(log_path: &Path, settings_path: &Path, install_dir: &Path) -> BundleSources {
+    let patcher_dir = LauncherData::load(paths::dat_path())
+        .ok()
+        .and_then(|launcher_data| FileManager::get_patcher_dir(app_slug(&launcher_data)).ok());
+    BundleSources {
+        log_path: Some(log_path.to_path_buf()),
+        crash_path: Some(log_path.with_file_name(CRASH_FILE_NAME)),
+        settings_path: Some(settings_path.to_path_buf()),
+        dat_path: Some(paths::dat_path()),
+        patcher_dir,
+        install_dir: Some(install_dir.to_path_buf()),
+    }
 }
 
-async fn run_launcher(sender: Sender<UiMessage>) -> Result<()> {
-    // Initialize components
-    info!("Initializing components");
-    let network = NetworkManager::new();
-    
-    // Read the .dat file first to get the app secret
-    info!("Reading launcher.dat file");
-    let dat_file = std::fs::File::open("launcher.dat")
-        .map_err(|e| {
-            error!("Failed to open launcher.dat: {}", e);
-            runner2::Error::DatFile(format!("Failed to open launcher.dat: {}", e))
-        })?;
-    let launcher_data = LauncherData::from_binary(dat_file)?;
-    info!("Successfully read launcher.dat");
-    
-    // Initialize file manager with the first 8 chars of app secret
-    let app_slug = &launcher_data.app_secret[..8];
+/// Where the app is installed, as far as it can be told before the update starts.
+fn install_dir(settings: &Settings) -> PathBuf {
+    if let Some(install_dir) = &settings.install_dir {
+        return install_dir.clone();
+    }
+    LauncherData::load(paths::dat_path())
+        .ok()
+        .and_then(|launcher_data| FileManager::new(app_slug(&launcher_data)).ok())
+        .map(|file_manager| file_manager.get_install_dir().to_path_buf())
+        .or_else(|| env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Network manager using the proxy from the settings, or the system's when that one is broken.
+fn proxied_network(settings: &Settings) -> NetworkManager {
+    let Some(proxy) = &settings.proxy else {
+        return NetworkManager::new();
+    };
+    NetworkManager::new().with_proxy(proxy).unwrap_or_else(|e| {
+        warn!("Ignoring the proxy from the settings: {}", e);
+        NetworkManager::new()
+    })
+}
+
+/// Network manager signing its requests if the dat asks for it.
+fn network_for(launcher_data: &LauncherData, settings: &Settings) -> NetworkManager {
+    let mut network = proxied_network(settings);
+    if !settings.disable_certificate_pinning {
+        network = network.with_certificate_pins(launcher_data.api_certificate_pins.clone());
+    }
+    if launcher_data.sign_requests {
+        return network.with_request_signing(&launcher_data.app_secret);
+    }
+    network
+}
+
+/// Sends the anonymous error event and the developer's error report, as far as allowed.
+async fn report_fatal_error(e: &runner2::Error) {
+    let settings = Settings::path().map(|path| load_settings(&path)).unwrap_or_default();
+    let Ok(launcher_data) = LauncherData::load(paths::dat_path()) else {
+        return;
+    };
+
+    if settings.share_error_statistics.unwrap_or(true) {
+        let event = ErrorEvent::new(e, app_slug(&launcher_data));
+        if let Err(report_error) = network_for(&launcher_data, &settings).report_error_event(&event).await {
+            warn!("Failed to send error event: {}", report_error);
+        }
+    }
+    if let Some(telemetry) = Telemetry::new(launcher_data.telemetry.as_ref(), &settings) {
+        if let Err(report_error) = telemetry.send(&ErrorReport::from_error(e)).await {
+            warn!("Failed to send error report: {}", report_error);
+        }
+    }
+}
+
+/// Error reporting, if both the dat and the user allow it.
+fn configured_telemetry() -> Option<Telemetry> {
+    let settings = load_settings(&Settings::path().ok()?);
+    let launcher_data = LauncherData::load(paths::dat_path()).ok()?;
+    Telemetry::new(launcher_data.telemetry.as_ref(), &settings)
+}
+
+/// Loads the user settings, falling back to defaults so a broken file never blocks the update.
+fn load_settings(path: &Path) -> Settings {
+    match Settings::load(path) {
+        Ok(settings) => settings,
+        Err(e) => {
+            warn!("Using default settings: {}", e);
+            Settings::default()
+        }
+    }
+}
+
+/// Removes the installed app. The installed apps list doesn't run the runner from its directory,
+/// so the dat is looked up next to the runner.
+fn uninstall(settings: &Settings) -> Result<()> {
+    // Run from the installed apps list, so the working directory isn't the runner's
+    let dat_path = match paths::bundle_dir() {
+        Some(_) => paths::dat_path(),
+        None => paths::exe_dir()?.join(DAT_FILE_NAME),
+    };
+    let launcher_data = LauncherData::load(dat_path)?;
+    let app_slug = app_slug(&launcher_data);
     let mut file_manager = FileManager::new(app_slug)?;
-    let launcher = Launcher::new();
-    let extract_path = FileManager::get_patcher_dir(app_slug)?;
+    if let Some(install_dir) = &settings.install_dir {
+        file_manager = file_manager.with_install_dir(install_dir.clone());
+    }
+    uninstall::uninstall(&file_manager, app_slug)?;
 
-    // Check network connection
-    info!("Checking network connection");
-    sender.send(UiMessage::SetStatus("Checking network connection...".into()))
-        .map_err(|e| runner2::Error::Other(e.to_string()))?;
+    // A runner that couldn't write next to itself installed for the current user
+    let (install_dir, patcher_dir) = FileManager::per_user_dirs(app_slug)?;
+    if install_dir.exists() || patcher_dir.exists() {
+        let file_manager = FileManager::new(app_slug)?.with_install_dir(install_dir).with_patcher_dir(patcher_dir);
+        uninstall::uninstall(&file_manager, app_slug)?;
+    }
+    PathProvider::current()?.record_install_scope(app_slug, InstallScope::Portable)
+}
 
-    if !network.check_connection().await? {
-        return Err(runner2::Error::Other("No internet connection".into()));
+/// Compares the installed version with the latest or `pinned` one without changing anything.
+async fn check_update(pinned: Option<String>, settings: &Settings) -> Result<UpdateCheck> {
+    let launcher_data = LauncherData::load(paths::dat_path())?;
+    let mut network = network_for(&launcher_data, settings);
+    if launcher_data.verify_responses {
+        network = network.with_response_verification(ResponseVerifier::built_in()?);
     }
-    info!("Network connection established");
+    let file_manager = FileManager::new(app_slug(&launcher_data))?;
 
-    // Get app info to determine the correct patcher secret
-    info!("Fetching app info");
-    sender.send(UiMessage::SetStatus("Fetching app info...".into()))
-        .map_err(|e| runner2::Error::Other(e.to_string()))?;
     let app_info = network.get_app_info(&launcher_data.app_secret).await?;
-    info!("Got app info: {:?}", app_info);
-
-    // Determine which patcher secret to use
     let patcher_secret = app_info.patcher_secret
         .unwrap_or_else(|| launcher_data.patcher_secret.clone());
-    info!("Using patcher secret: {}", patcher_secret);
-
-    // Get latest version
-    info!("Fetching latest version");
-    sender.send(UiMessage::SetStatus("Fetching latest version...".into()))
-        .map_err(|e| runner2::Error::Other(e.to_string()))?;
-    let version = network.get_latest_version(&patcher_secret).await?;
-    info!("Latest version: {}", version);
-
-    // Check if we need to update
-    info!("Checking if update is needed");
-    if !file_manager.needs_update(&version, &patcher_secret)? {
-        info!("Already have the latest version {}, skipping update", version);
-        
-        // Launch the existing version
-        launch_from_manifest(&extract_path, &file_manager, &launcher_data, &launcher, &sender)?;
-        return Ok(());
-    }
-    info!("Update needed to version {}", version);
-
-    // Get download URLs
-    info!("Getting download URLs");
-    sender.send(UiMessage::SetStatus("Getting download URLs...".into()))
-        .map_err(|e| runner2::Error::Other(e.to_string()))?;
-    let content_urls = network
-        .get_content_urls(&patcher_secret, &version)
-        .await?;
-
-    if let Some(content) = content_urls.first() {
-        info!("Found content URL: {}", content.url);
-        
-        // Download launcher package
-        info!("Downloading launcher package");
-        sender.send(UiMessage::SetStatus("Downloading launcher...".into()))
-            .map_err(|e| runner2::Error::Other(e.to_string()))?;
-        
-        // Create a temporary file for download
-        let temp_file = tempfile::Builder::new()
-            .prefix("launcher")
-            .suffix(".zip")
-            .tempfile()
-            .map_err(|e| runner2::Error::Other(format!("Failed to create temporary file: {}", e)))?;
-        let download_path = temp_file.path().to_path_buf();
-        
-        let sender_clone = sender.clone();
-        network.download_file(&content.url, &download_path, move |progress| {
-            let percentage = if progress.total_bytes > 0 {
-                progress.bytes as f32 / progress.total_bytes as f32
-            } else {
-                0.0
-            };
-            let _ = sender_clone.send(UiMessage::SetDownloadProgress {
-                progress: percentage,
-                speed_kbps: progress.speed_kbps,
-            });
-        }).await?;
-        
-        info!("Download complete: {}", download_path.display());
-
-        // Extract package
-        info!("Extracting launcher package");
-        sender.send(UiMessage::SetStatus("Extracting launcher...".into()))
-            .map_err(|e| runner2::Error::Other(e.to_string()))?;
-        
-        // Remove old files before extracting new ones
-        info!("Removing old files");
-        file_manager.remove_old_files()?;
-        
-        // Extract to Patcher directory in the install directory
-        let extract_path = FileManager::get_patcher_dir(app_slug)?;
-        file_manager.extract_zip(&download_path, &extract_path)?;
-        info!("Extraction complete: {}", extract_path.display());
-
-        // Save the current version
-        info!("Saving version information");
-        file_manager.save_version(&version, &patcher_secret)?;
-        info!("Version {} saved", version);
-
-        // Clean up the temporary file
-        if let Err(e) = temp_file.close() {
-            warn!("Failed to remove temporary file: {}", e);
-            // Non-critical error, continue execution
-        }
+    let latest_version = match pinned {
+        Some(pinned) => pinned,
+        None => network.get_latest_version(&patcher_secret).await?,
+    };
 
-        // Launch the new version
-        launch_from_manifest(&extract_path, &file_manager, &launcher_data, &launcher, &sender)?;
+    Ok(UpdateCheck {
+        update_available: file_manager.needs_update(&latest_version, &patcher_secret)?,
+        installed_version: file_manager.get_current_version()?.map(|info| info.version),
+        latest_version,
+    })
+}
+
+/// Runs the launcher without a window, showing progress in the terminal if there is one and
+/// logging it otherwise.
+async fn run_without_window(config: RunnerConfig) -> Result<()> {
+    if std::io::stdout().is_terminal() {
+        info!("Running in terminal mode");
+        run_headless(config, Mutex::new(TerminalProgress::new(std::io::stdout()))).await
     } else {
-        warn!("No content URLs found");
+        info!("Running in silent mode");
+        run_headless(config, LogProgress).await
     }
-
-    info!("Runner completed successfully");
-    Ok(())
 }
 
-fn launch_from_manifest(
-    extract_path: &std::path::Path,
-    file_manager: &FileManager,
-    launcher_data: &LauncherData,
-    launcher: &Launcher,
-    sender: &Sender<UiMessage>,
-) -> Result<()> {
-    // Read manifest
-    info!("Reading manifest file {}", extract_path.join("patcher.manifest").display());
-    let manifest_path = extract_path.join("patcher.manifest");
-    let manifest_content = std::fs::read_to_string(&manifest_path)
-        .map_err(|e| {
-            error!("Failed to read manifest: {}", e);
-            runner2::Error::Manifest(format!("Failed to read manifest: {}", e))
-        })?;
-    let mut manifest = ManifestManager::new(&manifest_content)?;
-    info!("Successfully read manifest");
-
-    // Set up manifest variables
-    info!("Setting up manifest variables");
-    manifest.set_variable("exedir", extract_path.to_string_lossy().into());
-    manifest.set_variable("installdir", file_manager.get_install_dir().to_string_lossy().into());
-    let encoded_secret = config::secret::encode_secret(&launcher_data.app_secret);
-    manifest.set_variable("secret", encoded_secret);
-    manifest.set_variable("lockfile", "launcher.lock".into());
-    manifest.set_variable("network-status", "online".into());
-
-    // Launch the executable
-    info!("Launching executable");
-    sender.send(UiMessage::SetStatus("Launching...".into()))
-        .map_err(|e| runner2::Error::Other(e.to_string()))?;
-    let target = manifest.get_target()?;
-    let arguments = manifest.get_arguments()?;
-    info!("Launching {} with arguments: {:?}", target.display(), arguments);
-    launcher.launch_executable(target, &arguments)?;
-    info!("Launcher started successfully");
-
-    sender.send(UiMessage::SetProgress(1.0))
-        .map_err(|e| runner2::Error::Other(e.to_string()))?;
-    sender.send(UiMessage::Close)
-        .map_err(|e| runner2::Error::Other(e.to_string()))?;
-    Ok(())
+/// Runs the launcher without a window, passing every progress message to `handle`.
+async fn run_headless<S: ProgressSink>(config: RunnerConfig, sink: S) -> Result<()> {
+    let control = DownloadControl::default();
+    let shutdown = tokio::spawn(cancel_on_shutdown(control.cancellation()));
+    let result = Runner::new(config).with_control(control).run(sink).await;
+    shutdown.abort();
+
+    if let Err(e) = &result {
+        error!("Runner error: {}", e.report());
+        if matches!(e.root(), runner2::Error::Permission(_)) && elevation::can_elevate() {
+            info!("Starting the runner as administrator may help");
+        }
+    }
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::mpsc::channel;
     use std::fs;
+    use std::sync::mpsc::channel;
     use tempfile::TempDir;
     use std::io::Write;
     use log::LevelFilter;
@@ -380,6 +652,18 @@ mod tests {
         assert!(matches!(rx.recv().unwrap(), UiMessage::SetProgress(0.5)));
     }
 
+    #[test]
+    fn test_open_log_file_creates_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("PatchKit").join("Apps").join("my-app").join(LOG_FILE_NAME);
+        open_log_file(&log_path).unwrap();
+        assert!(log_path.exists());
+
+        let fallback_path = fallback_log_file_path().unwrap();
+        assert!(fallback_path.ends_with(LOG_FILE_NAME));
+        assert!(fallback_path.components().any(|c| c.as_os_str() == "PatchKit"));
+    }
+
     #[test]
     fn test_log_file_creation() {
         // Create a temporary directory for testing
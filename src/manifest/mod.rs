@@ -1,14 +1,71 @@
+use crate::system::arch;
 use crate::Result;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
+/// Newest `manifest_version` the runner understands.
+pub const MANIFEST_VERSION: i32 = 4;
+
 #[derive(Debug, Deserialize)]
 pub struct Manifest {
     pub manifest_version: i32,
     pub target: String,
+    /// Native patcher builds by architecture, e.g. `"arm64"`, preferred over `target` which may
+    /// run emulated.
+    #[serde(default)]
+    pub arch_targets: HashMap<String, String>,
     pub target_arguments: Vec<TargetArgument>,
     pub capabilities: Vec<String>,
+    /// Runner versions the patcher works with.
+    #[serde(default)]
+    pub runner_compatibility: Option<RunnerCompatibility>,
+    /// Makes the runner wait for the target to exit, so it can ask to be run again with
+    /// [`crate::launcher::RESTART_EXIT_CODE`].
+    #[serde(default)]
+    pub monitor: bool,
+}
+
+/// Runner versions a patcher declares it works with, both bounds inclusive.
+#[derive(Debug, Deserialize)]
+pub struct RunnerCompatibility {
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// Refuses to launch with other runners instead of only warning.
+    #[serde(default)]
+    pub block: bool,
+}
+
+impl RunnerCompatibility {
+    fn accepts(&self, runner_version: &str) -> bool {
+        let Some(version) = parse_version(runner_version) else {
+            return true;
+        };
+        let at_least_min = self.min.as_deref().and_then(parse_version).map_or(true, |min| version >= min);
+        let at_most_max = self.max.as_deref().and_then(parse_version).map_or(true, |max| version <= max);
+        at_least_min && at_most_max
+    }
+
+    fn range(&self) -> String {
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) => format!("{} to {}", min, max),
+            (Some(min), None) => format!("{} or later", min),
+            (None, Some(max)) => format!("up to {}", max),
+            (None, None) => "any".into(),
+        }
+    }
+}
+
+/// Numeric components of a version like `1.2.0`, compared component by component. Pre-release
+/// and build suffixes are ignored.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let core = version.trim().trim_start_matches('v').split(['-', '+']).next()?;
+    let mut components: Vec<u64> = core.split('.').map(|component| component.parse().ok()).collect::<Option<_>>()?;
+    // 1.2 is the same as 1.2.0
+    while components.len() > 1 && components.last() == Some(&0) {
+        components.pop();
+    }
+    Some(components)
 }
 
 #[derive(Debug, Deserialize)]
@@ -20,6 +77,8 @@ pub struct TargetArgument {
 pub struct ManifestManager {
     manifest: Manifest,
     variables: HashMap<String, String>,
+    /// Variables that may be unset, leaving out the arguments that use them.
+    optional: HashSet<String>,
 }
 
 impl ManifestManager {
@@ -28,6 +87,7 @@ impl ManifestManager {
         Ok(Self {
             manifest,
             variables: HashMap::new(),
+            optional: HashSet::new(),
         })
     }
 
@@ -35,15 +95,69 @@ impl ManifestManager {
         self.variables.insert(key.to_string(), value);
     }
 
+    /// Sets a variable only some runs have. Without a value, every argument group using it is
+    /// left out instead of failing as unresolved.
+    pub fn set_optional_variable(&mut self, key: &str, value: Option<String>) {
+        self.optional.insert(key.to_string());
+        match value {
+            Some(value) => self.set_variable(key, value),
+            None => {
+                self.variables.remove(key);
+            },
+        }
+    }
+
+    /// Whether the runner waits for the target to exit.
+    pub fn monitors(&self) -> bool {
+        self.manifest.monitor
+    }
+
     pub fn get_target(&self) -> Result<PathBuf> {
-        let target = self.resolve_variables(&self.manifest.target)?;
+        let target = self.resolve_variables(self.target_for(arch::compatible_here()))?;
         Ok(PathBuf::from(target))
     }
 
+    /// The target of the best of the `compatible` architectures, or the default one.
+    fn target_for(&self, compatible: &[&str]) -> &str {
+        compatible
+            .iter()
+            .find_map(|arch| {
+                self.manifest
+                    .arch_targets
+                    .iter()
+                    .find(|(name, _)| arch::matches(name, arch))
+                    .map(|(_, target)| target.as_str())
+            })
+            .unwrap_or(&self.manifest.target)
+    }
+
+    /// Checks the patcher declares it works with `runner_version`. Known-incompatible versions
+    /// fail if the patcher blocks them and yield a warning otherwise.
+    pub fn check_runner_version(&self, runner_version: &str) -> Result<Option<String>> {
+        let Some(compatibility) = &self.manifest.runner_compatibility else {
+            return Ok(None);
+        };
+        if compatibility.accepts(runner_version) {
+            return Ok(None);
+        }
+        let message = format!(
+            "The launcher supports runner versions {}, this runner is version {}",
+            compatibility.range(),
+            runner_version
+        );
+        if compatibility.block {
+            return Err(crate::Error::Manifest(message));
+        }
+        Ok(Some(message))
+    }
+
     pub fn get_arguments(&self) -> Result<Vec<String>> {
         let mut resolved_args = Vec::new();
         
         for arg in &self.manifest.target_arguments {
+            if arg.value.iter().any(|value| self.uses_unset_optional(value)) {
+                continue;
+            }
             for value in &arg.value {
                 let resolved = self.resolve_variables(value)?;
                 resolved_args.push(resolved);
@@ -53,6 +167,12 @@ impl ManifestManager {
         Ok(resolved_args)
     }
 
+    fn uses_unset_optional(&self, value: &str) -> bool {
+        self.optional
+            .iter()
+            .any(|key| !self.variables.contains_key(key) && value.contains(&format!("{{{}}}", key)))
+    }
+
     fn resolve_variables(&self, input: &str) -> Result<String> {
         let mut result = input.to_string();
         
@@ -114,6 +234,62 @@ mod tests {
         assert_eq!(args[3], "/path/to/lock");
     }
 
+    #[test]
+    fn test_optional_variables() {
+        let manifest = r#"{
+            "manifest_version": 4,
+            "target": "Patcher.exe",
+            "target_arguments": [
+                { "value": ["--join", "{passthrough}"] },
+                { "value": ["--online"] }
+            ],
+            "capabilities": []
+        }"#;
+        let mut manager = ManifestManager::new(manifest).unwrap();
+        manager.set_optional_variable("passthrough", None);
+        assert_eq!(manager.get_arguments().unwrap(), vec!["--online"]);
+
+        manager.set_optional_variable("passthrough", Some("invite=AB12CD".into()));
+        assert_eq!(manager.get_arguments().unwrap(), vec!["--join", "invite=AB12CD", "--online"]);
+    }
+
+    #[test]
+    fn test_target_for() {
+        let manifest = r#"{
+            "manifest_version": 4,
+            "target": "{exedir}/Patcher.exe",
+            "arch_targets": { "arm64": "{exedir}/arm64/Patcher.exe" },
+            "target_arguments": [],
+            "capabilities": []
+        }"#;
+        let manager = ManifestManager::new(manifest).unwrap();
+        assert_eq!(manager.target_for(&["aarch64", "x86_64"]), "{exedir}/arm64/Patcher.exe");
+        assert_eq!(manager.target_for(&["x86_64", "x86"]), "{exedir}/Patcher.exe");
+
+        let manager = ManifestManager::new(SAMPLE_MANIFEST).unwrap();
+        assert_eq!(manager.target_for(&["aarch64", "x86_64"]), "{exedir}/Patcher.exe");
+    }
+
+    #[test]
+    fn test_check_runner_version() {
+        let manifest = |compatibility: &str| {
+            ManifestManager::new(&format!(
+                r#"{{"manifest_version": 4, "target": "Patcher.exe", "target_arguments": [], "capabilities": [], "runner_compatibility": {}}}"#,
+                compatibility
+            ))
+            .unwrap()
+        };
+        let range = manifest(r#"{"min": "0.2", "max": "1.4.0"}"#);
+        assert_eq!(range.check_runner_version("0.2.0").unwrap(), None);
+        assert_eq!(range.check_runner_version("1.4.0-beta").unwrap(), None);
+        assert_eq!(
+            range.check_runner_version("0.1.9").unwrap().as_deref(),
+            Some("The launcher supports runner versions 0.2 to 1.4.0, this runner is version 0.1.9")
+        );
+        assert!(manifest(r#"{"min": "1.10.0", "block": true}"#).check_runner_version("1.9.3").is_err());
+        assert_eq!(ManifestManager::new(SAMPLE_MANIFEST).unwrap().check_runner_version("0.1.0").unwrap(), None);
+    }
+
     #[test]
     fn test_unresolved_variables() {
         let manager = ManifestManager::new(SAMPLE_MANIFEST).unwrap();
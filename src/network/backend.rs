@@ -0,0 +1,82 @@
+//! Backends a part of a package can be downloaded with. The content URLs list the transports a
+//! part offers besides its URLs, e.g. a torrent using them as web seeds to take load off the CDN.
+//! HTTP(S) is what every part falls back to.
+
+use super::{AppApi, ContentUrl, ProgressCallback};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A way a part can be delivered, from the optional "transports" of its content URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Http,
+    /// BitTorrent, with the part's URLs as web seeds, see [`super::torrent`]. Needs the part's
+    /// "torrent".
+    Torrent,
+    /// A transport this runner doesn't know, offered for newer ones.
+    #[serde(other)]
+    Other,
+}
+
+/// Downloads parts over one transport.
+// The pipeline is run on the caller's task, so these futures needn't be `Send`
+#[allow(async_fn_in_trait)]
+pub trait Downloader {
+    fn transport(&self) -> Transport;
+
+    /// Downloads `part` to `path` starting from `url`, one of its URLs, continuing what `path`
+    /// already holds.
+    async fn download(&self, part: &ContentUrl, url: &str, path: &Path, progress: ProgressCallback) -> Result<()>;
+}
+
+/// Downloads parts from the CDN.
+pub struct HttpDownloader<'a, A>(pub &'a A);
+
+impl<A: AppApi> Downloader for HttpDownloader<'_, A> {
+    fn transport(&self) -> Transport {
+        Transport::Http
+    }
+
+    async fn download(&self, _part: &ContentUrl, url: &str, path: &Path, progress: ProgressCallback) -> Result<()> {
+        self.0.download_file(url, path, progress).await
+    }
+}
+
+/// The transport `part` is downloaded with: the first it offers that one of the `available`
+/// backends has, or HTTP.
+pub fn select_transport(part: &ContentUrl, available: &[Transport]) -> Transport {
+    part.transports
+        .iter()
+        .copied()
+        .filter(|&transport| transport != Transport::Torrent || part.torrent.is_some())
+        .find(|transport| available.contains(transport))
+        .unwrap_or(Transport::Http)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_transport() {
+        let part: ContentUrl = serde_json::from_str(
+            r#"{"size": 1, "url": "https://cdn.example.com/1.zip", "transports": ["ipfs", "torrent", "http"], "torrent": "https://cdn.example.com/1.zip.torrent"}"#,
+        )
+        .unwrap();
+        assert_eq!(part.transports, [Transport::Other, Transport::Torrent, Transport::Http]);
+        assert_eq!(select_transport(&part, &[Transport::Http]), Transport::Http);
+        assert_eq!(select_transport(&part, &[Transport::Http, Transport::Torrent]), Transport::Torrent);
+
+        let part: ContentUrl = serde_json::from_str(r#"{"size": 1, "url": "https://cdn.example.com/1.zip"}"#).unwrap();
+        assert_eq!(select_transport(&part, &[Transport::Torrent]), Transport::Http);
+
+        // A torrent transport without its torrent can't be used
+        let part: ContentUrl = serde_json::from_str(
+            r#"{"size": 1, "url": "https://cdn.example.com/1.zip", "transports": ["torrent"]}"#,
+        )
+        .unwrap();
+        assert_eq!(select_transport(&part, &[Transport::Http, Transport::Torrent]), Transport::Http);
+    }
+}
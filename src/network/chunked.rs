@@ -0,0 +1,343 @@
+//! Downloads large files over several connections at once, each fetching a range of the file
+//! into its own chunk file. The chunks are joined once all of them are complete.
+
+use super::{
+    check_stalled, content_range, should_report, stall_duration, DownloadProgress, NetworkManager, STALL_CHECK_INTERVAL,
+};
+use super::speed::SpeedMeter;
+use crate::error::ResultExt;
+use crate::format::format_size;
+use crate::Result;
+use futures_util::future::try_join_all;
+use futures_util::StreamExt;
+use log::{debug, info};
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Files smaller than this are downloaded over one connection.
+pub const MIN_CHUNKED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Progress over all chunks, reported as one download.
+struct ChunkProgress<F> {
+    callback: Mutex<F>,
+    downloaded: AtomicU64,
+    total: u64,
+    speed: Mutex<SpeedMeter>,
+    last_report: Mutex<Option<Instant>>,
+    last_data: Mutex<Instant>,
+}
+
+impl<F: Fn(DownloadProgress)> ChunkProgress<F> {
+    fn add(&self, bytes: u64) {
+        let downloaded = self.downloaded.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        let now = Instant::now();
+        *self.last_data.lock().unwrap() = now;
+        let mut last_report = self.last_report.lock().unwrap();
+        if should_report(*last_report, now, downloaded >= self.total) {
+            *last_report = Some(now);
+            self.report(downloaded, None);
+        }
+    }
+
+    /// Reports a stall once no chunk has received data for a while.
+    fn check_stall(&self) {
+        let since_last_data = self.last_data.lock().unwrap().elapsed();
+        if let Some(stalled_for) = stall_duration(since_last_data) {
+            debug!("No data received for {:?}", stalled_for);
+            self.report(self.downloaded.load(Ordering::SeqCst), Some(stalled_for));
+        }
+    }
+
+    fn report(&self, downloaded: u64, stalled_for: Option<std::time::Duration>) {
+        let speed_kbps = match stalled_for {
+            Some(_) => 0.0,
+            None => self.speed.lock().unwrap().record(Instant::now(), downloaded),
+        };
+        (self.callback.lock().unwrap())(DownloadProgress {
+            bytes: downloaded,
+            total_bytes: self.total,
+            speed_kbps,
+            stalled_for,
+        });
+    }
+}
+
+/// Splits `total` bytes into at most `count` ranges of about the same size.
+pub fn chunk_ranges(total: u64, count: usize) -> Vec<Range<u64>> {
+    let size = total.div_ceil(count.max(1) as u64).max(1);
+    (0..count as u64)
+        .map(|index| (index * size).min(total)..((index + 1) * size).min(total))
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+/// Where the `index`th chunk of the download to `path` is kept until the chunks are joined.
+pub fn chunk_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".chunk{}", index));
+    PathBuf::from(name)
+}
+
+/// Where the size and chunk count of the download to `path` are kept, as `<total>/<count>`. A
+/// chunk only holds the range it was started for under the same layout.
+fn layout_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".chunks");
+    PathBuf::from(name)
+}
+
+/// Removes the chunks of the download to `path` unless they were started for `total` bytes in
+/// `count` chunks, e.g. after `download_connections` changed, and records that layout.
+fn check_layout(path: &Path, total: u64, count: usize) -> Result<()> {
+    let layout = format!("{}/{}", total, count);
+    let layout_path = layout_path(path);
+    if fs::read_to_string(&layout_path).ok().as_deref() == Some(layout.as_str()) {
+        return Ok(());
+    }
+    remove_chunks(path);
+    fs::write(&layout_path, layout).with_context(|| format!("Writing {}", layout_path.display()))
+}
+
+/// Removes all chunks of the download to `path` and their layout.
+pub(super) fn remove_chunks(path: &Path) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let prefix = format!("{}.chunk", name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let entry_name = entry.file_name().to_string_lossy().into_owned();
+        let is_chunk = entry_name
+            .strip_prefix(&prefix)
+            .is_some_and(|index| !index.is_empty() && index.bytes().all(|byte| byte.is_ascii_digit()));
+        if is_chunk {
+            debug!("Removing {}", entry_name);
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    let _ = fs::remove_file(layout_path(path));
+}
+
+impl NetworkManager {
+    /// Size of the file at `url` if the server serves ranges of it, which one byte is asked for
+    /// to find out. Signed URLs often refuse `HEAD`, so it isn't used.
+    pub(super) async fn ranged_size(&self, url: &str) -> Result<Option<u64>> {
+        let response = self.send_download(self.client.get(url).header(RANGE, "bytes=0-0")).await?;
+        if matches!(response.status(), StatusCode::FORBIDDEN | StatusCode::GONE) {
+            return Err(crate::Error::ExpiredUrl(url.to_string()));
+        }
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Ok(None);
+        }
+        Ok(content_range(&response).and_then(|(_, total)| total))
+    }
+
+    /// Downloads the `total` bytes at `url` to `path` over [`with_download_connections`]
+    /// connections. Chunks left by an interrupted download are continued.
+    ///
+    /// [`with_download_connections`]: NetworkManager::with_download_connections
+    pub(super) async fn download_chunked(
+        &self,
+        url: &str,
+        path: &Path,
+        total: u64,
+        progress_callback: impl Fn(DownloadProgress) + Send + 'static,
+    ) -> Result<()> {
+        let ranges = chunk_ranges(total, self.connections);
+        check_layout(path, total, ranges.len())?;
+        let chunks: Vec<PathBuf> = (0..ranges.len()).map(|index| chunk_path(path, index)).collect();
+        let resumed_from: u64 = chunks
+            .iter()
+            .zip(&ranges)
+            .map(|(chunk, range)| fs::metadata(chunk).map_or(0, |metadata| metadata.len()).min(range.end - range.start))
+            .sum();
+        if resumed_from > 0 {
+            info!("Resuming download at {}", format_size(resumed_from));
+        }
+        info!("Downloading {} over {} connections", format_size(total), ranges.len());
+
+        let start = Instant::now();
+        let progress = ChunkProgress {
+            callback: Mutex::new(progress_callback),
+            downloaded: AtomicU64::new(resumed_from),
+            total,
+            speed: Mutex::new(SpeedMeter::new(start, resumed_from)),
+            last_report: Mutex::new(None),
+            last_data: Mutex::new(start),
+        };
+        try_join_all(ranges.iter().zip(&chunks).map(|(range, chunk)| self.fetch_chunk(url, chunk, range.clone(), &progress)))
+            .await?;
+
+        // A chunk with the wrong length would corrupt the file, it's downloaded again instead
+        for (chunk, range) in chunks.iter().zip(&ranges) {
+            let length = fs::metadata(chunk)?.len();
+            if length != range.end - range.start {
+                let _ = fs::remove_file(chunk);
+                return Err(crate::Error::Connection(format!(
+                    "Received {} instead of {} for bytes {}-{} of {}",
+                    format_size(length),
+                    format_size(range.end - range.start),
+                    range.start,
+                    range.end - 1,
+                    url
+                )));
+            }
+        }
+        join_chunks(&chunks, path).inspect_err(|_| {
+            let _ = fs::remove_file(path);
+        })?;
+        for chunk in &chunks {
+            let _ = fs::remove_file(chunk);
+        }
+        let _ = fs::remove_file(layout_path(path));
+
+        progress.report(total, None);
+        debug!("Download complete: {} in {:?}", format_size(total), start.elapsed());
+        Ok(())
+    }
+
+    /// Downloads `range` of the file at `url` to `path`, continuing what `path` already holds.
+    async fn fetch_chunk<F: Fn(DownloadProgress)>(
+        &self,
+        url: &str,
+        path: &Path,
+        range: Range<u64>,
+        progress: &ChunkProgress<F>,
+    ) -> Result<()> {
+        let existing = fs::metadata(path).map_or(0, |metadata| metadata.len());
+        if existing >= range.end - range.start {
+            return Ok(());
+        }
+        let first = range.start + existing;
+        let request = self.client.get(url).header(RANGE, format!("bytes={}-{}", first, range.end - 1));
+        let response = self.send_download(request).await?;
+        if matches!(response.status(), StatusCode::FORBIDDEN | StatusCode::GONE) {
+            return Err(crate::Error::ExpiredUrl(url.to_string()));
+        }
+        let response = response.error_for_status()?;
+        if response.status() != StatusCode::PARTIAL_CONTENT || content_range(&response).and_then(|(start, _)| start) != Some(first) {
+            return Err(crate::Error::Connection(format!("{} didn't serve bytes {}-{}", url, first, range.end - 1)));
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Opening {}", path.display()))?;
+        let mut stream = response.bytes_stream();
+        let mut last_data = Instant::now();
+        loop {
+            let next = tokio::time::timeout(STALL_CHECK_INTERVAL, stream.next());
+            let data = match self.control.cancellation.run(next).await? {
+                Ok(Some(data)) => data?,
+                Ok(None) => break,
+                Err(_) => {
+                    check_stalled(url, last_data.elapsed())?;
+                    progress.check_stall();
+                    continue;
+                },
+            };
+            self.control.wait_while_paused().await?;
+            last_data = Instant::now();
+            file.write_all(&data).with_context(|| format!("Writing {}", path.display()))?;
+            progress.add(data.len() as u64);
+        }
+        Ok(())
+    }
+}
+
+/// Writes the `chunks` one after another to `path`.
+fn join_chunks(chunks: &[PathBuf], path: &Path) -> Result<()> {
+    let mut file = File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+    for chunk in chunks {
+        let mut chunk = File::open(chunk).with_context(|| format!("Opening {}", chunk.display()))?;
+        io::copy(&mut chunk, &mut file).with_context(|| format!("Writing {}", path.display()))?;
+    }
+    Ok(file.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use tempfile::tempdir;
+
+    /// Serves the ranges of `body` asked for at a local URL.
+    fn serve_ranges(body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/launcher.zip", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut range = 0..body.len();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(bytes) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        let (first, last) = bytes.trim().split_once('-').unwrap();
+                        range = first.parse().unwrap()..last.parse::<usize>().unwrap() + 1;
+                    }
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    range.start,
+                    range.end - 1,
+                    body.len(),
+                    range.len()
+                )
+                .unwrap();
+                stream.write_all(&body[range]).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_chunk_ranges() {
+        assert_eq!(chunk_ranges(10, 3), [0..4, 4..8, 8..10]);
+        assert_eq!(chunk_ranges(2, 4), [0..1, 1..2]);
+        assert_eq!(chunk_ranges(10, 1), vec![0..10]);
+    }
+
+    #[test]
+    fn test_join_chunks() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("launcher-2.zip.part");
+        let chunks: Vec<PathBuf> = (0..2).map(|index| chunk_path(&path, index)).collect();
+        fs::write(&chunks[0], "launcher ").unwrap();
+        fs::write(&chunks[1], "2.0").unwrap();
+
+        join_chunks(&chunks, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "launcher 2.0");
+        assert_eq!(chunks[1].file_name().unwrap(), "launcher-2.zip.part.chunk1");
+    }
+
+    #[tokio::test]
+    async fn test_resume_with_other_connection_count() {
+        const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuv";
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("launcher-2.zip.part");
+        // A run with 4 connections was interrupted after the first bytes of its second chunk
+        check_layout(&path, 32, 4).unwrap();
+        fs::write(chunk_path(&path, 0), &BODY[..8]).unwrap();
+        fs::write(chunk_path(&path, 1), &BODY[8..12]).unwrap();
+        fs::write(chunk_path(&path, 3), &BODY[24..26]).unwrap();
+
+        let network = NetworkManager::new().with_download_connections(2);
+        network.download_chunked(&serve_ranges(BODY), &path, 32, |_| {}).await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), BODY);
+        assert!(!chunk_path(&path, 3).exists());
+        assert!(!layout_path(&path).exists());
+    }
+}
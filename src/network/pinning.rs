@@ -0,0 +1,259 @@
+//! Public key pinning for the PatchKit API. The API is reached with the app's secrets, so on a
+//! hostile network a certificate from a trusted but wrong authority must not be enough to get the
+//! runner to install from it. Pins are SHA-256 hashes of the server certificate's public key, as in
+//! `sha256/pY6l+Aw2HAYl1qL5hrEaAkoeez5BQIysNF55G56S7OY=`, so a renewed certificate for the same
+//! key still matches.
+//!
+//! The key is checked during the TLS handshake, so no request is sent to a server presenting
+//! another key. The API is reached with a client of its own for this, using rustls with the
+//! Mozilla roots; content downloads keep the platform's TLS.
+
+use crate::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+const PIN_PREFIX: &str = "sha256/";
+
+/// SHA-256 of a DER-encoded SubjectPublicKeyInfo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CertificatePin([u8; 32]);
+
+impl TryFrom<String> for CertificatePin {
+    type Error = String;
+
+    fn try_from(pin: String) -> std::result::Result<Self, String> {
+        pin.strip_prefix(PIN_PREFIX)
+            .and_then(|hash| BASE64.decode(hash).ok())
+            .and_then(|hash| hash.try_into().ok())
+            .map(CertificatePin)
+            .ok_or_else(|| format!("Invalid certificate pin {:?}, expected sha256/<base64>", pin))
+    }
+}
+
+impl From<CertificatePin> for String {
+    fn from(pin: CertificatePin) -> Self {
+        pin.to_string()
+    }
+}
+
+impl fmt::Display for CertificatePin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", PIN_PREFIX, BASE64.encode(self.0))
+    }
+}
+
+/// The keys the API server may present, checked on each handshake after the usual validation of
+/// its certificate.
+pub struct CertificatePins {
+    pins: Vec<CertificatePin>,
+    roots: WebPkiVerifier,
+    /// Why the last handshake was refused, for the error of its request.
+    rejection: Mutex<Option<String>>,
+}
+
+impl CertificatePins {
+    pub fn new(pins: Vec<CertificatePin>) -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|root| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(root.subject, root.spki, root.name_constraints)
+        }));
+        Self::with_roots(pins, roots)
+    }
+
+    fn with_roots(pins: Vec<CertificatePin>, roots: RootCertStore) -> Self {
+        Self { pins, roots: WebPkiVerifier::new(roots, None), rejection: Mutex::new(None) }
+    }
+
+    /// TLS configuration of the API client, checking the keys during the handshake.
+    pub fn tls_config(self: &Arc<Self>) -> ClientConfig {
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(self.clone())
+            .with_no_client_auth()
+    }
+
+    /// `Error::PinnedCertificate` if a handshake was refused for its key since the last call.
+    pub fn take_rejection(&self) -> Result<()> {
+        match self.rejection.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(reason) => Err(crate::Error::PinnedCertificate(reason)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for CertificatePins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertificatePins").field("pins", &self.pins).finish_non_exhaustive()
+    }
+}
+
+impl ServerCertVerifier for CertificatePins {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verified = self.roots.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_string(),
+            ServerName::IpAddress(ip) => ip.to_string(),
+            _ => "The server".to_string(),
+        };
+        let reason = match public_key_info(&end_entity.0) {
+            None => format!("{} presented a certificate without a public key", host),
+            Some(key) => {
+                let hash = CertificatePin(Sha256::digest(key).into());
+                if self.pins.contains(&hash) {
+                    return Ok(verified);
+                }
+                format!("{} presented the unpinned key {}", host, hash)
+            },
+        };
+        *self.rejection.lock().unwrap_or_else(|e| e.into_inner()) = Some(reason.clone());
+        Err(rustls::Error::General(reason))
+    }
+}
+
+/// The SubjectPublicKeyInfo of a DER-encoded X.509 `certificate`, with its header.
+fn public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let certificate = DerElement::parse(certificate)?.contents;
+    let mut fields = DerElement::parse(certificate)?.contents;
+    // The version is optional and tagged [0]
+    if fields.first() == Some(&0xa0) {
+        fields = DerElement::parse(fields)?.rest;
+    }
+    // Skip the serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        fields = DerElement::parse(fields)?.rest;
+    }
+    let key = DerElement::parse(fields)?;
+    (key.tag == 0x30).then_some(key.whole)
+}
+
+/// A DER element at the start of some data.
+struct DerElement<'a> {
+    tag: u8,
+    /// The element with its tag and length.
+    whole: &'a [u8],
+    contents: &'a [u8],
+    /// The data after the element.
+    rest: &'a [u8],
+}
+
+impl<'a> DerElement<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let (&tag, rest) = data.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (length, rest) = if first < 0x80 {
+            (first as usize, rest)
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return None;
+            }
+            let length = rest[..count].iter().fold(0, |length, &byte| (length << 8) | byte as usize);
+            (length, &rest[count..])
+        };
+        if rest.len() < length {
+            return None;
+        }
+        let header = data.len() - rest.len();
+        Some(Self { tag, whole: &data[..header + length], contents: &rest[..length], rest: &rest[length..] })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::NetworkManager;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Self-signed P-256 certificate for api.test
+    const CERTIFICATE: &str = "MIIBejCCASGgAwIBAgIUB3YAiw8DhSLdENaSHDvoIm11LV0wCgYIKoZIzj0EAwIwEzERMA8GA1UEAwwIYXBpLnRlc3QwHhcNMjYxMDE2MTg1MTE5WhcNMzYxMDEzMTg1MTE5WjATMREwDwYDVQQDDAhhcGkudGVzdDBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABDgIyOCbr7zyBPLgyt2QvS7gJlg+Uu2Oj6T8Z8BP7rgv02X+zVoQmQxExUosEpDjmJN5dWqoracRVLOrPICpKCijUzBRMB0GA1UdDgQWBBTO/GSxWYeJgVvukb5/Awc8tp/xmjAfBgNVHSMEGDAWgBTO/GSxWYeJgVvukb5/Awc8tp/xmjAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCIBWHmrvB0qesezIJ3z/hY7PHuI4tw5MD/qceG30gU544AiAdREwtt9E1xYie+bN+YF1mEvEeuNoPOphe8JRVRWfaUQ==";
+
+    #[test]
+    fn test_certificate_pin() {
+        let certificate = BASE64.decode(CERTIFICATE).unwrap();
+        let key = public_key_info(&certificate).unwrap();
+        // As computed by `openssl x509 -pubkey | openssl pkey -pubin -outform der | openssl dgst -sha256`
+        let pin = CertificatePin::try_from("sha256/pY6l+Aw2HAYl1qL5hrEaAkoeez5BQIysNF55G56S7OY=".to_string()).unwrap();
+        assert_eq!(CertificatePin(Sha256::digest(key).into()), pin);
+        assert_eq!(pin.to_string(), "sha256/pY6l+Aw2HAYl1qL5hrEaAkoeez5BQIysNF55G56S7OY=");
+
+        assert!(CertificatePin::try_from("pY6l+Aw2HAYl1qL5hrEaAkoeez5BQIysNF55G56S7OY=".to_string()).is_err());
+        assert!(CertificatePin::try_from("sha256/AAAA".to_string()).is_err());
+        assert_eq!(public_key_info(&certificate[..100]), None);
+    }
+
+    /// Serves one HTTPS request for localhost with the test certificate, answering `body`.
+    /// Returns the API URL and whether a request arrived.
+    fn serve_tls_once(body: &'static str) -> (String, Arc<AtomicBool>) {
+        use std::io::{BufRead, BufReader, Write};
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(include_bytes!("testdata/api.der").to_vec())],
+                rustls::PrivateKey(include_bytes!("testdata/api-key.der").to_vec()),
+            )
+            .unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://localhost:{}", listener.local_addr().unwrap().port());
+        let requested = Arc::new(AtomicBool::new(false));
+        let seen = requested.clone();
+        std::thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let connection = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+            let mut stream = BufReader::new(rustls::StreamOwned::new(connection, socket));
+            let mut line = String::new();
+            // Fails when the client refuses the handshake
+            while stream.read_line(&mut line).unwrap_or(0) > 2 {
+                seen.store(true, Ordering::SeqCst);
+                line.clear();
+            }
+            let stream = stream.get_mut();
+            let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+            let _ = stream.flush();
+        });
+        (url, requested)
+    }
+
+    #[tokio::test]
+    async fn test_key_is_checked_before_the_request() {
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(include_bytes!("testdata/api-ca.der").to_vec())).unwrap();
+        let key = public_key_info(include_bytes!("testdata/api.der")).unwrap();
+        let pinned = CertificatePin(Sha256::digest(key).into());
+        let other = CertificatePin::try_from("sha256/pY6l+Aw2HAYl1qL5hrEaAkoeez5BQIysNF55G56S7OY=".to_string()).unwrap();
+        let manager = |url: String, pin: CertificatePin| {
+            let mut manager = NetworkManager {
+                api_url: url,
+                pins: Some(Arc::new(CertificatePins::with_roots(vec![pin], roots.clone()))),
+                ..NetworkManager::new()
+            };
+            manager.build_clients().unwrap();
+            manager
+        };
+
+        let (url, requested) = serve_tls_once(r#"{"id": 2}"#);
+        assert_eq!(manager(url, pinned).get_latest_version("secret").await.unwrap(), "2");
+        assert!(requested.load(Ordering::SeqCst));
+
+        let (url, requested) = serve_tls_once(r#"{"id": 2}"#);
+        let result = manager(url, other).get_latest_version("secret").await;
+        assert!(matches!(result, Err(crate::Error::PinnedCertificate(_))), "{:?}", result);
+        // The secret in the URL never reached the server
+        assert!(!requested.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,163 @@
+//! Download speed limits that depend on the time of day, e.g. unlimited at night and 1 MB/s
+//! during work hours, for connections shared with others.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Local time of day as `HH:MM`, stored as minutes since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(u16);
+
+impl TimeOfDay {
+    pub fn minutes(&self) -> u16 {
+        self.0
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid time {:?}, expected HH:MM", value);
+        let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+        let hours: u16 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u16 = minutes.parse().map_err(|_| invalid())?;
+        // 24:00 ends a rule at midnight
+        if minutes >= 60 || hours > 24 || (hours == 24 && minutes > 0) {
+            return Err(invalid());
+        }
+        Ok(Self(hours * 60 + minutes))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+/// Speed limit from `from` until `to`, which may be past midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BandwidthRule {
+    pub from: TimeOfDay,
+    pub to: TimeOfDay,
+    /// Limit in KB/s, 0 for unlimited.
+    pub limit_kbps: u64,
+}
+
+impl BandwidthRule {
+    fn applies_at(&self, minute: u16) -> bool {
+        let (from, to) = (self.from.minutes(), self.to.minutes());
+        if from <= to {
+            (from..to).contains(&minute)
+        } else {
+            minute >= from || minute < to
+        }
+    }
+}
+
+/// The download speed limit over the day: the first rule covering the time, or the default.
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthSchedule {
+    default_kbps: Option<u64>,
+    rules: Vec<BandwidthRule>,
+}
+
+impl BandwidthSchedule {
+    pub fn new(default_kbps: Option<u64>, rules: Vec<BandwidthRule>) -> Self {
+        Self { default_kbps: default_kbps.filter(|limit| *limit > 0), rules }
+    }
+
+    /// Limit in KB/s at `minute` past midnight, `None` for unlimited.
+    pub fn limit_at(&self, minute: u16) -> Option<u64> {
+        match self.rules.iter().find(|rule| rule.applies_at(minute % MINUTES_PER_DAY)) {
+            Some(rule) => (rule.limit_kbps > 0).then_some(rule.limit_kbps),
+            None => self.default_kbps,
+        }
+    }
+
+    /// Limit in KB/s now, `None` for unlimited.
+    pub fn current_limit(&self) -> Option<u64> {
+        if self.rules.is_empty() {
+            return self.default_kbps;
+        }
+        self.limit_at(native::local_minute_of_day())
+    }
+}
+
+#[cfg(unix)]
+mod native {
+    pub fn local_minute_of_day() -> u16 {
+        unsafe {
+            let now = libc::time(std::ptr::null_mut());
+            let mut local: libc::tm = std::mem::zeroed();
+            if libc::localtime_r(&now, &mut local).is_null() {
+                return 0;
+            }
+            (local.tm_hour * 60 + local.tm_min) as u16
+        }
+    }
+}
+
+#[cfg(windows)]
+mod native {
+    use winapi::um::minwinbase::SYSTEMTIME;
+    use winapi::um::sysinfoapi::GetLocalTime;
+
+    pub fn local_minute_of_day() -> u16 {
+        let mut local: SYSTEMTIME = unsafe { std::mem::zeroed() };
+        unsafe { GetLocalTime(&mut local) };
+        local.wHour * 60 + local.wMinute
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod native {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// UTC, without a way to tell the local time zone.
+    pub fn local_minute_of_day() -> u16 {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        ((seconds / 60) % u64::from(super::MINUTES_PER_DAY)) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> TimeOfDay {
+        TimeOfDay::try_from(value.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_limit_at() {
+        let schedule = BandwidthSchedule::new(
+            Some(256),
+            vec![
+                BandwidthRule { from: time("09:00"), to: time("17:00"), limit_kbps: 1024 },
+                BandwidthRule { from: time("23:00"), to: time("07:00"), limit_kbps: 0 },
+            ],
+        );
+        assert_eq!(schedule.limit_at(time("09:00").minutes()), Some(1024));
+        assert_eq!(schedule.limit_at(time("16:59").minutes()), Some(1024));
+        assert_eq!(schedule.limit_at(time("17:00").minutes()), Some(256));
+        assert_eq!(schedule.limit_at(time("23:30").minutes()), None);
+        assert_eq!(schedule.limit_at(time("06:59").minutes()), None);
+        assert_eq!(BandwidthSchedule::new(Some(0), Vec::new()).current_limit(), None);
+
+        assert_eq!(time("24:00").minutes(), MINUTES_PER_DAY);
+        assert!(TimeOfDay::try_from("9".to_string()).is_err());
+        assert!(TimeOfDay::try_from("12:60".to_string()).is_err());
+        assert_eq!(time("07:05").to_string(), "07:05");
+    }
+}
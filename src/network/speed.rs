@@ -0,0 +1,63 @@
+//! Download speed over the last few seconds. The average since the start hides slowdowns and
+//! speedups for the rest of a long download, so the speed shown follows a sliding window.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How far back the speed is measured.
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+/// Byte counts seen within the last [`SPEED_WINDOW`].
+#[derive(Debug)]
+pub struct SpeedMeter {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedMeter {
+    /// Starts measuring at `bytes` already downloaded.
+    pub fn new(start: Instant, bytes: u64) -> Self {
+        Self { samples: VecDeque::from([(start, bytes)]) }
+    }
+
+    /// Records that `bytes` are downloaded at `now` and returns the speed in KB/s over the window.
+    pub fn record(&mut self, now: Instant, bytes: u64) -> f64 {
+        self.samples.push_back((now, bytes));
+        // The oldest sample within the window is the baseline, older ones are dropped
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= SPEED_WINDOW {
+            self.samples.pop_front();
+        }
+        let (since, from) = self.samples[0];
+        let elapsed = now.duration_since(since).as_secs_f64();
+        if elapsed > 0.0 {
+            bytes.saturating_sub(from) as f64 / 1024.0 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Time left to download `remaining` bytes at `speed_kbps`, unknown while nothing arrives.
+pub fn remaining_time(remaining: u64, speed_kbps: f64) -> Option<Duration> {
+    (speed_kbps > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / 1024.0 / speed_kbps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speed_meter() {
+        let start = Instant::now();
+        let mut meter = SpeedMeter::new(start, 0);
+        assert_eq!(meter.record(start + Duration::from_secs(1), 1024 * 1024), 1024.0);
+        assert_eq!(meter.record(start + Duration::from_secs(5), 5 * 1024 * 1024), 1024.0);
+        // A slowdown shows once the fast seconds leave the window
+        assert_eq!(meter.record(start + Duration::from_secs(10), 5 * 1024 * 1024 + 640 * 1024), 128.0);
+    }
+
+    #[test]
+    fn test_remaining_time() {
+        assert_eq!(remaining_time(10 * 1024, 1.0), Some(Duration::from_secs(10)));
+        assert_eq!(remaining_time(1024, 0.0), None);
+    }
+}
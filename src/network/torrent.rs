@@ -0,0 +1,314 @@
+//! The torrent transport. A part offering it links its `.torrent`, whose pieces are fetched from
+//! HTTP web seeds as in BEP 19: the part's URL and the torrent's `url-list`, so a launch can be
+//! spread over servers besides the CDN. Each piece is checked against its SHA-1 from the torrent
+//! before it's written, and one a seed gets wrong is fetched from the next. Peers aren't contacted.
+
+use super::backend::{Downloader, Transport};
+use super::speed::SpeedMeter;
+use super::{should_report, AppApi, ContentUrl, DownloadProgress, ProgressCallback};
+use crate::error::ResultExt;
+use crate::format::format_size;
+use crate::telemetry::cdn::host;
+use crate::Result;
+use bytes::Bytes;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const PIECE_HASH_SIZE: usize = 20;
+
+/// Metainfo of a single-file torrent.
+#[derive(Debug, Deserialize)]
+struct Metainfo {
+    info: Info,
+    /// Web seeds, one URL or a list of them.
+    #[serde(default, rename = "url-list")]
+    url_list: Option<UrlList>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Info {
+    name: String,
+    length: u64,
+    #[serde(rename = "piece length")]
+    piece_length: u64,
+    /// The SHA-1 hashes of the pieces, one after another.
+    #[serde(with = "serde_bytes")]
+    pieces: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum UrlList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Metainfo {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let metainfo: Self = serde_bencode::from_bytes(data)
+            .map_err(|e| crate::Error::Other(format!("Invalid torrent: {}", e)))?;
+        let info = &metainfo.info;
+        if info.piece_length == 0
+            || info.pieces.len() % PIECE_HASH_SIZE != 0
+            || (info.pieces.len() / PIECE_HASH_SIZE) as u64 != info.length.div_ceil(info.piece_length)
+        {
+            return Err(crate::Error::Other("Invalid torrent: its pieces don't cover the file".into()));
+        }
+        Ok(metainfo)
+    }
+
+    fn piece_count(&self) -> usize {
+        self.info.pieces.len() / PIECE_HASH_SIZE
+    }
+
+    /// The bytes of the file the `index`th piece covers.
+    fn piece(&self, index: usize) -> Range<u64> {
+        let start = index as u64 * self.info.piece_length;
+        start..(start + self.info.piece_length).min(self.info.length)
+    }
+
+    fn piece_hash(&self, index: usize) -> &[u8] {
+        &self.info.pieces[index * PIECE_HASH_SIZE..(index + 1) * PIECE_HASH_SIZE]
+    }
+
+    /// The web seeds of the file, `url` first. A seed ending in `/` is a directory holding the
+    /// file under the torrent's name.
+    fn web_seeds(&self, url: &str) -> Vec<String> {
+        let listed = match &self.url_list {
+            None => Vec::new(),
+            Some(UrlList::One(seed)) => vec![seed.clone()],
+            Some(UrlList::Many(seeds)) => seeds.clone(),
+        };
+        let mut seeds = vec![url.to_string()];
+        for seed in listed {
+            let seed = if seed.ends_with('/') { format!("{}{}", seed, self.info.name) } else { seed };
+            if !seeds.contains(&seed) {
+                seeds.push(seed);
+            }
+        }
+        seeds
+    }
+}
+
+/// Where the pieces of the download to `path` are written until all of them are there.
+pub fn pieces_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".pieces")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Downloads parts from the web seeds of their torrent.
+pub struct TorrentDownloader<'a, A>(pub &'a A);
+
+impl<A: AppApi> Downloader for TorrentDownloader<'_, A> {
+    fn transport(&self) -> Transport {
+        Transport::Torrent
+    }
+
+    async fn download(&self, part: &ContentUrl, url: &str, path: &Path, progress: ProgressCallback) -> Result<()> {
+        let torrent_url = part.torrent.as_deref()
+            .ok_or_else(|| crate::Error::Other(format!("{} has no torrent", part.url)))?;
+        let metainfo = self.metainfo(torrent_url, path).await.context("Fetching the torrent")?;
+        if part.size != 0 && metainfo.info.length != part.size {
+            return Err(crate::Error::Other(format!("The torrent of {} is of another file", part.url)));
+        }
+
+        // What an earlier download left is kept, its pieces are checked like any others
+        let pieces_path = pieces_path(path);
+        if path.exists() && !pieces_path.exists() {
+            fs::rename(path, &pieces_path).with_context(|| format!("Moving {}", path.display()))?;
+        }
+        let result = self.download_pieces(&metainfo, url, &pieces_path, progress).await;
+        if result.as_ref().is_err_and(|e| matches!(e.root(), crate::Error::Cancelled(_))) {
+            debug!("Download cancelled, removing {}", pieces_path.display());
+            let _ = fs::remove_file(&pieces_path);
+        }
+        result?;
+        fs::rename(&pieces_path, path).with_context(|| format!("Moving {}", pieces_path.display()))
+    }
+}
+
+impl<A: AppApi> TorrentDownloader<'_, A> {
+    /// Fetches and parses the torrent, next to the download to `path`.
+    async fn metainfo(&self, torrent_url: &str, path: &Path) -> Result<Metainfo> {
+        let torrent_path = with_suffix(path, ".torrent");
+        // Fetched anew each time, one left from before may be of another version of the file
+        let _ = fs::remove_file(&torrent_path);
+        let data = match self.0.download_file(torrent_url, &torrent_path, Box::new(|_| {})).await {
+            Ok(()) => fs::read(&torrent_path).map_err(Into::into),
+            Err(e) => Err(e),
+        };
+        let _ = fs::remove_file(&torrent_path);
+        Metainfo::parse(&data?)
+    }
+
+    /// Downloads the pieces missing from the file at `path`, one per seed at a time.
+    async fn download_pieces(&self, metainfo: &Metainfo, url: &str, path: &Path, progress: ProgressCallback) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Opening {}", path.display()))?;
+        let total = metainfo.info.length;
+        file.set_len(total).with_context(|| format!("Allocating {}", path.display()))?;
+        let mut missing = Vec::new();
+        for index in 0..metainfo.piece_count() {
+            if !has_piece(&mut file, metainfo, index).with_context(|| format!("Reading {}", path.display()))? {
+                missing.push(index);
+            }
+        }
+        let mut downloaded = total - missing.iter().map(|&index| metainfo.piece(index).end - metainfo.piece(index).start).sum::<u64>();
+        if downloaded > 0 {
+            info!("Resuming torrent download at {}", format_size(downloaded));
+        }
+        let seeds = metainfo.web_seeds(url);
+        debug!("Downloading {} of {} pieces from {} web seeds", missing.len(), metainfo.piece_count(), seeds.len());
+
+        let mut speed = SpeedMeter::new(Instant::now(), downloaded);
+        let mut last_report = None;
+        let mut pieces = stream::iter(missing)
+            .map(|index| self.fetch_piece(metainfo, &seeds, index))
+            .buffer_unordered(seeds.len());
+        loop {
+            let now = Instant::now();
+            if should_report(last_report, now, downloaded >= total) {
+                progress(DownloadProgress {
+                    bytes: downloaded,
+                    total_bytes: total,
+                    speed_kbps: speed.record(now, downloaded),
+                    stalled_for: None,
+                });
+                last_report = Some(now);
+            }
+            let Some((index, data)) = pieces.try_next().await? else {
+                break;
+            };
+            file.seek(SeekFrom::Start(metainfo.piece(index).start))
+                .and_then(|_| file.write_all(&data))
+                .with_context(|| format!("Writing {}", path.display()))?;
+            downloaded += data.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Fetches the `index`th piece from the first seed serving it intact. Pieces start at
+    /// different seeds, so the seeds share the work.
+    async fn fetch_piece(&self, metainfo: &Metainfo, seeds: &[String], index: usize) -> Result<(usize, Bytes)> {
+        let range = metainfo.piece(index);
+        let mut error = None;
+        for offset in 0..seeds.len() {
+            let seed = &seeds[(index + offset) % seeds.len()];
+            let seed_host = host(seed).unwrap_or_default();
+            match self.0.download_range(seed, range.clone()).await {
+                Ok(data) if Sha1::digest(&data).as_slice() == metainfo.piece_hash(index) => return Ok((index, data)),
+                Ok(_) => {
+                    warn!("Piece {} from {} doesn't match the torrent", index, seed_host);
+                    error = Some(crate::Error::Connection(format!("Piece {} from {} doesn't match the torrent", index, seed_host)));
+                },
+                Err(e) if matches!(e.root(), crate::Error::Cancelled(_)) => return Err(e),
+                Err(e) => {
+                    warn!("Fetching piece {} from {} failed: {}", index, seed_host, e);
+                    error = Some(e);
+                },
+            }
+        }
+        Err(error.unwrap_or_else(|| crate::Error::Other("The torrent has no web seeds".into())))
+    }
+}
+
+/// Whether the file already holds the `index`th piece intact.
+fn has_piece(file: &mut File, metainfo: &Metainfo, index: usize) -> std::io::Result<bool> {
+    let range = metainfo.piece(index);
+    let mut data = vec![0; (range.end - range.start) as usize];
+    file.seek(SeekFrom::Start(range.start))?;
+    file.read_exact(&mut data)?;
+    Ok(Sha1::digest(&data).as_slice() == metainfo.piece_hash(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::MockAppApi;
+    use mockall::predicate::eq;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    /// A torrent of `data` in pieces of 4 bytes, with `seeds` as its `url-list`.
+    fn torrent(data: &[u8], seeds: &str) -> Vec<u8> {
+        let pieces: Vec<u8> = data.chunks(4).flat_map(|piece| Sha1::digest(piece).to_vec()).collect();
+        let mut torrent = format!("d4:infod6:lengthi{}e4:name5:1.zip12:piece lengthi4e6:pieces{}:", data.len(), pieces.len()).into_bytes();
+        torrent.extend_from_slice(&pieces);
+        torrent.extend_from_slice(format!("e8:url-list{}e", seeds).as_bytes());
+        torrent
+    }
+
+    #[test]
+    fn test_metainfo() {
+        let metainfo = Metainfo::parse(&torrent(b"0123456789", "l18:https://seed.test/e")).unwrap();
+        assert_eq!(metainfo.piece_count(), 3);
+        assert_eq!(metainfo.piece(2), 8..10);
+        assert_eq!(metainfo.web_seeds("https://cdn.test/1.zip"), ["https://cdn.test/1.zip", "https://seed.test/1.zip"]);
+
+        let metainfo = Metainfo::parse(&torrent(b"0123456789", "22:https://cdn.test/1.zip")).unwrap();
+        assert_eq!(metainfo.web_seeds("https://cdn.test/1.zip"), ["https://cdn.test/1.zip"]);
+        assert!(Metainfo::parse(b"d4:infodee").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_from_web_seeds() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("launcher-2.zip.part");
+        let data: &'static [u8] = b"launcher 2.0 package";
+        // The first piece is left from an earlier download
+        fs::write(&path, b"laun").unwrap();
+        let part = ContentUrl {
+            size: data.len() as u64,
+            url: "https://cdn.test/1.zip".into(),
+            arch: None,
+            mirrors: Vec::new(),
+            continued: false,
+            transports: vec![Transport::Torrent],
+            torrent: Some("https://cdn.test/1.zip.torrent".into()),
+        };
+
+        let mut network = MockAppApi::new();
+        network.expect_download_file()
+            .withf(|url, _, _| url == "https://cdn.test/1.zip.torrent")
+            .times(1)
+            .returning(|_, path, _| Ok(fs::write(path, torrent(data, "l18:https://seed.test/e"))?));
+        // The CDN serves a broken piece, which is taken from the other seed instead
+        network.expect_download_range()
+            .with(eq("https://cdn.test/1.zip"), eq(8..12))
+            .times(1)
+            .returning(|_, _| Ok(Bytes::from_static(b"XXXX")));
+        network.expect_download_range()
+            .withf(|_, range| range.start > 0)
+            .times(4)
+            .returning(move |_, range| Ok(Bytes::from_static(&data[range.start as usize..range.end as usize])));
+
+        let reported = Arc::new(AtomicU64::new(0));
+        let seen = reported.clone();
+        TorrentDownloader(&network)
+            .download(&part, &part.url, &path, Box::new(move |progress| seen.store(progress.bytes, Ordering::SeqCst)))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+        assert_eq!(reported.load(Ordering::SeqCst), data.len() as u64);
+        assert!(!pieces_path(&path).exists());
+        assert!(!with_suffix(&path, ".torrent").exists());
+    }
+}
@@ -0,0 +1,102 @@
+//! Signatures of API responses. The app's patcher secret, the latest version and where its
+//! content is decide what the runner installs, so when the dat asks for it these responses must be signed by PatchKit, and a
+//! compromised CDN, proxy or DNS can't point the runner at other packages.
+//!
+//! A signed response carries `X-PatchKit-Signature: <key id>:<base64 signature>`, the Ed25519
+//! signature of `<path and query>\n<body>`. The public keys are built into the runner from
+//! `PK_RUNNER_RESPONSE_KEYS`, comma-separated `<key id>:<base64 key>` pairs, so a new key can ship
+//! alongside the old one before the API switches to it.
+
+use crate::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use ed25519_dalek::{Signature, VerifyingKey};
+
+pub const SIGNATURE_HEADER: &str = "X-PatchKit-Signature";
+const BUILT_IN_KEYS: Option<&str> = option_env!("PK_RUNNER_RESPONSE_KEYS");
+
+/// The keys API responses may be signed with.
+#[derive(Debug)]
+pub struct ResponseVerifier {
+    keys: Vec<(String, VerifyingKey)>,
+}
+
+impl ResponseVerifier {
+    /// Verifier with the keys built into the runner.
+    pub fn built_in() -> Result<Self> {
+        let verifier = Self::parse(BUILT_IN_KEYS.unwrap_or_default())?;
+        if verifier.keys.is_empty() {
+            return Err(crate::Error::DatFile(
+                "The dat requires signed API responses, but this runner was built without keys".into(),
+            ));
+        }
+        Ok(verifier)
+    }
+
+    /// Parses comma-separated `<key id>:<base64 key>` pairs.
+    pub fn parse(keys: &str) -> Result<Self> {
+        let keys = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| {
+                let parsed = key.split_once(':').and_then(|(id, key)| {
+                    let key: [u8; 32] = BASE64.decode(key).ok()?.try_into().ok()?;
+                    Some((id.to_string(), VerifyingKey::from_bytes(&key).ok()?))
+                });
+                parsed.ok_or_else(|| crate::Error::Other(format!("Invalid response key {:?}", key)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Checks `signature`, the value of the [`SIGNATURE_HEADER`], of `body` in response to `path`.
+    pub fn verify(&self, path: &str, signature: Option<&str>, body: &[u8]) -> Result<()> {
+        let invalid = |reason: String| crate::Error::InvalidSignature(reason);
+        let signature = signature.ok_or_else(|| invalid(format!("The response to {} isn't signed", path)))?;
+        let (id, signature) = signature
+            .split_once(':')
+            .ok_or_else(|| invalid(format!("Malformed signature of {}", path)))?;
+        let key = self
+            .keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .map(|(_, key)| key)
+            .ok_or_else(|| invalid(format!("The response to {} is signed with the unknown key {}", path, id)))?;
+        let signature = BASE64
+            .decode(signature)
+            .ok()
+            .and_then(|signature| Signature::from_slice(&signature).ok())
+            .ok_or_else(|| invalid(format!("Malformed signature of {}", path)))?;
+        let mut message = Vec::with_capacity(path.len() + 1 + body.len());
+        message.extend_from_slice(path.as_bytes());
+        message.push(b'\n');
+        message.extend_from_slice(body);
+        key.verify_strict(&message, &signature)
+            .map_err(|_| invalid(format!("The signature of the response to {} doesn't match", path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_verify() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let old_key = SigningKey::from_bytes(&[1; 32]).verifying_key();
+        let keys = format!("old:{},2026:{}", BASE64.encode(old_key.as_bytes()), BASE64.encode(signing_key.verifying_key().as_bytes()));
+        let verifier = ResponseVerifier::parse(&keys).unwrap();
+        let path = "/1/apps/secret/versions/latest/id";
+        let body = br#"{"id": 2}"#;
+        let signature = signing_key.sign(format!("{}\n{}", path, String::from_utf8_lossy(body)).as_bytes());
+        let header = format!("2026:{}", BASE64.encode(signature.to_bytes()));
+
+        assert!(verifier.verify(path, Some(&header), body).is_ok());
+        assert!(verifier.verify(path, Some(&header), br#"{"id": 3}"#).is_err());
+        assert!(verifier.verify("/1/apps/other/versions/latest/id", Some(&header), body).is_err());
+        assert!(verifier.verify(path, Some(&header.replace("2026:", "2027:")), body).is_err());
+        assert!(verifier.verify(path, None, body).is_err());
+        assert!(ResponseVerifier::parse("2026:AAAA").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Callbacks integrators run around the pipeline's steps, e.g. to prefetch DRM data after an
+//! update or show a EULA before the launch, without forking the runner.
+
+use crate::Result;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPoint {
+    /// Before the package is downloaded. Not reached when the installed version is current.
+    PreDownload,
+    /// After the package was extracted and the new version saved.
+    PostExtract,
+    /// Before the app is launched, whether it was updated or not.
+    PreLaunch,
+}
+
+impl HookPoint {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::PreDownload => "pre-download",
+            Self::PostExtract => "post-extract",
+            Self::PreLaunch => "pre-launch",
+        }
+    }
+}
+
+/// What a hook is told about the run.
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub point: HookPoint,
+    /// Version being installed, or the installed one before the launch. `None` if unknown.
+    pub version: Option<String>,
+    pub install_dir: PathBuf,
+    pub patcher_dir: PathBuf,
+    /// The downloaded package, or where it's downloaded to; its first part when the version comes
+    /// in several. Only set before the download and after the extraction.
+    pub package: Option<PathBuf>,
+}
+
+type Hook = Arc<dyn Fn(&HookContext) -> Result<()> + Send + Sync>;
+
+/// Hooks registered for a run. An error returned by a hook stops the run with that error.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    hooks: Vec<(HookPoint, Hook)>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `hook` at `point`, run after the hooks added there before.
+    pub fn on<F>(mut self, point: HookPoint, hook: F) -> Self
+    where
+        F: Fn(&HookContext) -> Result<()> + Send + Sync + 'static,
+    {
+        self.hooks.push((point, Arc::new(hook)));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Runs the hooks registered at `context.point`, stopping at the first error.
+    pub fn run(&self, context: &HookContext) -> Result<()> {
+        for (_, hook) in self.hooks.iter().filter(|(point, _)| *point == context.point) {
+            hook(context)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let points: Vec<_> = self.hooks.iter().map(|(point, _)| point.label()).collect();
+        f.debug_struct("Hooks").field("hooks", &points).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_hooks_run_at_their_point() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = |name: &'static str| {
+            let calls = calls.clone();
+            move |context: &HookContext| {
+                calls.lock().unwrap().push((name, context.version.clone()));
+                Ok(())
+            }
+        };
+        let hooks = Hooks::new()
+            .on(HookPoint::PreLaunch, record("eula"))
+            .on(HookPoint::PostExtract, record("drm"))
+            .on(HookPoint::PreLaunch, |_: &HookContext| Err(crate::Error::Cancelled("EULA declined".into())))
+            .on(HookPoint::PreLaunch, record("never"));
+
+        let context = HookContext {
+            point: HookPoint::PreLaunch,
+            version: Some("2".into()),
+            install_dir: PathBuf::from("app"),
+            patcher_dir: PathBuf::from("patcher"),
+            package: None,
+        };
+        assert!(matches!(hooks.run(&context), Err(crate::Error::Cancelled(_))));
+        assert_eq!(*calls.lock().unwrap(), vec![("eula", Some("2".to_string()))]);
+    }
+}
@@ -0,0 +1,1664 @@
+//! The update pipeline: checks the connection, fetches the latest version, downloads and extracts
+//! it if needed, and launches the app. It has no UI of its own; progress is reported to a
+//! [`ProgressSink`], so it can run behind the runner's window, in a terminal or embedded in other tools.
+
+pub mod hooks;
+pub mod progress;
+pub mod retry;
+pub mod state;
+
+use crate::cancel::CancellationToken;
+use crate::config::apps;
+use crate::config::paths::{self, InstallScope, PathProvider};
+use crate::config::{LauncherData, Settings, UpdateMode};
+use crate::deeplink::{self, DeepLink, PASSTHROUGH_VARIABLE};
+use crate::error::ResultExt;
+use crate::file::{cleanup, hash_file, volumes, FileManager, PackageStore};
+use crate::format::format_size;
+use crate::launcher::{Launcher, ProcessLauncher, RESTART_EXIT_CODE};
+use crate::manifest::ManifestManager;
+use crate::network::backend::{select_transport, Downloader, HttpDownloader, Transport};
+use crate::network::torrent::{self, TorrentDownloader};
+use crate::network::speed::remaining_time;
+use crate::network::verification::ResponseVerifier;
+use crate::network::{archive_groups, parts_for_arch, AppApi, ContentFile, ContentUrl, DownloadControl, NetworkManager, ProgressCallback};
+use crate::system::{arch, sandbox};
+use crate::telemetry::analytics::{Analytics, LifecycleEvent, INSTALL_ID_FILE_NAME};
+use crate::telemetry::cdn::{host, CdnFeedback};
+use crate::ui::{Phase, UpdateChoice};
+use crate::uninstall::UninstallEntry;
+use crate::{crash, telemetry};
+use hooks::{HookContext, HookPoint, Hooks};
+use progress::ProgressSink;
+use retry::{with_retry, RetryPolicy};
+use state::{Checkpoint, RunState};
+use crate::Result;
+use log::{debug, error, info, warn};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info_span, Instrument};
+
+/// How often the download URLs are fetched anew when the CDN refuses one, e.g. once it expired.
+const URL_REFRESHES: u32 = 2;
+/// How often a monitored launcher may ask to be run again in one run, so one that always asks
+/// doesn't keep the runner going forever.
+const MAX_RESTARTS: u32 = 5;
+
+const RUNNER_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Written next to the launcher, so it can tell which runner started it.
+pub const RUNNER_VERSION_FILE_NAME: &str = "runner-version.txt";
+
+/// What the runner supports beyond the manifest, for support scripts and self-update checks.
+pub const CAPABILITIES: &[&str] = &[
+    "arch_targets",
+    "runner_compatibility",
+    "content_mirrors",
+    "split_archives",
+    "encrypted_packages",
+    "deep_links",
+    "restart_exit_code",
+    "loose_files",
+    "delta_updates",
+];
+
+/// What a single run of the update pipeline should do.
+#[derive(Debug, Clone)]
+pub struct RunnerConfig {
+    pub settings: Settings,
+    /// Verify the installed files and reinstall even if the version is current.
+    pub repair: bool,
+    /// Download and install the version anew even if it's current, ignoring what earlier runs
+    /// downloaded.
+    pub force_update: bool,
+    /// The app's dat file.
+    pub dat_path: PathBuf,
+    /// Overrides the patcher directory next to the runner.
+    pub patcher_dir: Option<PathBuf>,
+    /// Deep link the runner was opened with, passed on to the launcher.
+    pub deep_link: Option<String>,
+    /// App to run when the runner ships several, by id. See [`crate::config::apps`].
+    pub app: Option<String>,
+    /// Where the dats of a multi-app runner are.
+    pub apps_dir: PathBuf,
+    /// Version ID to install instead of the latest one, overriding the settings.
+    pub pinned_version: Option<String>,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            settings: Settings::default(),
+            repair: false,
+            force_update: false,
+            dat_path: paths::dat_path(),
+            patcher_dir: None,
+            deep_link: None,
+            app: None,
+            apps_dir: paths::apps_dir(),
+            pinned_version: None,
+        }
+    }
+}
+
+/// Runs the update pipeline once.
+pub struct Runner {
+    config: RunnerConfig,
+    control: DownloadControl,
+    hooks: Hooks,
+}
+
+impl Runner {
+    pub fn new(config: RunnerConfig) -> Self {
+        Self {
+            config,
+            control: DownloadControl::default(),
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// Lets the caller pause the download or cancel the update.
+    pub fn with_control(mut self, control: DownloadControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Callbacks to run around the pipeline's steps.
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Updates and launches the app, reporting progress to `sink`.
+    pub async fn run<S: ProgressSink>(self, sink: S) -> Result<()> {
+        run_launcher(sink, self.control, self.hooks, self.config).await
+    }
+
+    /// Downloads the latest version for the next run to install, without installing or
+    /// launching anything. Returns the version waiting to be installed, if any.
+    pub async fn predownload<S: ProgressSink>(self, sink: S) -> Result<Option<String>> {
+        let mut config = self.config;
+        select_app(&sink, &self.control.cancellation(), &mut config).await?;
+        Pipeline::new(Arc::new(sink), self.control, self.hooks, config)?.predownload().await
+    }
+}
+
+/// The first 8 characters of the app secret identify the app's directories.
+pub fn app_slug(launcher_data: &LauncherData) -> &str {
+    &launcher_data.app_secret[..8]
+}
+
+/// Share of the package already downloaded by an earlier run, if any.
+fn resume_progress(existing: u64, total: u64) -> Option<f32> {
+    (existing > 0 && existing < total).then(|| existing as f32 / total as f32)
+}
+
+/// Share of all parts downloaded, with `completed` bytes in the finished parts and `bytes` of
+/// `part_total` in the current one. Without sizes from the API only the current part is known.
+fn overall_progress(completed: u64, bytes: u64, part_total: u64, total: u64) -> f32 {
+    if total > 0 {
+        ((completed + bytes) as f32 / total as f32).min(1.0)
+    } else if part_total > 0 {
+        bytes as f32 / part_total as f32
+    } else {
+        0.0
+    }
+}
+
+/// Bytes left to download, with the same knowledge as [`overall_progress`]. `None` when unknown.
+fn remaining_bytes(completed: u64, bytes: u64, part_total: u64, total: u64) -> Option<u64> {
+    if total > 0 {
+        Some(total.saturating_sub(completed + bytes))
+    } else if part_total > 0 {
+        Some(part_total.saturating_sub(bytes))
+    } else {
+        None
+    }
+}
+
+/// Checks a downloaded part has the size the API announced. A short or overlong file is removed
+/// so the retry downloads it again.
+fn verify_part(part: &ContentUrl, path: &Path) -> Result<()> {
+    let size = fs::metadata(path)?.len();
+    if part.size == 0 || size == part.size {
+        return Ok(());
+    }
+    let _ = fs::remove_file(path);
+    Err(crate::Error::Connection(format!(
+        "Downloaded {} of {} from {}",
+        format_size(size),
+        format_size(part.size),
+        part.url
+    )))
+}
+
+/// Checks a downloaded loose file against its hash. A mismatch is removed, so the retry
+/// downloads it again.
+fn verify_file(file: &ContentFile, path: &Path) -> Result<()> {
+    let hash = hash_file(path).with_context(|| format!("Hashing {}", path.display()))?;
+    if hash.eq_ignore_ascii_case(&file.sha256) {
+        return Ok(());
+    }
+    let _ = fs::remove_file(path);
+    Err(crate::Error::Connection(format!("Downloaded {} doesn't match its hash", file.path)))
+}
+
+/// Reports the progress of a download to `sink`, as part of `total` bytes of which `completed`
+/// were downloaded before it.
+fn download_progress<S: ProgressSink>(sink: Arc<S>, completed: u64, total: u64) -> ProgressCallback {
+    Box::new(move |progress| {
+        let percentage = overall_progress(completed, progress.bytes, progress.total_bytes, total);
+        let remaining = remaining_bytes(completed, progress.bytes, progress.total_bytes, total);
+        match progress.stalled_for {
+            Some(stalled_for) => sink.stalled(stalled_for),
+            None => sink.speed(percentage, progress.speed_kbps, remaining.and_then(|remaining| remaining_time(remaining, progress.speed_kbps))),
+        }
+    })
+}
+
+/// Logs a soft failure and shows it to the user without stopping the update.
+fn send_warning(sink: &impl ProgressSink, warning: String) {
+    warn!("{}", warning);
+    sink.warning(warning);
+}
+
+/// Reports the phase, which crash and error reports also keep track of.
+fn set_phase(sink: &impl ProgressSink, phase: Phase) {
+    telemetry::set_last_phase(phase.label());
+    sink.phase(phase);
+}
+
+/// Reports the status, which crash reports also keep track of.
+fn set_status(sink: &impl ProgressSink, status: impl Into<String>) {
+    let status = status.into();
+    crash::set_last_status(&status);
+    sink.message(status);
+}
+
+async fn run_launcher<S: ProgressSink>(sink: S, control: DownloadControl, hooks: Hooks, mut config: RunnerConfig) -> Result<()> {
+    select_app(&sink, &control.cancellation(), &mut config).await?;
+    Pipeline::new(Arc::new(sink), control, hooks, config)?.run().await
+}
+
+/// Points `config` at the app to run when the runner ships several, asking `sink` if it's
+/// unclear which one.
+async fn select_app(sink: &impl ProgressSink, cancellation: &CancellationToken, config: &mut RunnerConfig) -> Result<()> {
+    if config.app.is_none() && config.dat_path.exists() {
+        return Ok(());
+    }
+
+    let apps = apps::discover(&config.apps_dir);
+    let app = match (&config.app, apps.as_slice()) {
+        (Some(id), _) => apps::find(&apps, id).ok_or_else(|| {
+            crate::Error::DatFile(format!("No app {} in {}", id, config.apps_dir.display()))
+        })?,
+        // Reading the missing dat reports the error
+        (None, []) => return Ok(()),
+        (None, [app]) => app,
+        (None, _) => {
+            let names = apps.iter().map(|app| app.name.clone()).collect();
+            let Some(choice) = sink.ask_app(names) else {
+                let ids: Vec<_> = apps.iter().map(|app| app.id.as_str()).collect();
+                return Err(crate::Error::DatFile(format!(
+                    "Several apps are available, choose one with --app: {}",
+                    ids.join(", ")
+                )));
+            };
+            let index = cancellation
+                .run(choice)
+                .await?
+                .map_err(|_| crate::Error::Cancelled("No app chosen".into()))?;
+            apps.get(index).ok_or_else(|| crate::Error::Other(format!("No app at index {}", index)))?
+        },
+    };
+
+    info!("Running app {} ({})", app.id, app.name);
+    config.dat_path = app.dat_path.clone();
+    config.app = Some(app.id.clone());
+    Ok(())
+}
+
+/// Everything the pipeline's states share.
+struct Pipeline<S, A, P, L> {
+    sink: Arc<S>,
+    cancellation: CancellationToken,
+    repair: bool,
+    force_update: bool,
+    network: A,
+    launcher_data: LauncherData,
+    file_manager: P,
+    launcher: L,
+    extract_path: PathBuf,
+    analytics: Option<Analytics>,
+    cdn: Option<CdnFeedback>,
+    hooks: Hooks,
+    deep_link: Option<String>,
+    /// Installed instead of the latest version.
+    pinned_version: Option<String>,
+    checkpoint: Checkpoint,
+    /// State the previous run stopped in, if it didn't finish.
+    previous: Option<RunState>,
+    /// Times the launcher asked to be run again.
+    restarts: u32,
+    /// How often and how far apart failing network steps are attempted.
+    retry: RetryPolicy,
+    /// The network is down and the installed version is launched without updating.
+    offline: bool,
+}
+
+impl<S: ProgressSink> Pipeline<S, NetworkManager, FileManager, Launcher> {
+    fn new(sink: Arc<S>, control: DownloadControl, hooks: Hooks, config: RunnerConfig) -> Result<Self> {
+        let settings = config.settings;
+        // Initialize components
+        info!("Initializing components");
+        let mut network = NetworkManager::new()
+            .with_control(control.clone())
+            .with_bandwidth_schedule(settings.bandwidth_limit_kbps, settings.bandwidth_schedule)
+            .with_download_connections(settings.download_connections.unwrap_or(1));
+        if let Some(proxy) = &settings.proxy {
+            network = network.with_proxy(proxy).context("Setting up the proxy")?;
+        }
+
+        // Read the .dat file first to get the app secret
+        info!("Reading {}", config.dat_path.display());
+        let launcher_data = LauncherData::load(&config.dat_path)
+            .inspect_err(|e| error!("Failed to read {}: {}", config.dat_path.display(), e))?;
+        info!("Successfully read {}", config.dat_path.display());
+        if launcher_data.sign_requests {
+            network = network.with_request_signing(&launcher_data.app_secret);
+        }
+        if launcher_data.verify_responses {
+            network = network.with_response_verification(ResponseVerifier::built_in()?);
+        }
+        if !settings.disable_certificate_pinning {
+            network = network.with_certificate_pins(launcher_data.api_certificate_pins.clone());
+        } else if !launcher_data.api_certificate_pins.is_empty() {
+            warn!("Ignoring the certificate pins of the dat file, pinning is disabled in the settings");
+        }
+
+        // Initialize file manager with the first 8 chars of app secret
+        let app_slug = app_slug(&launcher_data);
+        let mut file_manager = FileManager::new(app_slug)?
+            .with_cancellation(control.cancellation())
+            .with_package_password(launcher_data.package_password());
+        let chosen_dirs = settings.install_dir.is_some() || config.patcher_dir.is_some();
+        if let Some(install_dir) = settings.install_dir {
+            info!("Using install directory from settings: {}", install_dir.display());
+            file_manager = file_manager.with_install_dir(install_dir);
+        }
+        // The apps of a multi-app runner share its directories, each in a subdirectory of its own
+        if let Some(app) = &config.app {
+            let install_dir = file_manager.get_install_dir().join(app);
+            file_manager = file_manager.with_install_dir(install_dir);
+        }
+        let patcher_dir = match (config.patcher_dir, &config.app) {
+            (Some(patcher_dir), _) => Some(patcher_dir),
+            (None, Some(app)) => Some(file_manager.patcher_dir().join(app)),
+            (None, None) => None,
+        };
+        if let Some(patcher_dir) = patcher_dir {
+            file_manager = file_manager.with_patcher_dir(patcher_dir);
+        }
+        // Directories the runner picked move to the user's data directory when they can't be
+        // written, e.g. under Program Files. Only chosen ones need administrator rights.
+        // Known read-only locations skip the attempt, which would leave empty directories.
+        // Apps that moved there once stay, also when the runner's directory becomes writable.
+        let paths = PathProvider::current()?;
+        let installed_for_user = !chosen_dirs && paths.install_scope(app_slug) == InstallScope::User;
+        let read_only = if chosen_dirs || installed_for_user || cfg!(target_os = "macos") { None } else { sandbox::detect() };
+        let writable = match read_only {
+            Some(location) => Err(crate::Error::Permission(format!("The runner is in {}, which is read-only", location))),
+            None => file_manager.check_writable(),
+        };
+        if let Err(e) = writable {
+            if chosen_dirs {
+                return Err(e);
+            }
+            let (mut install_dir, mut patcher_dir) = FileManager::per_user_dirs(app_slug)?;
+            if let Some(app) = &config.app {
+                install_dir.push(app);
+                patcher_dir.push(app);
+            }
+            info!("{}, installing for the current user in {}", e, install_dir.display());
+            file_manager = file_manager.with_install_dir(install_dir).with_patcher_dir(patcher_dir);
+            file_manager.check_writable()?;
+            if let Err(e) = paths.record_install_scope(app_slug, InstallScope::User) {
+                warn!("Failed to remember the app is installed for the current user: {}", e);
+            }
+        }
+        let extract_path = file_manager.patcher_dir().to_path_buf();
+        cleanup::remove_stale(&extract_path, app_slug);
+
+        let analytics = Settings::path().ok().and_then(|path| {
+            Analytics::new(&launcher_data, app_slug, network.clone(), &path.with_file_name(INSTALL_ID_FILE_NAME))
+        });
+        let cdn = CdnFeedback::new(settings.share_download_statistics, network.clone(), app_slug, &extract_path);
+        Ok(Self {
+            sink,
+            cancellation: control.cancellation(),
+            repair: config.repair,
+            force_update: config.force_update,
+            network,
+            launcher_data,
+            file_manager,
+            launcher: Launcher::new(),
+            checkpoint: Checkpoint::new(&extract_path),
+            extract_path,
+            analytics,
+            cdn,
+            hooks,
+            deep_link: config.deep_link,
+            pinned_version: config.pinned_version.or(settings.pinned_version),
+            previous: None,
+            restarts: 0,
+            retry: RetryPolicy::new(settings.retry_attempts.unwrap_or(retry::DEFAULT_ATTEMPTS)),
+            offline: false,
+        })
+    }
+}
+
+impl<S: ProgressSink, A: AppApi, P: PackageStore, L: ProcessLauncher> Pipeline<S, A, P, L> {
+    async fn run(mut self) -> Result<()> {
+        if let Some(analytics) = self.analytics.clone() {
+            tokio::spawn(async move { analytics.send(LifecycleEvent::RunnerStarted).await });
+        }
+
+        // Show the cached news right away, it's replaced once fresh news are fetched
+        if let Some(news) = self.file_manager.load_news() {
+            self.sink.news(news);
+        }
+        self.register_url_scheme();
+
+        // A run that stopped early is continued as far as its files allow
+        self.previous = self.checkpoint.load();
+        if let Some(previous) = &self.previous {
+            info!("Previous run stopped while {}", previous.name());
+        }
+        if self.force_update && self.previous.take().is_some() {
+            info!("Forced update, starting over");
+        }
+
+        let mut state = RunState::CheckingNetwork;
+        while state != RunState::Done {
+            // Checked before the checkpoint, which would otherwise claim a step that never started
+            if let Err(e) = self.cancellation.check() {
+                info!("Update cancelled while {}", state.name());
+                return Err(e);
+            }
+            self.checkpoint.save(&state);
+            debug!("State: {}", state.name());
+            let next = self.step(state.clone()).await?;
+            debug_assert!(state.can_transition_to(&next), "{:?} can't be followed by {:?}", state, next);
+            state = next;
+        }
+        self.checkpoint.clear();
+
+        info!("Runner completed successfully");
+        Ok(())
+    }
+
+    /// Downloads the latest version without installing it, leaving the package for the next run.
+    /// Returns the version waiting to be installed, if any.
+    async fn predownload(mut self) -> Result<Option<String>> {
+        self.previous = self.checkpoint.load();
+        let mut state = RunState::CheckingNetwork;
+        loop {
+            self.cancellation.check()?;
+            state = match state {
+                RunState::Extracting { version, patcher_secret, packages } => {
+                    info!("Version {} is downloaded and ready to install", version);
+                    let downloaded = RunState::Downloaded { version: version.clone(), patcher_secret, packages };
+                    self.checkpoint.save(&downloaded);
+                    return Ok(Some(version));
+                },
+                RunState::Launching | RunState::Done => return Ok(None),
+                // Only the changes are downloaded, right when installing
+                RunState::Patching { .. } | RunState::Syncing { .. } => return Ok(None),
+                state => self.step(state).await?,
+            };
+        }
+    }
+
+    /// Runs `state` and returns the state that follows it.
+    async fn step(&mut self, state: RunState) -> Result<RunState> {
+        match state {
+            RunState::CheckingNetwork => match self.check_network().await {
+                // Without a connection the installed version still starts, told it's offline
+                Err(e) if e.is_transient() && self.can_launch_offline()? => {
+                    send_warning(self.sink.as_ref(), format!("Starting the installed version offline: {}", e));
+                    self.offline = true;
+                    Ok(RunState::Launching)
+                },
+                result => result,
+            },
+            RunState::FetchingInfo => self.fetch_info().await,
+            RunState::Downloading { version, patcher_secret, parts } => {
+                self.download(version, patcher_secret, &parts).await
+            },
+            RunState::Extracting { version, patcher_secret, packages } => {
+                self.extract(version, patcher_secret, packages).await
+            },
+            RunState::Patching { version, patcher_secret, delta } => {
+                self.patch(version, patcher_secret, &delta).await
+            },
+            RunState::Syncing { version, patcher_secret, files } => {
+                self.sync_files(version, patcher_secret, &files).await
+            },
+            RunState::Launching => {
+                let installed_version = self.file_manager.get_current_version()?.map(|info| info.version);
+                self.run_hooks(HookPoint::PreLaunch, installed_version, None)?;
+                let result = launch_from_manifest(
+                    &self.extract_path,
+                    &self.file_manager,
+                    &self.launcher_data,
+                    &self.launcher,
+                    self.passthrough(),
+                    self.offline,
+                    self.sink.as_ref(),
+                );
+                match report_launch(self.analytics.as_ref(), result).await? {
+                    Launched::RestartRequested if self.restarts < MAX_RESTARTS => {
+                        info!("Launcher asked to be run again, checking for updates");
+                        self.restarts += 1;
+                        self.repair = false;
+                        self.force_update = false;
+                        self.previous = None;
+                        // The connection may be back by now
+                        if std::mem::take(&mut self.offline) {
+                            return Ok(RunState::CheckingNetwork);
+                        }
+                        Ok(RunState::FetchingInfo)
+                    },
+                    Launched::RestartRequested => {
+                        warn!("Launcher asked to be run again {} times, stopping", self.restarts + 1);
+                        Ok(RunState::Done)
+                    },
+                    Launched::Started => Ok(RunState::Done),
+                }
+            },
+            // Only left as a checkpoint, never run
+            RunState::Downloaded { .. } | RunState::Done => Ok(RunState::Done),
+        }
+    }
+
+    fn run_hooks(&self, point: HookPoint, version: Option<String>, package: Option<PathBuf>) -> Result<()> {
+        if self.hooks.is_empty() {
+            return Ok(());
+        }
+        info!("Running {} hooks", point.label());
+        let context = HookContext {
+            point,
+            version,
+            install_dir: self.file_manager.get_install_dir().to_path_buf(),
+            patcher_dir: self.extract_path.clone(),
+            package,
+        };
+        self.hooks.run(&context).with_context(|| format!("Running {} hooks", point.label()))
+    }
+
+    /// Adds the app to the installed apps list. The app works without it, so failing is only logged.
+    fn register_uninstall(&self, version: &str) {
+        let result = std::env::current_exe().map_err(crate::Error::from).and_then(|runner_path| {
+            UninstallEntry::new(&self.launcher_data, version, self.file_manager.get_install_dir(), &runner_path).register()
+        });
+        if let Err(e) = result {
+            warn!("Failed to add the app to the installed apps list: {}", e);
+        }
+    }
+
+    /// Makes the app's links open the runner. Links are a convenience, so failing is only logged.
+    fn register_url_scheme(&self) {
+        let Some(scheme) = &self.launcher_data.url_scheme else {
+            return;
+        };
+        let display_name = self.launcher_data.app_display_name.as_deref().unwrap_or(scheme);
+        let result = std::env::current_exe()
+            .map_err(crate::Error::from)
+            .and_then(|runner_path| deeplink::register_scheme(scheme, display_name, &runner_path));
+        if let Err(e) = result {
+            warn!("Failed to register the {}:// URL scheme: {}", scheme, e);
+        }
+    }
+
+    /// Parameters of the deep link the runner was opened with, for the launcher.
+    fn passthrough(&self) -> Option<String> {
+        let link = self.deep_link.as_deref()?;
+        let Some(scheme) = &self.launcher_data.url_scheme else {
+            warn!("Ignoring deep link {}, the app has no URL scheme", link);
+            return None;
+        };
+        match DeepLink::parse(link, scheme) {
+            Ok(deep_link) => {
+                info!("Opened with deep link {}", link);
+                Some(deep_link.passthrough())
+            },
+            Err(e) => {
+                warn!("Ignoring {}", e);
+                None
+            },
+        }
+    }
+
+    /// Whether the installed version can be started without updating it first.
+    fn can_launch_offline(&self) -> Result<bool> {
+        if self.force_update || self.repair || self.previous.as_ref().is_some_and(RunState::interrupted_install) {
+            return Ok(false);
+        }
+        Ok(self.file_manager.get_current_version()?.is_some() && self.extract_path.join("patcher.manifest").exists())
+    }
+
+    async fn check_network(&self) -> Result<RunState> {
+        info!("Checking network connection");
+        set_phase(self.sink.as_ref(), Phase::Connect);
+        set_status(self.sink.as_ref(), "Checking network connection...");
+
+        with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || async {
+            match self.network.check_connection().await? {
+                true => Ok(()),
+                false => Err(crate::Error::Connection("No internet connection".into())),
+            }
+        }).instrument(info_span!("connect")).await?;
+        info!("Network connection established");
+        Ok(RunState::FetchingInfo)
+    }
+
+    async fn fetch_info(&self) -> Result<RunState> {
+        let network = &self.network;
+        let launcher_data = &self.launcher_data;
+        let file_manager = &self.file_manager;
+
+        // Get app info to determine the correct patcher secret
+        info!("Fetching app info");
+        set_phase(self.sink.as_ref(), Phase::AppInfo);
+        set_status(self.sink.as_ref(), "Fetching app info...");
+        let app_info = with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || network.get_app_info(&launcher_data.app_secret))
+            .instrument(info_span!("api"))
+            .await
+            .context("Fetching app info")?;
+        info!("Got app info: {:?}", app_info);
+
+        // News are optional, so failing to fetch them must not block the update
+        match network.get_news(&launcher_data.app_secret).await {
+            Ok(news) => {
+                if let Err(e) = file_manager.save_news(news.as_deref()) {
+                    warn!("Failed to cache news: {}", e);
+                }
+                if let Some(news) = news {
+                    self.sink.news(news);
+                }
+            },
+            Err(e) => warn!("Failed to fetch news: {}", e),
+        }
+
+        // Determine which patcher secret to use
+        let patcher_secret = app_info.patcher_secret
+            .unwrap_or_else(|| launcher_data.patcher_secret.clone());
+        info!("Using patcher secret: {}", patcher_secret);
+
+        // Get latest version, unless a version is pinned
+        set_phase(self.sink.as_ref(), Phase::Version);
+        let version = match &self.pinned_version {
+            Some(pinned) => {
+                warn!("Version {} is pinned, not looking for the latest version", pinned);
+                pinned.clone()
+            },
+            None => {
+                info!("Fetching latest version");
+                set_status(self.sink.as_ref(), "Fetching latest version...");
+                let version = with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || network.get_latest_version(&patcher_secret))
+                    .instrument(info_span!("api"))
+                    .await
+                    .context("Fetching latest version")?;
+                info!("Latest version: {}", version);
+                version
+            },
+        };
+        self.sink.version_info(launcher_data.app_display_name.clone(), version.clone());
+
+        // An extraction that didn't finish left the installed files incomplete
+        let interrupted = self.previous.as_ref().is_some_and(RunState::interrupted_install);
+        if interrupted {
+            warn!("The previous update didn't finish, reinstalling");
+        }
+
+        // Check if we need to update
+        if self.repair {
+            info!("Repair requested, verifying installed files");
+            set_status(self.sink.as_ref(), "Verifying installed files...");
+            match file_manager.verify_files() {
+                Ok(damaged) if damaged.is_empty() => info!("All installed files are intact, reinstalling anyway"),
+                Ok(damaged) => {
+                    warn!("{} installed files are missing or modified", damaged.len());
+                    for path in &damaged {
+                        debug!("Damaged file: {}", path.display());
+                    }
+                },
+                Err(e) => {
+                    send_warning(self.sink.as_ref(), format!("Couldn't verify installed files: {}", e));
+                },
+            }
+        } else if self.force_update {
+            info!("Forced update requested, reinstalling");
+        } else {
+            info!("Checking if update is needed");
+        }
+        let reinstall = self.repair || self.force_update;
+        if !reinstall && !interrupted {
+            match file_manager.repair_version_record(&version, &patcher_secret) {
+                Ok(true) => info!("Installed files are intact, not downloading version {} again", version),
+                Ok(false) => {},
+                Err(e) => warn!("Failed to repair the version record: {}", e),
+            }
+        }
+        if !reinstall && !interrupted && !file_manager.needs_update(&version, &patcher_secret)? {
+            info!("Already have the latest version {}, skipping update", version);
+            return Ok(RunState::Launching);
+        }
+        info!("Update needed to version {}", version);
+
+        // The changelog is only informational, so failing to fetch it must not block the update
+        match network.get_changelog(&patcher_secret, &version).await {
+            Ok(Some(changelog)) => {
+                self.sink.changelog(changelog);
+            },
+            Ok(None) => debug!("No changelog for version {}", version),
+            Err(e) => warn!("Failed to fetch changelog: {}", e),
+        }
+
+        // Optional updates let the user keep playing the installed version
+        let policy = &launcher_data.update_policy;
+        if policy.mode == UpdateMode::Prompt && !reinstall && !interrupted && file_manager.get_current_version()?.is_some() {
+            if let Some(choice) = self.sink.ask_update(version.clone(), policy.countdown()) {
+                if self.cancellation.run(choice).await? == Ok(UpdateChoice::LaunchInstalled) {
+                    info!("Launching the installed version instead of updating to {}", version);
+                    return Ok(RunState::Launching);
+                }
+            }
+        }
+
+        if launcher_data.loose_files {
+            info!("Getting the files of version {}", version);
+            set_status(self.sink.as_ref(), "Getting file list...");
+            let files = with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || network.get_content_files(&patcher_secret, &version))
+                .instrument(info_span!("api"))
+                .await
+                .context("Getting file list")?;
+            if files.is_empty() {
+                warn!("No content files found");
+                return Ok(RunState::Done);
+            }
+            return Ok(RunState::Syncing { version, patcher_secret, files });
+        }
+
+        // Only the changes are downloaded when the API has a delta from the installed version
+        let installed = file_manager.get_current_version()?.filter(|installed| installed.patcher_secret == patcher_secret);
+        let resumable = matches!(self.previous, Some(RunState::Downloading { .. } | RunState::Downloaded { .. }));
+        if let Some(installed) = installed.filter(|_| !reinstall && !interrupted && !resumable) {
+            match network.get_delta_url(&patcher_secret, &version, &installed.version).await {
+                Ok(Some(delta)) => {
+                    info!("Updating from version {} with a delta of {}", installed.version, format_size(delta.size));
+                    return Ok(RunState::Patching { version, patcher_secret, delta });
+                },
+                Ok(None) => debug!("No delta from version {}", installed.version),
+                Err(e) => warn!("Failed to fetch the delta URL: {}", e),
+            }
+        }
+
+        self.full_download(version, patcher_secret).await
+    }
+
+    /// Fetches the content URLs of `version` for the whole package to be downloaded.
+    async fn full_download(&self, version: String, patcher_secret: String) -> Result<RunState> {
+        let network = &self.network;
+        info!("Getting download URLs");
+        set_status(self.sink.as_ref(), "Getting download URLs...");
+        let content_urls = with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || network.get_content_urls(&patcher_secret, &version))
+            .instrument(info_span!("api"))
+            .await
+            .context("Getting download URLs")?;
+
+        if content_urls.is_empty() {
+            warn!("No content URLs found");
+            return Ok(RunState::Done);
+        }
+        let content_urls = parts_for_arch(content_urls, arch::compatible_here());
+        if content_urls.is_empty() {
+            return Err(crate::Error::Other(format!(
+                "Version {} has no content for this machine ({} {})",
+                version,
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )));
+        }
+        for content in &content_urls {
+            info!("Found content URL: {} ({})", content.url, format_size(content.size));
+        }
+        Ok(RunState::Downloading {
+            version,
+            patcher_secret,
+            parts: content_urls,
+        })
+    }
+
+    async fn download(&self, version: String, patcher_secret: String, parts: &[ContentUrl]) -> Result<RunState> {
+        // Download launcher package
+        info!("Downloading launcher package");
+        set_phase(self.sink.as_ref(), Phase::Download);
+
+        // The previous run may have stopped after downloading this version
+        if let Some(packages) = self.previous.as_ref().and_then(|previous| previous.downloaded_packages(&version)) {
+            info!("Using the package downloaded by the previous run: {}", packages[0].display());
+            let packages = packages.to_vec();
+            return Ok(RunState::Extracting { version, patcher_secret, packages });
+        }
+
+        // A download interrupted by a previous run continues where it stopped
+        let packages = (0..parts.len())
+            .map(|part| self.file_manager.partial_download_path(&version, part))
+            .collect::<Result<Vec<_>>>()
+            .context("Preparing download")?;
+        if self.force_update {
+            for package in &packages {
+                let _ = fs::remove_file(package);
+            }
+        }
+        self.run_hooks(HookPoint::PreDownload, Some(version.clone()), Some(packages[0].clone()))?;
+        let total: u64 = parts.iter().map(|part| part.size).sum();
+        let existing: u64 = packages.iter().map(|package| fs::metadata(package).map_or(0, |metadata| metadata.len())).sum();
+        match resume_progress(existing, total) {
+            Some(progress) => {
+                info!("Found partial download of {}", format_size(existing));
+                set_status(self.sink.as_ref(), "Resuming download...");
+                self.sink.percentage(progress);
+            },
+            None => {
+                set_status(self.sink.as_ref(), "Downloading launcher...");
+            },
+        }
+
+        // Parts are downloaded one after another, progress is reported over all of them
+        let http = HttpDownloader(&self.network);
+        let torrent = TorrentDownloader(&self.network);
+        let available = [http.transport(), torrent.transport()];
+        let mut completed = 0;
+        for (index, (part, download_path)) in parts.iter().zip(&packages).enumerate() {
+            if parts.len() > 1 {
+                info!("Downloading part {} of {}", index + 1, parts.len());
+            }
+            let mut transport = select_transport(part, &available);
+            debug!("Downloading part {} over {:?}", index + 1, transport);
+            // The preferred mirror comes first, the others take over when it fails
+            let preferred = self.cdn.as_ref().map_or(part.url.as_str(), |cdn| cdn.choose_url(part));
+            let mirrors: Vec<String> = part.urls(preferred).into_iter().map(str::to_string).collect();
+            let mut mirror = 0;
+            let mut url = mirrors[0].clone();
+            let started = Instant::now();
+            let resumed_from = fs::metadata(download_path).map_or(0, |metadata| metadata.len());
+            let mut attempts = 0;
+            let mut refreshes = 0;
+            let result = loop {
+                // Each retry continues the partial download
+                let mut step = || {
+                    attempts += 1;
+                    async {
+                        let progress = download_progress(self.sink.clone(), completed, total);
+                        match transport {
+                            Transport::Torrent => torrent.download(part, &url, download_path, progress).await?,
+                            _ => http.download(part, &url, download_path, progress).await?,
+                        }
+                        verify_part(part, download_path)
+                    }
+                };
+                // Only the last mirror is retried, a failing one is left for the next right away
+                let result = if mirror + 1 < mirrors.len() {
+                    step().instrument(info_span!("download")).await
+                } else {
+                    with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, step).instrument(info_span!("download")).await
+                };
+                match result {
+                    Err(crate::Error::ExpiredUrl(_)) if refreshes < URL_REFRESHES => {
+                        refreshes += 1;
+                        warn!("Download URL of part {} was refused, fetching a fresh one", index + 1);
+                        url = self.refresh_url(&version, &patcher_secret, index).await?;
+                    },
+                    Err(e) if mirror + 1 < mirrors.len() && (e.is_transient() || matches!(e.root(), crate::Error::Network(_))) => {
+                        mirror += 1;
+                        url = mirrors[mirror].clone();
+                        warn!("Downloading part {} failed: {}, switching to mirror {}", index + 1, e, host(&url).unwrap_or_default());
+                    },
+                    // The CDN is the last resort, for when the torrent or all of its seeds fail
+                    Err(e) if transport == Transport::Torrent && !matches!(e.root(), crate::Error::Cancelled(_)) => {
+                        warn!("Downloading part {} over the torrent failed: {}, falling back to HTTP", index + 1, e);
+                        transport = Transport::Http;
+                        mirror = 0;
+                        url = mirrors[0].clone();
+                        let _ = fs::remove_file(torrent::pieces_path(download_path));
+                    },
+                    result => break result,
+                }
+            };
+            if result.is_ok() {
+                info!("Downloaded part {} from {}", index + 1, host(&url).unwrap_or_default());
+            }
+            if let Some(cdn) = &self.cdn {
+                let downloaded = fs::metadata(download_path).map_or(0, |metadata| metadata.len());
+                let failed = if result.is_ok() { attempts - 1 } else { attempts };
+                cdn.report(&version, &url, downloaded.saturating_sub(resumed_from), started.elapsed(), failed, result.is_ok()).await;
+            }
+            result.context("Downloading launcher")?;
+            completed += part.size;
+        }
+
+        // The volumes of a split archive are joined into one
+        let groups = archive_groups(parts);
+        let packages = if groups.len() == packages.len() {
+            packages
+        } else {
+            set_status(self.sink.as_ref(), "Joining downloaded volumes...");
+            let assemble = |group: Range<usize>| {
+                if group.len() == 1 {
+                    return Ok(packages[group.start].clone());
+                }
+                // Broken volumes would fail again, so the next run downloads them anew
+                volumes::assemble(&packages[group.clone()]).inspect_err(|_| {
+                    for volume in &packages[group] {
+                        let _ = fs::remove_file(volume);
+                    }
+                })
+            };
+            groups.into_iter().map(assemble).collect::<Result<Vec<_>>>().context("Joining split archive")?
+        };
+
+        info!("Download complete: {}", packages[0].display());
+        Ok(RunState::Extracting { version, patcher_secret, packages })
+    }
+
+    /// A fresh URL of the `index`th part of `version`, for one the CDN refused.
+    async fn refresh_url(&self, version: &str, patcher_secret: &str, index: usize) -> Result<String> {
+        set_status(self.sink.as_ref(), "Refreshing download URL...");
+        let parts = with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || self.network.get_content_urls(patcher_secret, version))
+            .instrument(info_span!("api"))
+            .await
+            .context("Refreshing download URLs")?;
+        let parts = parts_for_arch(parts, arch::compatible_here());
+        let part = parts.get(index).ok_or_else(|| crate::Error::Other(format!("Version {} lost part {} of its content", version, index + 1)))?;
+        set_status(self.sink.as_ref(), "Resuming download...");
+        Ok(self.cdn.as_ref().map_or(part.url.as_str(), |cdn| cdn.choose_url(part)).to_string())
+    }
+
+    async fn extract(&mut self, version: String, patcher_secret: String, packages: Vec<PathBuf>) -> Result<RunState> {
+        // Extract package
+        info!("Extracting launcher package");
+        set_phase(self.sink.as_ref(), Phase::Extract);
+        set_status(self.sink.as_ref(), "Extracting launcher...");
+        let file_manager = &mut self.file_manager;
+
+        let extract_span = info_span!("extract").entered();
+
+        let installed_version = file_manager.get_current_version()?.map(|info| info.version);
+
+        // Remove old files before extracting new ones. Their version is gone with them, so an
+        // update that doesn't finish leaves no record of files that aren't there.
+        info!("Removing old files");
+        file_manager.remove_old_files().context("Removing old files")?;
+        file_manager.remove_version().context("Removing the version record")?;
+
+        // Extract to Patcher directory in the install directory
+        if let Err(e) = file_manager.extract_zips(&packages, &self.extract_path) {
+            // A broken package would fail again, so the next run downloads it anew. A cancelled
+            // extraction is finished from the same package instead.
+            if !matches!(e.root(), crate::Error::Cancelled(_)) {
+                for package in &packages {
+                    let _ = fs::remove_file(package);
+                }
+            }
+            return Err(e).context("Extracting launcher");
+        }
+        info!("Extraction complete: {}", self.extract_path.display());
+        drop(extract_span);
+
+        // Save the current version
+        info!("Saving version information");
+        self.finish_install(version, &patcher_secret, installed_version, Some(packages[0].clone())).await?;
+
+        // Clean up the downloaded package
+        for package in &packages {
+            if let Err(e) = fs::remove_file(package) {
+                // Non-critical error, continue execution
+                send_warning(self.sink.as_ref(), format!("Couldn't remove the downloaded package: {}", e));
+            }
+        }
+        Ok(RunState::Launching)
+    }
+
+    /// Downloads the loose files that changed since the installed version into place, each
+    /// checked against its hash before it replaces the installed one.
+    async fn sync_files(&mut self, version: String, patcher_secret: String, files: &[ContentFile]) -> Result<RunState> {
+        info!("Syncing the files of version {}", version);
+        set_phase(self.sink.as_ref(), Phase::Download);
+        set_status(self.sink.as_ref(), "Checking installed files...");
+        let installed_version = self.file_manager.get_current_version()?.map(|info| info.version);
+        let outdated = self.file_manager.outdated_files(files, &self.extract_path).context("Checking installed files")?;
+        info!("Downloading {} of {} files", outdated.len(), files.len());
+        self.run_hooks(HookPoint::PreDownload, Some(version.clone()), None)?;
+        set_status(self.sink.as_ref(), "Downloading launcher...");
+
+        let total: u64 = outdated.iter().map(|file| file.size).sum();
+        let mut completed = 0;
+        for file in &outdated {
+            let target = self.extract_path.join(file.relative_path()?);
+            let mut download_name = target.file_name().unwrap_or_default().to_os_string();
+            download_name.push(".part");
+            let download_path = target.with_file_name(download_name);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+            }
+            with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || async {
+                let progress = download_progress(self.sink.clone(), completed, total);
+                self.network.download_file(&file.url, &download_path, progress).await?;
+                verify_file(file, &download_path)
+            }).instrument(info_span!("download")).await.with_context(|| format!("Downloading {}", file.path))?;
+            fs::rename(&download_path, &target).with_context(|| format!("Replacing {}", target.display()))?;
+            completed += file.size;
+        }
+
+        self.file_manager.install_files(files, &self.extract_path).context("Recording installed files")?;
+        self.finish_install(version, &patcher_secret, installed_version, None).await?;
+        Ok(RunState::Launching)
+    }
+
+    /// Downloads the delta from the installed version and applies it. When that fails, the whole
+    /// version is downloaded instead.
+    async fn patch(&mut self, version: String, patcher_secret: String, delta: &ContentUrl) -> Result<RunState> {
+        let installed_version = self.file_manager.get_current_version()?.map(|info| info.version);
+        match self.apply_delta(&version, delta).await {
+            Ok(()) => {
+                self.finish_install(version, &patcher_secret, installed_version, None).await?;
+                Ok(RunState::Launching)
+            },
+            Err(e) if matches!(e.root(), crate::Error::Cancelled(_)) => Err(e),
+            Err(e) => {
+                warn!("Delta update failed, downloading the whole version: {}", e);
+                self.full_download(version, patcher_secret).await
+            },
+        }
+    }
+
+    async fn apply_delta(&mut self, version: &str, delta: &ContentUrl) -> Result<()> {
+        info!("Downloading delta");
+        set_phase(self.sink.as_ref(), Phase::Download);
+        set_status(self.sink.as_ref(), "Downloading update...");
+        let download_path = self.file_manager.partial_download_path(&format!("{}.delta", version), 0)?;
+        self.run_hooks(HookPoint::PreDownload, Some(version.to_string()), None)?;
+        with_retry(self.sink.as_ref(), &self.cancellation, &self.retry, || async {
+            let progress = download_progress(self.sink.clone(), 0, delta.size);
+            self.network.download_file(&delta.url, &download_path, progress).await?;
+            verify_part(delta, &download_path)
+        }).instrument(info_span!("download")).await.context("Downloading delta")?;
+
+        info!("Applying delta");
+        set_phase(self.sink.as_ref(), Phase::Extract);
+        set_status(self.sink.as_ref(), "Applying update...");
+        let result = {
+            let _span = info_span!("extract").entered();
+            self.file_manager.apply_delta(&download_path, &self.extract_path)
+        };
+        let _ = fs::remove_file(&download_path);
+        result.context("Applying delta")
+    }
+
+    /// Records `version` as installed in place of `installed_version`, and tells the analytics
+    /// and the hooks.
+    async fn finish_install(&self, version: String, patcher_secret: &str, installed_version: Option<String>, package: Option<PathBuf>) -> Result<()> {
+        self.file_manager.save_version(&version, patcher_secret).context("Saving version information")?;
+        info!("Version {} saved", version);
+        if installed_version.is_none() && self.launcher_data.register_uninstall {
+            self.register_uninstall(&version);
+        }
+        if let Some(analytics) = &self.analytics {
+            analytics.send(LifecycleEvent::UpdatePerformed {
+                from_version: installed_version,
+                to_version: version.clone(),
+            }).await;
+        }
+        self.run_hooks(HookPoint::PostExtract, Some(version), package)
+    }
+}
+
+/// How a launch ended.
+#[derive(Debug, PartialEq)]
+enum Launched {
+    Started,
+    /// A monitored launcher exited with [`RESTART_EXIT_CODE`].
+    RestartRequested,
+}
+
+/// Tells analytics whether the launch worked and passes its result on.
+async fn report_launch<T>(analytics: Option<&Analytics>, result: Result<T>) -> Result<T> {
+    if let Some(analytics) = analytics {
+        let event = match &result {
+            Ok(_) => LifecycleEvent::LaunchSucceeded,
+            Err(e) => LifecycleEvent::LaunchFailed { code: e.code() },
+        };
+        analytics.send(event).await;
+    }
+    result
+}
+
+fn launch_from_manifest(
+    extract_path: &Path,
+    file_manager: &impl PackageStore,
+    launcher_data: &LauncherData,
+    launcher: &impl ProcessLauncher,
+    passthrough: Option<String>,
+    offline: bool,
+    sink: &impl ProgressSink,
+) -> Result<Launched> {
+    let _span = info_span!("launch").entered();
+
+    // Read manifest
+    info!("Reading manifest file {}", extract_path.join("patcher.manifest").display());
+    let manifest_path = extract_path.join("patcher.manifest");
+    let manifest_content = std::fs::read_to_string(&manifest_path)
+        .map_err(|e| {
+            error!("Failed to read manifest: {}", e);
+            crate::Error::Manifest(format!("Failed to read manifest: {}", e))
+        })?;
+    let mut manifest = ManifestManager::new(&manifest_content)?;
+    info!("Successfully read manifest");
+    if let Some(warning) = manifest.check_runner_version(RUNNER_VERSION)? {
+        send_warning(sink, warning);
+    }
+    // Lets the launcher check the runner's version, e.g. before relying on newer behavior
+    if let Err(e) = fs::write(extract_path.join(RUNNER_VERSION_FILE_NAME), RUNNER_VERSION) {
+        warn!("Failed to write {}: {}", RUNNER_VERSION_FILE_NAME, e);
+    }
+
+    // Set up manifest variables
+    info!("Setting up manifest variables");
+    manifest.set_variable("exedir", extract_path.to_string_lossy().into());
+    manifest.set_variable("installdir", file_manager.get_install_dir().to_string_lossy().into());
+    let encoded_secret = crate::config::secret::encode_secret(&launcher_data.app_secret);
+    manifest.set_variable("secret", encoded_secret);
+    manifest.set_variable("lockfile", "launcher.lock".into());
+    manifest.set_variable("network-status", if offline { "offline" } else { "online" }.into());
+    manifest.set_variable("runner-version", RUNNER_VERSION.into());
+    manifest.set_optional_variable(PASSTHROUGH_VARIABLE, passthrough);
+
+    // Launch the executable
+    info!("Launching executable");
+    set_phase(sink, Phase::Launch);
+    set_status(sink, "Launching...");
+    let target = manifest.get_target()?;
+    let arguments = manifest.get_arguments()?;
+    info!("Launching {} with arguments: {:?}", target.display(), arguments);
+    if manifest.monitors() {
+        let code = launcher.run_executable(&target, &arguments)
+            .map_err(|e| crate::Error::Launch(e.to_string()))?;
+        if code == RESTART_EXIT_CODE {
+            return Ok(Launched::RestartRequested);
+        }
+        if code != 0 {
+            return Err(crate::Error::Launch(format!("Launcher exited with code {}", code)));
+        }
+    } else {
+        launcher.launch_executable(&target, &arguments)
+            .map_err(|e| crate::Error::Launch(e.to_string()))?;
+        info!("Launcher started successfully");
+    }
+
+    sink.percentage(1.0);
+    sink.finished();
+    Ok(Launched::Started)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::apps::APPS_DIR_NAME;
+    use crate::config::{Branding, UpdatePolicy, DAT_FILE_NAME};
+    use crate::file::{MockPackageStore, VersionInfo};
+    use crate::launcher::MockProcessLauncher;
+    use crate::network::{AppInfo, MockAppApi};
+    use crate::runner::progress::NoProgress;
+    use mockall::predicate::eq;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    const MANIFEST: &str = r#"{"manifest_version": 4, "target": "{exedir}/app", "target_arguments": [], "capabilities": []}"#;
+
+    /// An API that has version 2 of the app.
+    fn api() -> MockAppApi {
+        let mut network = MockAppApi::new();
+        network.expect_check_connection().returning(|| Ok(true));
+        network.expect_get_app_info().returning(|secret| {
+            Ok(AppInfo { id: 1, patcher_secret: Some("patchersecret".into()), secret: secret.into() })
+        });
+        network.expect_get_news().returning(|_| Ok(None));
+        network.expect_get_latest_version().returning(|_| Ok("2".into()));
+        network.expect_get_changelog().returning(|_, _| Ok(None));
+        network
+    }
+
+    /// A store with version 1 installed, extracting to `dir`.
+    fn store(dir: &Path) -> MockPackageStore {
+        let mut file_manager = MockPackageStore::new();
+        file_manager.expect_get_install_dir().return_const(dir.to_path_buf());
+        file_manager.expect_get_current_version()
+            .returning(|| Ok(Some(VersionInfo::new("1".into(), "patchersecret".into()))));
+        file_manager.expect_repair_version_record().returning(|_, _| Ok(false));
+        file_manager.expect_load_news().returning(|| None);
+        file_manager.expect_save_news().returning(|_| Ok(()));
+        file_manager
+    }
+
+    fn pipeline(
+        dir: &Path,
+        network: MockAppApi,
+        file_manager: MockPackageStore,
+        launcher: MockProcessLauncher,
+    ) -> Pipeline<NoProgress, MockAppApi, MockPackageStore, MockProcessLauncher> {
+        fs::write(dir.join("patcher.manifest"), MANIFEST).unwrap();
+        Pipeline {
+            sink: Arc::new(NoProgress),
+            cancellation: CancellationToken::default(),
+            repair: false,
+            force_update: false,
+            network,
+            launcher_data: LauncherData {
+                patcher_secret: "patchersecret".into(),
+                app_secret: "appsecret123".into(),
+                app_display_name: None,
+                app_author: None,
+                app_identifier: None,
+                branding: Branding::default(),
+                update_policy: UpdatePolicy::default(),
+                telemetry: None,
+                disable_analytics: true,
+                register_uninstall: false,
+                url_scheme: None,
+                package_key: None,
+                sign_requests: false,
+                loose_files: false,
+                api_certificate_pins: Vec::new(),
+                verify_responses: false,
+            },
+            file_manager,
+            launcher,
+            extract_path: dir.to_path_buf(),
+            analytics: None,
+            cdn: None,
+            hooks: Hooks::default(),
+            deep_link: None,
+            pinned_version: None,
+            checkpoint: Checkpoint::new(dir),
+            previous: None,
+            restarts: 0,
+            retry: RetryPolicy { base_delay: Duration::ZERO, ..RetryPolicy::default() },
+            offline: false,
+        }
+    }
+
+    #[test]
+    fn test_resume_progress() {
+        assert_eq!(resume_progress(0, 100), None);
+        assert_eq!(resume_progress(25, 100), Some(0.25));
+        // A complete or oversized file is downloaded again
+        assert_eq!(resume_progress(100, 100), None);
+    }
+
+    #[test]
+    fn test_overall_progress() {
+        assert_eq!(overall_progress(0, 25, 50, 100), 0.25);
+        assert_eq!(overall_progress(50, 25, 50, 100), 0.75);
+        assert_eq!(overall_progress(0, 25, 50, 0), 0.5);
+        assert_eq!(overall_progress(0, 25, 0, 0), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_current_version_is_launched() {
+        let dir = tempdir().unwrap();
+        let mut network = api();
+        network.expect_download_file().never();
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().with(eq("2"), eq("patchersecret")).returning(|_, _| Ok(false));
+        file_manager.expect_extract_zips().never();
+        let mut launcher = MockProcessLauncher::new();
+        let target = dir.path().join("app");
+        launcher.expect_launch_executable()
+            .withf(move |executable, arguments| executable == target && arguments.is_empty())
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        pipeline(dir.path(), network, file_manager, launcher).run().await.unwrap();
+        assert!(!dir.path().join(state::CHECKPOINT_FILE_NAME).exists());
+        assert_eq!(fs::read_to_string(dir.path().join(RUNNER_VERSION_FILE_NAME)).unwrap(), RUNNER_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_monitored_launcher_is_run_again() {
+        let dir = tempdir().unwrap();
+        let mut network = api();
+        network.expect_download_file().never();
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().times(2).returning(|_, _| Ok(false));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().never();
+        let mut runs = 0;
+        launcher.expect_run_executable().times(2).returning(move |_, _| {
+            runs += 1;
+            Ok(if runs == 1 { RESTART_EXIT_CODE } else { 0 })
+        });
+
+        let pipeline = pipeline(dir.path(), network, file_manager, launcher);
+        fs::write(
+            dir.path().join("patcher.manifest"),
+            r#"{"manifest_version": 4, "target": "{exedir}/app", "target_arguments": [], "capabilities": [], "monitor": true}"#,
+        )
+        .unwrap();
+        pipeline.run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pinned_version_skips_latest_lookup() {
+        let dir = tempdir().unwrap();
+        // No latest version expected
+        let mut network = MockAppApi::new();
+        network.expect_check_connection().returning(|| Ok(true));
+        network.expect_get_app_info().returning(|secret| {
+            Ok(AppInfo { id: 1, patcher_secret: Some("patchersecret".into()), secret: secret.into() })
+        });
+        network.expect_get_news().returning(|_| Ok(None));
+        network.expect_get_changelog().returning(|_, _| Ok(None));
+        network.expect_get_content_urls().never();
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().with(eq("1"), eq("patchersecret")).times(1).returning(|_, _| Ok(false));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        let mut pipeline = pipeline(dir.path(), network, file_manager, launcher);
+        pipeline.pinned_version = Some("1".into());
+        pipeline.run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_is_downloaded_and_extracted() {
+        let dir = tempdir().unwrap();
+        let packages = vec![dir.path().join("launcher-2.zip.part"), dir.path().join("launcher-2.1.zip.part")];
+        let mut network = api();
+        network.expect_get_delta_url().returning(|_, _, _| Ok(None));
+        network.expect_get_content_urls()
+            .with(eq("patchersecret"), eq("2"))
+            .returning(|_, _| Ok(vec![
+                ContentUrl { size: 3, url: "https://example.com/2.zip".into(), arch: None, mirrors: Vec::new(), continued: false, transports: Vec::new(), torrent: None },
+                ContentUrl { size: 5, url: "https://example.com/2.1.zip".into(), arch: None, mirrors: Vec::new(), continued: false, transports: Vec::new(), torrent: None },
+            ]));
+        // The second part arrives short once, and is downloaded again
+        let mut short = true;
+        network.expect_download_file().times(3).returning(move |url, path, _| {
+            let content = match url {
+                "https://example.com/2.1.zip" if std::mem::take(&mut short) => "zip",
+                "https://example.com/2.1.zip" => "zip 2",
+                _ => "zip",
+            };
+            Ok(fs::write(path, content)?)
+        });
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().returning(|_, _| Ok(true));
+        let download_paths = packages.clone();
+        file_manager.expect_partial_download_path().returning(move |_, part| Ok(download_paths[part].clone()));
+        file_manager.expect_remove_old_files().times(1).returning(|| Ok(()));
+        file_manager.expect_remove_version().times(1).returning(|| Ok(()));
+        let extracted = packages.clone();
+        file_manager.expect_extract_zips()
+            .withf(move |zip_paths, _| zip_paths == extracted)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        file_manager.expect_save_version().with(eq("2"), eq("patchersecret")).times(1).returning(|_, _| Ok(()));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        pipeline(dir.path(), network, file_manager, launcher).run().await.unwrap();
+        // The packages are removed once extracted
+        assert!(packages.iter().all(|package| !package.exists()));
+    }
+
+    #[tokio::test]
+    async fn test_failed_delta_falls_back_to_the_whole_version() {
+        let dir = tempdir().unwrap();
+        let mut network = api();
+        network.expect_get_delta_url()
+            .with(eq("patchersecret"), eq("2"), eq("1"))
+            .returning(|_, _, _| Ok(Some(ContentUrl { size: 5, url: "https://example.com/1-2.delta.zip".into(), arch: None, mirrors: Vec::new(), continued: false, transports: Vec::new(), torrent: None })));
+        network.expect_get_content_urls().times(1).returning(|_, _| {
+            Ok(vec![ContentUrl { size: 3, url: "https://example.com/2.zip".into(), arch: None, mirrors: Vec::new(), continued: false, transports: Vec::new(), torrent: None }])
+        });
+        network.expect_download_file().times(2).returning(|url, path, _| {
+            Ok(fs::write(path, if url.ends_with(".delta.zip") { "delta" } else { "zip" })?)
+        });
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().returning(|_, _| Ok(true));
+        let patcher_dir = dir.path().to_path_buf();
+        file_manager.expect_partial_download_path()
+            .returning(move |version, _| Ok(patcher_dir.join(format!("launcher-{}.zip.part", version))));
+        file_manager.expect_apply_delta()
+            .times(1)
+            .returning(|_, _| Err(crate::Error::Other("Patched app doesn't match its hash".into())));
+        file_manager.expect_remove_old_files().times(1).returning(|| Ok(()));
+        file_manager.expect_remove_version().times(1).returning(|| Ok(()));
+        file_manager.expect_extract_zips().times(1).returning(|_, _| Ok(()));
+        file_manager.expect_save_version().with(eq("2"), eq("patchersecret")).times(1).returning(|_, _| Ok(()));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        pipeline(dir.path(), network, file_manager, launcher).run().await.unwrap();
+        assert!(!dir.path().join("launcher-2.delta.zip.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_forced_update_downloads_again() {
+        let dir = tempdir().unwrap();
+        let package = dir.path().join("launcher-2.zip.part");
+        fs::write(&package, "old").unwrap();
+        let mut network = api();
+        network.expect_get_content_urls()
+            .returning(|_, _| Ok(vec![ContentUrl { size: 3, url: "https://example.com/2.zip".into(), arch: None, mirrors: Vec::new(), continued: false, transports: Vec::new(), torrent: None }]));
+        // The leftover partial download isn't resumed
+        network.expect_download_file().times(1).returning(|_, path, _| {
+            assert!(!path.exists());
+            Ok(fs::write(path, "zip")?)
+        });
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().never();
+        let download_path = package.clone();
+        file_manager.expect_partial_download_path().returning(move |_, _| Ok(download_path.clone()));
+        file_manager.expect_remove_old_files().returning(|| Ok(()));
+        file_manager.expect_remove_version().returning(|| Ok(()));
+        file_manager.expect_extract_zips().times(1).returning(|_, _| Ok(()));
+        file_manager.expect_save_version().with(eq("2"), eq("patchersecret")).times(1).returning(|_, _| Ok(()));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        let mut pipeline = pipeline(dir.path(), network, file_manager, launcher);
+        pipeline.force_update = true;
+        pipeline.run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expired_url_is_fetched_again() {
+        let dir = tempdir().unwrap();
+        let package = dir.path().join("launcher-2.zip.part");
+        let mut network = api();
+        network.expect_get_delta_url().returning(|_, _, _| Ok(None));
+        let mut signature = 0;
+        network.expect_get_content_urls().times(2).returning(move |_, _| {
+            signature += 1;
+            let url = format!("https://example.com/2.zip?signature={}", signature);
+            Ok(vec![ContentUrl { size: 7, url, arch: None, mirrors: Vec::new(), continued: false, transports: Vec::new(), torrent: None }])
+        });
+        // The first URL expires halfway, the fresh one continues from there
+        network.expect_download_file().times(2).returning(|url, path, _| {
+            if url.ends_with("signature=1") {
+                fs::write(path, "zip")?;
+                return Err(crate::Error::ExpiredUrl(url.to_string()));
+            }
+            assert_eq!(fs::read_to_string(path)?, "zip");
+            let mut file = fs::OpenOptions::new().append(true).open(path)?;
+            Ok(std::io::Write::write_all(&mut file, b" 2.0")?)
+        });
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().returning(|_, _| Ok(true));
+        let download_path = package.clone();
+        file_manager.expect_partial_download_path().returning(move |_, _| Ok(download_path.clone()));
+        file_manager.expect_remove_old_files().returning(|| Ok(()));
+        file_manager.expect_remove_version().returning(|| Ok(()));
+        file_manager.expect_extract_zips().times(1).returning(|_, _| Ok(()));
+        file_manager.expect_save_version().with(eq("2"), eq("patchersecret")).times(1).returning(|_, _| Ok(()));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        pipeline(dir.path(), network, file_manager, launcher).run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_installed_version_starts_offline() {
+        let dir = tempdir().unwrap();
+        let mut network = MockAppApi::new();
+        network.expect_check_connection().returning(|| Ok(false));
+        network.expect_get_app_info().never();
+        let file_manager = store(dir.path());
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable()
+            .withf(|_, arguments| arguments == ["--network-status", "offline"])
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let pipeline = pipeline(dir.path(), network, file_manager, launcher);
+        fs::write(
+            dir.path().join("patcher.manifest"),
+            r#"{"manifest_version": 4, "target": "{exedir}/app", "target_arguments": [{"value": ["--network-status", "{network-status}"]}], "capabilities": []}"#,
+        ).unwrap();
+        pipeline.run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_mirror_falls_back_to_the_next() {
+        let dir = tempdir().unwrap();
+        let package = dir.path().join("launcher-2.zip.part");
+        let mut network = api();
+        network.expect_get_delta_url().returning(|_, _, _| Ok(None));
+        network.expect_get_content_urls().returning(|_, _| {
+            Ok(vec![ContentUrl {
+                size: 3,
+                url: "https://a.example.com/2.zip".into(),
+                arch: None,
+                mirrors: vec!["https://b.example.com/2.zip".into()],
+                continued: false,
+                transports: Vec::new(),
+                torrent: None,
+            }])
+        });
+        // The first mirror is down, the second is used without retrying the first
+        network.expect_download_file().times(2).returning(|url, path, _| {
+            if url.contains("a.example.com") {
+                return Err(crate::Error::Connection("Connection refused".into()));
+            }
+            Ok(fs::write(path, "zip")?)
+        });
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().returning(|_, _| Ok(true));
+        let download_path = package.clone();
+        file_manager.expect_partial_download_path().returning(move |_, _| Ok(download_path.clone()));
+        file_manager.expect_remove_old_files().returning(|| Ok(()));
+        file_manager.expect_remove_version().returning(|| Ok(()));
+        file_manager.expect_extract_zips().times(1).returning(|_, _| Ok(()));
+        file_manager.expect_save_version().with(eq("2"), eq("patchersecret")).times(1).returning(|_, _| Ok(()));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        pipeline(dir.path(), network, file_manager, launcher).run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_torrent_falls_back_to_http() {
+        let dir = tempdir().unwrap();
+        let package = dir.path().join("launcher-2.zip.part");
+        let mut network = api();
+        network.expect_get_delta_url().returning(|_, _, _| Ok(None));
+        network.expect_get_content_urls().returning(|_, _| {
+            Ok(vec![ContentUrl {
+                size: 3,
+                url: "https://example.com/2.zip".into(),
+                arch: None,
+                mirrors: Vec::new(),
+                continued: false,
+                transports: vec![Transport::Torrent, Transport::Http],
+                torrent: Some("https://example.com/2.zip.torrent".into()),
+            }])
+        });
+        // The torrent can't be fetched, so the part comes from the CDN
+        network.expect_download_file()
+            .withf(|url, _, _| url.ends_with(".torrent"))
+            .times(1)
+            .returning(|_, _, _| Err(crate::Error::Other("Torrent not found".into())));
+        network.expect_download_file()
+            .withf(|url, _, _| url == "https://example.com/2.zip")
+            .times(1)
+            .returning(|_, path, _| Ok(fs::write(path, "zip")?));
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().returning(|_, _| Ok(true));
+        let download_path = package.clone();
+        file_manager.expect_partial_download_path().returning(move |_, _| Ok(download_path.clone()));
+        file_manager.expect_remove_old_files().returning(|| Ok(()));
+        file_manager.expect_remove_version().returning(|| Ok(()));
+        file_manager.expect_extract_zips().times(1).returning(|_, _| Ok(()));
+        file_manager.expect_save_version().with(eq("2"), eq("patchersecret")).times(1).returning(|_, _| Ok(()));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        pipeline(dir.path(), network, file_manager, launcher).run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_loose_files_download_only_changed_ones() {
+        let dir = tempdir().unwrap();
+        let file = |path: &str, content: &str| ContentFile {
+            path: path.into(),
+            url: format!("https://example.com/{}", path),
+            sha256: format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(content)),
+            size: content.len() as u64,
+        };
+        let files = vec![file("bin/game", "game 2"), file("data/level.pak", "level")];
+        let mut network = api();
+        network.expect_get_content_urls().never();
+        let listed = files.clone();
+        network.expect_get_content_files().with(eq("patchersecret"), eq("2")).returning(move |_, _| Ok(listed.clone()));
+        network.expect_download_file()
+            .withf(|url, _, _| url == "https://example.com/bin/game")
+            .times(1)
+            .returning(|_, path, _| Ok(fs::write(path, "game 2")?));
+        let mut file_manager = store(dir.path());
+        file_manager.expect_needs_update().returning(|_, _| Ok(true));
+        let outdated = vec![files[0].clone()];
+        file_manager.expect_outdated_files().times(1).returning(move |_, _| Ok(outdated.clone()));
+        let installed = files.clone();
+        file_manager.expect_install_files()
+            .withf(move |files, _| files == installed)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        file_manager.expect_extract_zips().never();
+        file_manager.expect_save_version().with(eq("2"), eq("patchersecret")).times(1).returning(|_, _| Ok(()));
+        let mut launcher = MockProcessLauncher::new();
+        launcher.expect_launch_executable().times(1).returning(|_, _| Ok(()));
+
+        let mut pipeline = pipeline(dir.path(), network, file_manager, launcher);
+        pipeline.launcher_data.loose_files = true;
+        pipeline.run().await.unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("bin/game")).unwrap(), "game 2");
+        assert!(!dir.path().join("bin/game.part").exists());
+    }
+
+    /// Picks an app when asked.
+    struct Chooser(usize);
+
+    impl ProgressSink for Chooser {
+        fn phase(&self, _phase: Phase) {}
+        fn message(&self, _message: String) {}
+        fn percentage(&self, _percentage: f32) {}
+        fn speed(&self, _percentage: f32, _speed_kbps: f64, _eta: Option<Duration>) {}
+
+        fn ask_app(&self, _apps: Vec<String>) -> Option<tokio::sync::oneshot::Receiver<usize>> {
+            let (reply, choice) = tokio::sync::oneshot::channel();
+            reply.send(self.0).unwrap();
+            Some(choice)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_select_app() {
+        let dir = tempdir().unwrap();
+        let apps_dir = dir.path().join(APPS_DIR_NAME);
+        fs::create_dir(&apps_dir).unwrap();
+        for id in ["puzzle", "racer"] {
+            let json = format!(r#"{{"patcher_secret": "{0}", "app_secret": "{0}secret"}}"#, id);
+            fs::write(apps_dir.join(format!("{}.dat", id)), crate::config::json_dat(&json)).unwrap();
+        }
+        let config = RunnerConfig { dat_path: dir.path().join(DAT_FILE_NAME), apps_dir, ..Default::default() };
+        let cancellation = CancellationToken::default();
+
+        let mut chosen = config.clone();
+        select_app(&Chooser(1), &cancellation, &mut chosen).await.unwrap();
+        assert_eq!(chosen.app.as_deref(), Some("racer"));
+        assert!(chosen.dat_path.ends_with("racer.dat"));
+
+        let mut given = RunnerConfig { app: Some("puzzle".into()), ..config.clone() };
+        select_app(&NoProgress, &cancellation, &mut given).await.unwrap();
+        assert!(given.dat_path.ends_with("puzzle.dat"));
+
+        // Without a window nobody can choose
+        let result = select_app(&NoProgress, &cancellation, &mut config.clone()).await;
+        assert!(matches!(result, Err(crate::Error::DatFile(message)) if message.ends_with("puzzle, racer")));
+    }
+}
@@ -0,0 +1,178 @@
+//! Where the pipeline reports its progress. The runner's window, the terminal and embedding tools
+//! each show it their own way, or not at all.
+
+use crate::ui::{self, Phase, UiMessage, UpdateChoice};
+use log::{debug, info};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Receives the progress of the update pipeline. Only the phase, percentage, speed and message
+/// are required, everything else is optional detail.
+pub trait ProgressSink: Send + Sync + 'static {
+    fn phase(&self, phase: Phase);
+
+    /// What the pipeline is doing, for the user.
+    fn message(&self, message: String);
+
+    /// Progress of the current phase, from 0 to 1.
+    fn percentage(&self, percentage: f32);
+
+    /// Download progress, from 0 to 1, with the current speed and the time left if it's known.
+    fn speed(&self, percentage: f32, speed_kbps: f64, eta: Option<Duration>);
+
+    /// No download data has arrived for `stalled_for`. Cleared by the next progress update.
+    fn stalled(&self, _stalled_for: Duration) {}
+
+    /// A soft failure, already logged.
+    fn warning(&self, _warning: String) {}
+
+    fn news(&self, _news: String) {}
+
+    fn changelog(&self, _changelog: String) {}
+
+    fn version_info(&self, _app_name: Option<String>, _version: String) {}
+
+    /// Asks whether to install `version` now. Without an answer the update goes ahead.
+    fn ask_update(&self, _version: String, _countdown: Duration) -> Option<oneshot::Receiver<UpdateChoice>> {
+        None
+    }
+
+    /// Asks which of `apps` to run, by index. Without an answer the run fails.
+    fn ask_app(&self, _apps: Vec<String>) -> Option<oneshot::Receiver<usize>> {
+        None
+    }
+
+    /// The app was launched.
+    fn finished(&self) {}
+}
+
+/// The runner's window, through its message channel. Sends fail once the window is gone, which
+/// cancels the update, so they're ignored here.
+impl ProgressSink for Sender<UiMessage> {
+    fn phase(&self, phase: Phase) {
+        let _ = self.send(UiMessage::SetPhase(phase));
+    }
+
+    fn message(&self, message: String) {
+        let _ = self.send(UiMessage::SetStatus(message));
+    }
+
+    fn percentage(&self, percentage: f32) {
+        let _ = self.send(UiMessage::SetProgress(percentage));
+    }
+
+    fn speed(&self, percentage: f32, speed_kbps: f64, eta: Option<Duration>) {
+        let _ = self.send(UiMessage::SetDownloadProgress { progress: percentage, speed_kbps, eta });
+    }
+
+    fn stalled(&self, stalled_for: Duration) {
+        let _ = self.send(UiMessage::SetStalled(stalled_for));
+    }
+
+    fn warning(&self, warning: String) {
+        let _ = self.send(UiMessage::ShowWarning(warning));
+    }
+
+    fn news(&self, news: String) {
+        let _ = self.send(UiMessage::SetNews(news));
+    }
+
+    fn changelog(&self, changelog: String) {
+        let _ = self.send(UiMessage::SetChangelog(changelog));
+    }
+
+    fn version_info(&self, app_name: Option<String>, version: String) {
+        let _ = self.send(UiMessage::SetVersionInfo { app_name, version });
+    }
+
+    fn ask_update(&self, version: String, countdown: Duration) -> Option<oneshot::Receiver<UpdateChoice>> {
+        let (reply, choice) = oneshot::channel();
+        self.send(UiMessage::AskUpdate { version, countdown, reply }).ok()?;
+        Some(choice)
+    }
+
+    fn ask_app(&self, apps: Vec<String>) -> Option<oneshot::Receiver<usize>> {
+        let (reply, choice) = oneshot::channel();
+        self.send(UiMessage::AskApp { apps, reply }).ok()?;
+        Some(choice)
+    }
+
+    fn finished(&self) {
+        let _ = self.send(UiMessage::Close);
+    }
+}
+
+/// Logs the progress, for silent mode.
+pub struct LogProgress;
+
+impl ProgressSink for LogProgress {
+    fn phase(&self, phase: Phase) {
+        debug!("Phase: {}", phase.label());
+    }
+
+    fn message(&self, message: String) {
+        info!("{}", message);
+    }
+
+    fn percentage(&self, _percentage: f32) {}
+
+    fn speed(&self, _percentage: f32, _speed_kbps: f64, _eta: Option<Duration>) {}
+
+    fn stalled(&self, stalled_for: Duration) {
+        debug!("{}", ui::stalled_label(stalled_for));
+    }
+
+    fn news(&self, news: String) {
+        info!("News: {}", news);
+    }
+
+    fn changelog(&self, changelog: String) {
+        info!("Changelog:\n{}", changelog);
+    }
+
+    fn version_info(&self, app_name: Option<String>, version: String) {
+        info!("{}", ui::version_info_label(app_name.as_deref(), &version));
+    }
+}
+
+/// Drops all progress, e.g. in tests.
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn phase(&self, _phase: Phase) {}
+
+    fn message(&self, _message: String) {}
+
+    fn percentage(&self, _percentage: f32) {}
+
+    fn speed(&self, _percentage: f32, _speed_kbps: f64, _eta: Option<Duration>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_sender_sink() {
+        let (sender, receiver) = channel();
+        sender.speed(0.5, 100.0, Some(Duration::from_secs(45)));
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(UiMessage::SetDownloadProgress { progress, speed_kbps, eta })
+                if progress == 0.5 && speed_kbps == 100.0 && eta == Some(Duration::from_secs(45))
+        ));
+
+        let choice = sender.ask_update("2".into(), Duration::from_secs(10)).unwrap();
+        let Ok(UiMessage::AskUpdate { version, reply, .. }) = receiver.try_recv() else {
+            panic!("expected an update prompt");
+        };
+        assert_eq!(version, "2");
+        reply.send(UpdateChoice::LaunchInstalled).unwrap();
+        assert_eq!(choice.blocking_recv(), Ok(UpdateChoice::LaunchInstalled));
+
+        // Nobody asks without a window
+        assert!(NoProgress.ask_update("2".into(), Duration::from_secs(10)).is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! Retrying steps of the update that fail with a transient error, e.g. a timeout, a server error
+//! or a dropped connection. Retries back off exponentially, with jitter so that many runners
+//! failing at once don't all come back at the same moment. When the API is busy and says how long
+//! to wait, that's waited instead.
+
+use super::progress::ProgressSink;
+use super::set_status;
+use crate::cancel::CancellationToken;
+use crate::format::format_duration;
+use crate::Result;
+use log::warn;
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often a step of the update is attempted when it fails with a transient error.
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubling with each further one.
+const BASE_DELAY: Duration = Duration::from_secs(2);
+/// Longest delay between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// How often a busy API is waited for as its `Retry-After` asks, on top of the attempts.
+const MAX_BUSY_RETRIES: u32 = 10;
+/// Longest `Retry-After` honoured.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// How often and how far apart failing steps are attempted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_ATTEMPTS)
+    }
+}
+
+impl RetryPolicy {
+    /// Attempts a step up to `attempts` times, at least once.
+    pub fn new(attempts: u32) -> Self {
+        Self { attempts: attempts.max(1), base_delay: BASE_DELAY, max_delay: MAX_DELAY }
+    }
+
+    /// Delay before the `retry`th retry, counting from 1, at `jitter` between half and all of it.
+    fn delay(&self, retry: u32, jitter: f64) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1))).min(self.max_delay);
+        backoff.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// Runs a step of the update again when it fails with a transient error, telling the user.
+pub async fn with_retry<T, F, Fut>(sink: &impl ProgressSink, cancellation: &CancellationToken, policy: &RetryPolicy, mut step: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    let mut busy_retries = 0;
+    loop {
+        let e = match step().await {
+            Err(e) if e.is_transient() => e,
+            result => return result,
+        };
+        let jitter: f64 = rand::thread_rng().gen();
+        let delay = match e.root() {
+            // Waiting as asked doesn't use up attempts. Up to half of the wait is added, so that
+            // runners told the same don't come back together.
+            crate::Error::RateLimited(Some(retry_after)) if busy_retries < MAX_BUSY_RETRIES => {
+                busy_retries += 1;
+                (*retry_after).min(MAX_RETRY_AFTER).mul_f64(1.0 + jitter / 2.0)
+            },
+            _ if attempt < policy.attempts => {
+                warn!("Attempt {} of {} failed: {}", attempt, policy.attempts, e);
+                let delay = policy.delay(attempt, jitter);
+                attempt += 1;
+                delay
+            },
+            _ => return Err(e),
+        };
+        if let crate::Error::RateLimited(_) = e.root() {
+            warn!("API busy, retrying in {:?}", delay);
+            set_status(sink, format!("Servers busy, retrying in {}...", format_duration(delay)));
+        } else {
+            set_status(sink, format!("Retrying ({}/{})...", attempt, policy.attempts));
+        }
+        cancellation.run(tokio::time::sleep(delay)).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::UiMessage;
+    use std::sync::mpsc::channel;
+
+    #[tokio::test]
+    async fn test_with_retry() {
+        let (sender, receiver) = channel();
+        let policy = RetryPolicy { base_delay: Duration::ZERO, ..RetryPolicy::default() };
+
+        // Permanent errors are returned right away
+        let mut attempts = 0;
+        let result: Result<()> = with_retry(&sender, &CancellationToken::default(), &policy, || {
+            attempts += 1;
+            async { Err(crate::Error::Manifest("No target".into())) }
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        let mut attempts = 0;
+        let result = with_retry(&sender, &CancellationToken::default(), &policy, || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                match attempt {
+                    1 => Err(crate::Error::Connection("No internet connection".into())),
+                    _ => Ok(attempt),
+                }
+            }
+        }).await;
+        assert_eq!(result.unwrap(), 2);
+        assert!(matches!(receiver.try_recv(), Ok(UiMessage::SetStatus(status)) if status == "Retrying (2/3)..."));
+
+        // A busy API is waited for without using up attempts
+        let mut attempts = 0;
+        let result = with_retry(&sender, &CancellationToken::default(), &RetryPolicy { attempts: 1, ..policy }, || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                match attempt {
+                    1 | 2 => Err(crate::Error::RateLimited(Some(Duration::ZERO))),
+                    _ => Ok(attempt),
+                }
+            }
+        }).await;
+        assert_eq!(result.unwrap(), 3);
+        assert!(matches!(receiver.try_recv(), Ok(UiMessage::SetStatus(status)) if status == "Servers busy, retrying in 0s..."));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1, 1.0), Duration::from_secs(2));
+        assert_eq!(policy.delay(3, 1.0), Duration::from_secs(8));
+        assert_eq!(policy.delay(3, 0.0), Duration::from_secs(4));
+        // The backoff stops growing at the maximum
+        assert_eq!(policy.delay(10, 1.0), MAX_DELAY);
+        assert_eq!(RetryPolicy::new(0).attempts, 1);
+    }
+}
@@ -0,0 +1,210 @@
+//! States of the update pipeline. The state about to run is saved as a checkpoint in the patcher
+//! directory, so the next run can tell where a crashed or killed run stopped.
+
+use crate::network::{ContentFile, ContentUrl};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CHECKPOINT_FILE_NAME: &str = "runner-state.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RunState {
+    CheckingNetwork,
+    /// App info, latest version and whether an update is needed.
+    FetchingInfo,
+    /// The version's content comes in one or more parts, extracted in order.
+    Downloading {
+        version: String,
+        patcher_secret: String,
+        parts: Vec<ContentUrl>,
+    },
+    Extracting {
+        version: String,
+        patcher_secret: String,
+        packages: Vec<PathBuf>,
+    },
+    /// Only the changes since the installed version are downloaded and applied, see
+    /// [`crate::file::delta`].
+    Patching {
+        version: String,
+        patcher_secret: String,
+        delta: ContentUrl,
+    },
+    /// The version comes as loose files, of which the changed ones are downloaded into place.
+    Syncing {
+        version: String,
+        patcher_secret: String,
+        files: Vec<ContentFile>,
+    },
+    /// Downloaded ahead of time, see [`crate::daemon`]. The next run installs the packages.
+    Downloaded {
+        version: String,
+        patcher_secret: String,
+        packages: Vec<PathBuf>,
+    },
+    Launching,
+    Done,
+}
+
+impl RunState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CheckingNetwork => "checking network",
+            Self::FetchingInfo => "fetching info",
+            Self::Downloading { .. } => "downloading",
+            Self::Extracting { .. } => "extracting",
+            Self::Patching { .. } => "patching",
+            Self::Syncing { .. } => "syncing files",
+            Self::Downloaded { .. } => "downloaded",
+            Self::Launching => "launching",
+            Self::Done => "done",
+        }
+    }
+
+    /// Whether the pipeline may go from this state to `next`.
+    pub fn can_transition_to(&self, next: &RunState) -> bool {
+        matches!(
+            (self, next),
+            (Self::CheckingNetwork, Self::FetchingInfo)
+                // Offline, the installed version is launched as it is
+                | (Self::CheckingNetwork, Self::Launching)
+                // No update needed, or no package to download
+                | (
+                    Self::FetchingInfo,
+                    Self::Downloading { .. } | Self::Patching { .. } | Self::Syncing { .. } | Self::Launching | Self::Done
+                )
+                // A delta that fails is replaced by the whole version
+                | (Self::Patching { .. }, Self::Downloading { .. } | Self::Launching | Self::Done)
+                | (Self::Downloading { .. }, Self::Extracting { .. } | Self::Downloaded { .. })
+                | (Self::Extracting { .. } | Self::Syncing { .. }, Self::Launching)
+                | (Self::Launching, Self::Done)
+                // The launcher asked to be run again
+                | (Self::Launching, Self::FetchingInfo | Self::CheckingNetwork)
+        )
+    }
+
+    /// Whether a run stopped in this state left the installed files incomplete.
+    pub fn interrupted_install(&self) -> bool {
+        matches!(self, Self::Extracting { .. } | Self::Syncing { .. })
+    }
+
+    /// Packages of `version` a run stopped in this state had fully downloaded.
+    pub fn downloaded_packages(&self, version: &str) -> Option<&[PathBuf]> {
+        match self {
+            Self::Extracting { version: downloaded, packages, .. } | Self::Downloaded { version: downloaded, packages, .. }
+                if downloaded == version && !packages.is_empty() && packages.iter().all(|package| package.exists()) =>
+            {
+                Some(packages)
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The checkpoint file of an app.
+pub struct Checkpoint {
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    pub fn new(patcher_dir: &Path) -> Self {
+        Self { path: patcher_dir.join(CHECKPOINT_FILE_NAME) }
+    }
+
+    /// State the previous run stopped in, if it didn't finish.
+    pub fn load(&self) -> Option<RunState> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                warn!("Ignoring invalid checkpoint {}: {}", self.path.display(), e);
+                None
+            },
+        }
+    }
+
+    pub fn save(&self, state: &RunState) {
+        let result = serde_json::to_string(state)
+            .map_err(crate::Error::from)
+            .and_then(|content| {
+                if let Some(parent) = self.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Ok(fs::write(&self.path, content)?)
+            });
+        if let Err(e) = result {
+            debug!("Failed to save checkpoint {}: {}", self.path.display(), e);
+        }
+    }
+
+    pub fn clear(&self) {
+        if self.path.exists() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("Failed to remove checkpoint {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn extracting(packages: Vec<PathBuf>) -> RunState {
+        RunState::Extracting { version: "2".into(), patcher_secret: "secret".into(), packages }
+    }
+
+    #[test]
+    fn test_transitions() {
+        let downloading = RunState::Downloading {
+            version: "2".into(),
+            patcher_secret: "secret".into(),
+            parts: vec![ContentUrl { size: 10, url: "https://example.com/2.zip".into(), arch: None, mirrors: Vec::new(), continued: false, transports: Vec::new(), torrent: None }],
+        };
+        assert!(RunState::CheckingNetwork.can_transition_to(&RunState::FetchingInfo));
+        assert!(RunState::FetchingInfo.can_transition_to(&downloading));
+        assert!(RunState::FetchingInfo.can_transition_to(&RunState::Launching));
+        assert!(downloading.can_transition_to(&extracting(vec!["package.zip".into()])));
+        assert!(extracting(vec!["package.zip".into()]).can_transition_to(&RunState::Launching));
+        assert!(RunState::Launching.can_transition_to(&RunState::Done));
+
+        assert!(!RunState::CheckingNetwork.can_transition_to(&downloading));
+        assert!(!downloading.can_transition_to(&RunState::Launching));
+        assert!(!RunState::Done.can_transition_to(&RunState::CheckingNetwork));
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let dir = tempdir().unwrap();
+        let checkpoint = Checkpoint::new(dir.path());
+        assert_eq!(checkpoint.load(), None);
+
+        let packages = vec![dir.path().join("launcher-2.zip.part"), dir.path().join("launcher-2.1.zip.part")];
+        for package in &packages {
+            fs::write(package, "zip").unwrap();
+        }
+        checkpoint.save(&extracting(packages.clone()));
+
+        let previous = checkpoint.load().unwrap();
+        assert!(previous.interrupted_install());
+        assert_eq!(previous.downloaded_packages("2"), Some(packages.as_slice()));
+        assert_eq!(previous.downloaded_packages("3"), None);
+
+        // A package downloaded ahead of time is used, but nothing was installed yet
+        checkpoint.save(&RunState::Downloaded { version: "2".into(), patcher_secret: "secret".into(), packages: packages.clone() });
+        let previous = checkpoint.load().unwrap();
+        assert!(!previous.interrupted_install());
+        assert_eq!(previous.downloaded_packages("2"), Some(packages.as_slice()));
+
+        // All parts are needed
+        fs::remove_file(&packages[1]).unwrap();
+        assert_eq!(checkpoint.load().unwrap().downloaded_packages("2"), None);
+
+        checkpoint.clear();
+        assert_eq!(checkpoint.load(), None);
+    }
+}
@@ -0,0 +1,51 @@
+//! CPU architectures that content and patchers are built for. Windows and macOS on ARM64 can run
+//! x64 builds through emulation, so those builds are used when there's no native one.
+
+/// `name` in the form of `std::env::consts::ARCH`, also accepting the names Windows and the
+/// API use, e.g. `x64` or `arm64`.
+pub fn normalize(name: &str) -> Option<&'static str> {
+    match name.to_ascii_lowercase().as_str() {
+        "x86_64" | "x64" | "amd64" => Some("x86_64"),
+        "aarch64" | "arm64" => Some("aarch64"),
+        "x86" | "i386" | "i686" | "win32" => Some("x86"),
+        _ => None,
+    }
+}
+
+/// Architectures whose builds run on `arch` under `os`, best first.
+pub fn compatible(os: &str, arch: &str) -> &'static [&'static str] {
+    match (os, normalize(arch)) {
+        ("windows", Some("aarch64")) | ("macos", Some("aarch64")) => &["aarch64", "x86_64"],
+        ("windows", Some("x86_64")) => &["x86_64", "x86"],
+        (_, Some("aarch64")) => &["aarch64"],
+        (_, Some("x86_64")) => &["x86_64"],
+        (_, Some("x86")) => &["x86"],
+        _ => &[],
+    }
+}
+
+/// Architectures whose builds run on this machine, best first.
+pub fn compatible_here() -> &'static [&'static str] {
+    compatible(std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Whether `name` stands for `arch`.
+pub fn matches(name: &str, arch: &str) -> bool {
+    normalize(name).is_some_and(|name| Some(name) == normalize(arch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compatible() {
+        assert_eq!(compatible("windows", "aarch64"), ["aarch64", "x86_64"]);
+        assert_eq!(compatible("linux", "aarch64"), ["aarch64"]);
+        assert_eq!(compatible("windows", "x86_64"), ["x86_64", "x86"]);
+        assert!(compatible("linux", "riscv64").is_empty());
+        assert!(matches("ARM64", "aarch64"));
+        assert!(matches("x64", "x86_64"));
+        assert!(!matches("x64", "aarch64"));
+    }
+}
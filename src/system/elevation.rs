@@ -0,0 +1,136 @@
+//! Administrator rights, asked for only when the install location the user chose can't be
+//! written. Locations the runner picks itself fall back to the user's data directory instead, so
+//! neither the runner nor the game it starts runs elevated without need.
+
+use crate::Result;
+
+/// Whether the runner could restart with administrator rights: on Windows, when it doesn't
+/// have them yet.
+pub fn can_elevate() -> bool {
+    native::can_elevate()
+}
+
+/// Starts the runner again with the same arguments and administrator rights, after the user
+/// agrees to it. The caller closes this instance.
+pub fn restart_elevated() -> Result<()> {
+    native::restart_elevated(&command_line(std::env::args().skip(1)))
+}
+
+/// `args` as a Windows command line, quoted so the new process parses them back the same.
+fn command_line(args: impl IntoIterator<Item = String>) -> String {
+    args.into_iter().map(|arg| quote(&arg)).collect::<Vec<_>>().join(" ")
+}
+
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    // Backslashes are only special before a quote, where they're doubled
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            },
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            },
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(windows)]
+mod native {
+    use crate::Result;
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+    use winapi::um::securitybaseapi::GetTokenInformation;
+    use winapi::um::shellapi::ShellExecuteW;
+    use winapi::um::winnt::{TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+    use winapi::um::winuser::SW_NORMAL;
+
+    fn wide(value: &OsStr) -> Vec<u16> {
+        value.encode_wide().chain(Some(0)).collect()
+    }
+
+    pub fn can_elevate() -> bool {
+        !is_elevated()
+    }
+
+    fn is_elevated() -> bool {
+        unsafe {
+            let mut token = null_mut();
+            if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+                return false;
+            }
+            let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+            let mut size = std::mem::size_of::<TOKEN_ELEVATION>() as u32;
+            let queried = GetTokenInformation(
+                token,
+                TokenElevation,
+                &mut elevation as *mut _ as *mut _,
+                size,
+                &mut size,
+            );
+            CloseHandle(token);
+            queried != 0 && elevation.TokenIsElevated != 0
+        }
+    }
+
+    pub fn restart_elevated(parameters: &str) -> Result<()> {
+        let exe = std::env::current_exe()?;
+        let directory = exe.parent().map(|dir| wide(dir.as_os_str())).unwrap_or_else(|| vec![0]);
+        let result = unsafe {
+            ShellExecuteW(
+                null_mut(),
+                wide(OsStr::new("runas")).as_ptr(),
+                wide(exe.as_os_str()).as_ptr(),
+                wide(OsStr::new(parameters)).as_ptr(),
+                directory.as_ptr(),
+                SW_NORMAL,
+            )
+        };
+        // Values up to 32 are errors, e.g. when the user declines
+        if result as usize <= 32 {
+            return Err(crate::Error::Permission("Administrator rights weren't granted".into()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+mod native {
+    use crate::Result;
+
+    pub fn can_elevate() -> bool {
+        false
+    }
+
+    pub fn restart_elevated(_parameters: &str) -> Result<()> {
+        Err(crate::Error::Permission("Restarting with administrator rights is only supported on Windows".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let args = ["--app", "racer", r"C:\My Games\", r#"say "hi""#, ""].map(String::from);
+        assert_eq!(command_line(args), r#"--app racer "C:\My Games\\" "say \"hi\"" """#);
+    }
+}
@@ -0,0 +1,238 @@
+//! Facts about the machine the runner is on, logged at startup and added to support bundles.
+
+pub mod arch;
+pub mod elevation;
+pub mod registry;
+pub mod sandbox;
+pub mod steamos;
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+#[cfg(any(target_os = "macos", windows))]
+use std::process::Command;
+
+/// Environment details support asks about first.
+#[derive(Debug, Clone)]
+pub struct SystemInfo {
+    pub os_version: String,
+    pub arch: &'static str,
+    pub locale: Option<String>,
+    /// Directory the app is installed to, whose volume the free space is measured on.
+    pub target_dir: PathBuf,
+    pub free_space: Option<u64>,
+    pub exe_dir: Option<PathBuf>,
+    pub exe_dir_writable: bool,
+    pub read_only_location: Option<sandbox::ReadOnlyLocation>,
+}
+
+impl SystemInfo {
+    pub fn collect(target_dir: &Path) -> Self {
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        Self {
+            os_version: os_version(),
+            arch: std::env::consts::ARCH,
+            locale: locale(),
+            target_dir: target_dir.to_path_buf(),
+            free_space: free_space(target_dir),
+            exe_dir_writable: exe_dir.as_deref().is_some_and(is_writable),
+            exe_dir,
+            read_only_location: sandbox::detect(),
+        }
+    }
+}
+
+impl fmt::Display for SystemInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "OS: {}", self.os_version)?;
+        writeln!(f, "Architecture: {}", self.arch)?;
+        writeln!(f, "Locale: {}", self.locale.as_deref().unwrap_or("unknown"))?;
+        match self.free_space {
+            Some(bytes) => writeln!(f, "Free space: {} on {}", crate::format::format_size(bytes), self.target_dir.display())?,
+            None => writeln!(f, "Free space: unknown on {}", self.target_dir.display())?,
+        }
+        match &self.exe_dir {
+            Some(exe_dir) => writeln!(f, "Runner directory writable: {} ({})", self.exe_dir_writable, exe_dir.display())?,
+            None => writeln!(f, "Runner directory writable: unknown")?,
+        }
+        if let Some(location) = self.read_only_location {
+            writeln!(f, "Running from {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prefix of the files [`is_writable`] creates. A killed run can leave one behind.
+pub const WRITE_TEST_PREFIX: &str = ".write-test";
+
+/// Whether a file can be created in `dir`.
+pub fn is_writable(dir: &Path) -> bool {
+    tempfile::Builder::new()
+        .prefix(WRITE_TEST_PREFIX)
+        .tempfile_in(dir)
+        .is_ok()
+}
+
+/// Bytes available to the user on the volume of `path`. The path doesn't need to exist yet, its
+/// nearest existing ancestor is measured.
+pub fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|dir| dir.exists())?;
+    native::free_space(existing)
+}
+
+/// Whether a window can be shown. On Linux and the BSDs that needs an X11 or Wayland display,
+/// which servers, containers and CI runners usually lack.
+pub fn has_display() -> bool {
+    has_display_from(std::env::consts::OS, |name| std::env::var_os(name))
+}
+
+fn has_display_from(os: &str, env: impl Fn(&str) -> Option<OsString>) -> bool {
+    match os {
+        "windows" | "macos" => true,
+        _ => ["DISPLAY", "WAYLAND_DISPLAY"]
+            .into_iter()
+            .filter_map(env)
+            .any(|value| !value.is_empty()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn os_version() -> String {
+    let name = std::fs::read_to_string("/etc/os-release").ok().and_then(|release| {
+        release
+            .lines()
+            .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+            .map(|name| name.trim_matches('"').to_string())
+    });
+    let kernel = std::fs::read_to_string("/proc/sys/kernel/osrelease").ok();
+    match (name, kernel) {
+        (Some(name), Some(kernel)) => format!("{} (kernel {})", name, kernel.trim()),
+        (Some(name), None) => name,
+        (None, Some(kernel)) => format!("Linux {}", kernel.trim()),
+        (None, None) => "Linux".into(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn os_version() -> String {
+    command_output(&mut Command::new("sw_vers").arg("-productVersion"))
+        .map_or_else(|| "macOS".into(), |version| format!("macOS {}", version))
+}
+
+#[cfg(windows)]
+fn os_version() -> String {
+    use std::os::windows::process::CommandExt;
+    // Keeps cmd from flashing a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    command_output(Command::new("cmd").args(["/C", "ver"]).creation_flags(CREATE_NO_WINDOW))
+        .unwrap_or_else(|| "Windows".into())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn os_version() -> String {
+    std::env::consts::OS.into()
+}
+
+#[cfg(any(target_os = "macos", windows))]
+fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok().filter(|output| output.status.success())?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(not(windows))]
+fn locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(windows)]
+fn locale() -> Option<String> {
+    native::locale()
+}
+
+#[cfg(unix)]
+mod native {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    pub fn free_space(path: &Path) -> Option<u64> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        #[allow(clippy::unnecessary_cast)]
+        Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+}
+
+#[cfg(windows)]
+mod native {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr::null_mut;
+    use winapi::um::fileapi::GetDiskFreeSpaceExW;
+    use winapi::um::winnls::GetUserDefaultLocaleName;
+    use winapi::um::winnt::{LOCALE_NAME_MAX_LENGTH, ULARGE_INTEGER};
+
+    pub fn free_space(path: &Path) -> Option<u64> {
+        let path: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+        unsafe {
+            let mut available: ULARGE_INTEGER = std::mem::zeroed();
+            if GetDiskFreeSpaceExW(path.as_ptr(), &mut available, null_mut(), null_mut()) == 0 {
+                return None;
+            }
+            Some(*available.QuadPart())
+        }
+    }
+
+    pub fn locale() -> Option<String> {
+        let mut name = [0u16; LOCALE_NAME_MAX_LENGTH];
+        let len = unsafe { GetUserDefaultLocaleName(name.as_mut_ptr(), name.len() as i32) };
+        // The length includes the terminating null
+        (len > 1).then(|| String::from_utf16_lossy(&name[..len as usize - 1]))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod native {
+    use std::path::Path;
+
+    pub fn free_space(_path: &Path) -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_collect() {
+        let dir = tempdir().unwrap();
+        assert!(is_writable(dir.path()));
+        assert!(!is_writable(&dir.path().join("missing")));
+        assert!(free_space(&dir.path().join("not").join("created")).is_some());
+
+        let info = SystemInfo::collect(dir.path()).to_string();
+        assert!(info.starts_with("OS: "));
+        assert!(info.contains(&format!("Architecture: {}", std::env::consts::ARCH)));
+        assert!(info.contains("Free space: "));
+    }
+
+    #[test]
+    fn test_has_display_from() {
+        assert!(has_display_from("windows", |_| None));
+        assert!(!has_display_from("linux", |_| None));
+        assert!(!has_display_from("linux", |name| (name == "DISPLAY").then(OsString::new)));
+        assert!(has_display_from("freebsd", |name| (name == "WAYLAND_DISPLAY").then(|| "wayland-0".into())));
+    }
+}
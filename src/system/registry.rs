@@ -0,0 +1,92 @@
+//! Keys under HKEY_CURRENT_USER, which the runner can write without elevation. Other platforms
+//! have no registry, so writing there does nothing.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegistryValue {
+    Text(String),
+    Number(u32),
+}
+
+pub use native::{delete_key, write_key};
+
+#[cfg(windows)]
+mod native {
+    use super::RegistryValue;
+    use crate::Result;
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use winapi::shared::minwindef::{BYTE, DWORD, HKEY};
+    use winapi::shared::winerror::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+    use winapi::um::winnt::{KEY_SET_VALUE, REG_DWORD, REG_OPTION_NON_VOLATILE, REG_SZ};
+    use winapi::um::winreg::{RegCloseKey, RegCreateKeyExW, RegDeleteTreeW, RegSetValueExW, HKEY_CURRENT_USER};
+
+    fn wide(value: &str) -> Vec<u16> {
+        OsStr::new(value).encode_wide().chain(Some(0)).collect()
+    }
+
+    fn check(status: i32, action: &str, path: &str) -> Result<()> {
+        if status as DWORD == ERROR_SUCCESS {
+            return Ok(());
+        }
+        let error = std::io::Error::from_raw_os_error(status);
+        Err(crate::Error::FileSystem(format!("Failed to {} {}: {}", action, path, error)))
+    }
+
+    /// Creates the key at `path` if needed and sets `values` in it. An empty name sets the key's
+    /// default value.
+    pub fn write_key(path: &str, values: &[(&str, RegistryValue)]) -> Result<()> {
+        let mut key: HKEY = ptr::null_mut();
+        let status = unsafe {
+            RegCreateKeyExW(
+                HKEY_CURRENT_USER,
+                wide(path).as_ptr(),
+                0,
+                ptr::null_mut(),
+                REG_OPTION_NON_VOLATILE,
+                KEY_SET_VALUE,
+                ptr::null_mut(),
+                &mut key,
+                ptr::null_mut(),
+            )
+        };
+        check(status, "create registry key", path)?;
+
+        let result = values.iter().try_for_each(|(name, value)| {
+            let (kind, data): (DWORD, Vec<BYTE>) = match value {
+                RegistryValue::Text(text) => (REG_SZ, wide(text).iter().flat_map(|unit| unit.to_le_bytes()).collect()),
+                RegistryValue::Number(number) => (REG_DWORD, number.to_le_bytes().to_vec()),
+            };
+            let status = unsafe {
+                RegSetValueExW(key, wide(name).as_ptr(), 0, kind, data.as_ptr(), data.len() as DWORD)
+            };
+            check(status, &format!("set {} of registry key", name), path)
+        });
+        unsafe { RegCloseKey(key) };
+        result
+    }
+
+    /// Deletes the key at `path` with everything in it. A missing key is fine.
+    pub fn delete_key(path: &str) -> Result<()> {
+        let status = unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, wide(path).as_ptr()) };
+        if status as DWORD == ERROR_FILE_NOT_FOUND {
+            return Ok(());
+        }
+        check(status, "delete registry key", path)
+    }
+}
+
+#[cfg(not(windows))]
+mod native {
+    use super::RegistryValue;
+    use crate::Result;
+
+    pub fn write_key(path: &str, _values: &[(&str, RegistryValue)]) -> Result<()> {
+        log::debug!("No registry on this platform, not writing {}", path);
+        Ok(())
+    }
+
+    pub fn delete_key(_path: &str) -> Result<()> {
+        Ok(())
+    }
+}
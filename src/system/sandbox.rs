@@ -0,0 +1,106 @@
+//! Read-only places the runner can be started from, where nothing can be installed next to it.
+//! Knowing them up front lets the runner install for the current user right away instead of
+//! failing partway through the update.
+
+use super::steamos;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOnlyLocation {
+    Flatpak,
+    AppImage,
+    /// A mounted `.dmg` on macOS.
+    DiskImage,
+    /// A copy macOS runs a quarantined download from until it's moved.
+    Translocated,
+    ProgramFiles,
+    /// The read-only root filesystem of SteamOS.
+    SteamOsRoot,
+}
+
+impl fmt::Display for ReadOnlyLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Flatpak => "a Flatpak sandbox",
+            Self::AppImage => "an AppImage",
+            Self::DiskImage => "a mounted disk image",
+            Self::Translocated => "a translocated app, move it to Applications",
+            Self::ProgramFiles => "Program Files",
+            Self::SteamOsRoot => "the read-only system directories of SteamOS",
+        })
+    }
+}
+
+/// The read-only location the runner is in, if it's a known one. Program Files only counts
+/// when the runner can't write there, which it can with administrator rights.
+pub fn detect() -> Option<ReadOnlyLocation> {
+    let exe = std::env::current_exe().ok()?;
+    if Path::new("/.flatpak-info").exists() {
+        return Some(ReadOnlyLocation::Flatpak);
+    }
+    match detect_from(&exe, |name| std::env::var_os(name)) {
+        Some(ReadOnlyLocation::ProgramFiles) if exe.parent().is_some_and(super::is_writable) => None,
+        Some(location) => Some(location),
+        None if steamos::is_read_only_root(&exe) && steamos::detect().is_some() => Some(ReadOnlyLocation::SteamOsRoot),
+        None => None,
+    }
+}
+
+fn detect_from(exe: &Path, env: impl Fn(&str) -> Option<OsString>) -> Option<ReadOnlyLocation> {
+    if env("FLATPAK_ID").is_some() {
+        return Some(ReadOnlyLocation::Flatpak);
+    }
+    if env("APPIMAGE").is_some() {
+        return Some(ReadOnlyLocation::AppImage);
+    }
+
+    let exe_path = exe.to_string_lossy();
+    if exe_path.contains("/AppTranslocation/") {
+        return Some(ReadOnlyLocation::Translocated);
+    }
+    if exe_path.starts_with("/Volumes/") {
+        return Some(ReadOnlyLocation::DiskImage);
+    }
+    let in_program_files = ["ProgramFiles", "ProgramFiles(x86)", "ProgramW6432"]
+        .into_iter()
+        .filter_map(&env)
+        .any(|dir| is_inside(&exe_path, &dir.to_string_lossy()));
+    in_program_files.then_some(ReadOnlyLocation::ProgramFiles)
+}
+
+/// Whether `path` is inside `dir`, ignoring case like Windows does.
+fn is_inside(path: &str, dir: &str) -> bool {
+    let dir = dir.trim_end_matches(['\\', '/']);
+    !dir.is_empty()
+        && path.len() > dir.len()
+        && path.is_char_boundary(dir.len())
+        && path[..dir.len()].eq_ignore_ascii_case(dir)
+        && path[dir.len()..].starts_with(['\\', '/'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_from() {
+        let no_env = |_: &str| None;
+        assert_eq!(detect_from(Path::new("/home/me/games/runner"), no_env), None);
+        assert_eq!(
+            detect_from(Path::new("/tmp/.mount_gameXYZ/runner"), |name: &str| (name == "APPIMAGE").then(|| "/home/me/Game.AppImage".into())),
+            Some(ReadOnlyLocation::AppImage)
+        );
+        assert_eq!(detect_from(Path::new("/app/bin/runner"), |name: &str| (name == "FLATPAK_ID").then(|| "com.game".into())), Some(ReadOnlyLocation::Flatpak));
+        assert_eq!(detect_from(Path::new("/Volumes/Game/Game.app/Contents/MacOS/runner"), no_env), Some(ReadOnlyLocation::DiskImage));
+        assert_eq!(
+            detect_from(Path::new("/private/var/folders/x/T/AppTranslocation/ABC/d/Game.app/Contents/MacOS/runner"), no_env),
+            Some(ReadOnlyLocation::Translocated)
+        );
+
+        let program_files = |name: &str| (name == "ProgramFiles").then(|| r"C:\Program Files".into());
+        assert_eq!(detect_from(Path::new(r"c:\program files\Game\runner.exe"), program_files), Some(ReadOnlyLocation::ProgramFiles));
+        assert_eq!(detect_from(Path::new(r"C:\Program Files Games\runner.exe"), program_files), None);
+    }
+}
@@ -0,0 +1,70 @@
+//! SteamOS, e.g. on a Steam Deck. In Gaming Mode the runner is shown by gamescope, which makes it
+//! fullscreen without a title bar, on a small screen, and Steam maps the gamepad to keys. The root
+//! filesystem is read-only, only the home directory, `/var` and removable media can be written.
+
+use std::ffi::OsString;
+use std::path::Path;
+
+const OS_RELEASE_PATH: &str = "/etc/os-release";
+
+/// Zoom applied on SteamOS unless `--scale` is given, the screen is small and far from the eyes.
+pub const SCALE: f32 = 1.5;
+
+/// Directories of the read-only root that can be written to.
+const WRITABLE_DIRS: [&str; 5] = ["/home", "/var", "/run/media", "/tmp", "/mnt"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    /// Gaming Mode, under gamescope with a gamepad.
+    Gaming,
+    /// Desktop Mode, or SteamOS without Steam's session.
+    Desktop,
+}
+
+/// The SteamOS session the runner is in, if any.
+pub fn detect() -> Option<Session> {
+    let os_release = std::fs::read_to_string(OS_RELEASE_PATH).ok();
+    detect_from(|name| std::env::var_os(name), os_release.as_deref())
+}
+
+fn detect_from(env: impl Fn(&str) -> Option<OsString>, os_release: Option<&str>) -> Option<Session> {
+    let is_set = |name: &str| env(name).is_some_and(|value| value == "1");
+    let gamescope = env("GAMESCOPE_WAYLAND_DISPLAY").is_some()
+        || env("XDG_CURRENT_DESKTOP").is_some_and(|desktop| desktop.eq_ignore_ascii_case("gamescope"))
+        || is_set("SteamGamepadUI");
+    if gamescope {
+        return Some(Session::Gaming);
+    }
+    let steamos = is_set("SteamOS")
+        || is_set("SteamDeck")
+        || os_release.is_some_and(|release| release.lines().any(|line| matches!(line.trim(), "ID=steamos" | "ID=\"steamos\"")));
+    steamos.then_some(Session::Desktop)
+}
+
+/// Whether `path` is on the read-only root filesystem of SteamOS.
+pub fn is_read_only_root(path: &Path) -> bool {
+    path.is_absolute() && !WRITABLE_DIRS.iter().any(|dir| path.starts_with(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_from() {
+        let no_env = |_: &str| None;
+        assert_eq!(detect_from(no_env, None), None);
+        assert_eq!(detect_from(no_env, Some("NAME=\"Ubuntu\"\nID=ubuntu\n")), None);
+        assert_eq!(detect_from(no_env, Some("NAME=\"SteamOS\"\nID=steamos\n")), Some(Session::Desktop));
+        assert_eq!(detect_from(|name: &str| (name == "SteamDeck").then(|| "1".into()), None), Some(Session::Desktop));
+        assert_eq!(detect_from(|name: &str| (name == "SteamDeck").then(|| "0".into()), None), None);
+        assert_eq!(
+            detect_from(|name: &str| (name == "GAMESCOPE_WAYLAND_DISPLAY").then(|| "gamescope-0".into()), Some("ID=steamos")),
+            Some(Session::Gaming)
+        );
+
+        assert!(is_read_only_root(Path::new("/usr/lib/game/runner")));
+        assert!(!is_read_only_root(Path::new("/home/deck/.local/share/Steam/steamapps/common/Game/runner")));
+        assert!(!is_read_only_root(Path::new("/run/media/mmcblk0p1/steamapps/common/Game/runner")));
+    }
+}
@@ -0,0 +1,135 @@
+//! Anonymous usage analytics sent to PatchKit: lifecycle events of the runner with a random
+//! install ID, so studios can see adoption and how updates go. The dat's `disable_analytics`
+//! turns them off.
+
+use crate::config::LauncherData;
+use crate::network::NetworkManager;
+use log::{debug, warn};
+use rand::RngCore;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+pub const INSTALL_ID_FILE_NAME: &str = "install-id.txt";
+
+/// What happened.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LifecycleEvent {
+    RunnerStarted,
+    UpdatePerformed {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from_version: Option<String>,
+        to_version: String,
+    },
+    LaunchSucceeded,
+    LaunchFailed { code: &'static str },
+}
+
+/// Body of an analytics request.
+#[derive(Debug, Serialize)]
+pub struct AnalyticsEvent<'a> {
+    #[serde(flatten)]
+    pub event: &'a LifecycleEvent,
+    pub install_id: &'a str,
+    pub app_slug: &'a str,
+    pub runner_version: &'static str,
+    pub os: &'static str,
+}
+
+#[derive(Clone)]
+pub struct Analytics {
+    network: NetworkManager,
+    install_id: String,
+    app_slug: String,
+}
+
+impl Analytics {
+    /// Analytics unless the dat disables them. The install ID is kept in `id_path`.
+    pub fn new(launcher_data: &LauncherData, app_slug: &str, network: NetworkManager, id_path: &Path) -> Option<Self> {
+        if launcher_data.disable_analytics {
+            debug!("Analytics are disabled in the dat file");
+            return None;
+        }
+
+        Some(Self {
+            network,
+            install_id: load_install_id(id_path),
+            app_slug: app_slug.to_string(),
+        })
+    }
+
+    /// Sends `event`. Failures are only logged, analytics never affect the update.
+    pub async fn send(&self, event: LifecycleEvent) {
+        let body = AnalyticsEvent {
+            event: &event,
+            install_id: &self.install_id,
+            app_slug: &self.app_slug,
+            runner_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+        };
+        if let Err(e) = self.network.report_analytics_event(&body).await {
+            debug!("Failed to send analytics event {:?}: {}", event, e);
+        }
+    }
+}
+
+/// The install ID saved at `path`, created on first use.
+pub fn load_install_id(path: &Path) -> String {
+    if let Some(id) = fs::read_to_string(path).ok().map(|id| id.trim().to_string()).filter(|id| !id.is_empty()) {
+        return id;
+    }
+
+    let id = random_id();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Err(e) = fs::write(path, &id) {
+        warn!("Failed to save install ID to {}: {}", path.display(), e);
+    }
+    id
+}
+
+/// Random UUID (version 4).
+pub fn random_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = format!("{:032x}", u128::from_be_bytes(bytes));
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_install_id_is_kept() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(INSTALL_ID_FILE_NAME);
+        let id = load_install_id(&path);
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert_eq!(load_install_id(&path), id);
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = LifecycleEvent::UpdatePerformed { from_version: Some("1".into()), to_version: "2".into() };
+        let body = AnalyticsEvent {
+            event: &event,
+            install_id: "id",
+            app_slug: "abcd1234",
+            runner_version: "0.1.0",
+            os: "linux",
+        };
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json["event"], "update_performed");
+        assert_eq!(json["from_version"], "1");
+        assert_eq!(json["to_version"], "2");
+        assert_eq!(json["install_id"], "id");
+    }
+}
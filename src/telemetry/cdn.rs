@@ -0,0 +1,176 @@
+//! Opt-in download statistics sent to PatchKit: which mirror a part came from, how fast, and how
+//! many attempts failed. PatchKit answers with weights for the mirrors, which decide the mirror
+//! of the next run's downloads.
+
+use crate::network::{ContentUrl, NetworkManager};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const MIRROR_WEIGHTS_FILE_NAME: &str = "mirror-weights.json";
+
+/// Weight of mirrors PatchKit hasn't rated.
+const DEFAULT_WEIGHT: f64 = 1.0;
+
+/// Body of a download report.
+#[derive(Debug, Serialize)]
+pub struct DownloadReport<'a> {
+    pub app_slug: &'a str,
+    pub version: &'a str,
+    /// Host the part was downloaded from.
+    pub mirror: &'a str,
+    pub bytes: u64,
+    pub throughput_kbps: f64,
+    /// Failed attempts before the download went through, or before giving up.
+    pub errors: u32,
+    pub succeeded: bool,
+    pub runner_version: &'static str,
+    pub os: &'static str,
+}
+
+/// Answer to a download report.
+#[derive(Debug, Default, Deserialize)]
+pub struct ReportResponse {
+    #[serde(default)]
+    pub mirror_weights: MirrorWeights,
+}
+
+/// How much each mirror host is preferred. Hosts with a higher weight are used first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MirrorWeights(HashMap<String, f64>);
+
+impl MirrorWeights {
+    /// Weights saved at `path`, none if there are no valid ones.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn weight(&self, url: &str) -> f64 {
+        host(url).and_then(|host| self.0.get(&host)).copied().unwrap_or(DEFAULT_WEIGHT)
+    }
+
+    /// URL of the preferred mirror of `part`, the first one of those weighted the same.
+    pub fn choose<'a>(&self, part: &'a ContentUrl) -> &'a str {
+        let mut best = (part.url.as_str(), self.weight(&part.url));
+        for mirror in &part.mirrors {
+            let weight = self.weight(mirror);
+            if weight > best.1 {
+                best = (mirror, weight);
+            }
+        }
+        best.0
+    }
+}
+
+/// Host of `url`, which identifies the mirror.
+pub fn host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url).ok()?.host_str().map(str::to_string)
+}
+
+/// Reports downloads and keeps the weights PatchKit answers with for the next run.
+#[derive(Clone)]
+pub struct CdnFeedback {
+    network: NetworkManager,
+    app_slug: String,
+    /// Saved by the previous run, the ones PatchKit answers with are saved for the next.
+    weights: MirrorWeights,
+    weights_path: PathBuf,
+}
+
+impl CdnFeedback {
+    /// Feedback if the user opted in, with the weights saved in `patcher_dir`.
+    pub fn new(enabled: bool, network: NetworkManager, app_slug: &str, patcher_dir: &Path) -> Option<Self> {
+        if !enabled {
+            return None;
+        }
+        let weights_path = patcher_dir.join(MIRROR_WEIGHTS_FILE_NAME);
+        Some(Self {
+            network,
+            app_slug: app_slug.to_string(),
+            weights: MirrorWeights::load(&weights_path),
+            weights_path,
+        })
+    }
+
+    /// URL to download `part` from, per the weights of the previous run.
+    pub fn choose_url<'a>(&self, part: &'a ContentUrl) -> &'a str {
+        self.weights.choose(part)
+    }
+
+    /// Sends the statistics of downloading `bytes` from `url` in `elapsed`. Failures are only
+    /// logged, the statistics never affect the update.
+    pub async fn report(&self, version: &str, url: &str, bytes: u64, elapsed: Duration, errors: u32, succeeded: bool) {
+        let mirror = host(url).unwrap_or_default();
+        let report = DownloadReport {
+            app_slug: &self.app_slug,
+            version,
+            mirror: &mirror,
+            bytes,
+            throughput_kbps: bytes as f64 / 1024.0 / elapsed.as_secs_f64().max(0.001),
+            errors,
+            succeeded,
+            runner_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+        };
+        let response = match self.network.report_download(&report).await {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("Failed to send download statistics: {}", e);
+                return;
+            },
+        };
+        if response.mirror_weights.0.is_empty() {
+            return;
+        }
+        info!("Got weights for {} mirrors", response.mirror_weights.0.len());
+        if let Err(e) = response.mirror_weights.save(&self.weights_path) {
+            warn!("Failed to save mirror weights to {}: {}", self.weights_path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_mirror_weights() {
+        let part = ContentUrl {
+            size: 1,
+            url: "https://cdn-a.example.com/1.zip".into(),
+            arch: None,
+            mirrors: vec!["https://cdn-b.example.com/1.zip".into(), "https://cdn-c.example.com/1.zip".into()],
+            continued: false,
+            transports: Vec::new(),
+            torrent: None,
+        };
+        assert_eq!(MirrorWeights::default().choose(&part), "https://cdn-a.example.com/1.zip");
+
+        let response: ReportResponse = serde_json::from_str(
+            r#"{"mirror_weights": {"cdn-a.example.com": 0.2, "cdn-c.example.com": 3.0}}"#,
+        )
+        .unwrap();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(MIRROR_WEIGHTS_FILE_NAME);
+        response.mirror_weights.save(&path).unwrap();
+        let weights = MirrorWeights::load(&path);
+        assert_eq!(weights, response.mirror_weights);
+        assert_eq!(weights.choose(&part), "https://cdn-c.example.com/1.zip");
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(MirrorWeights::load(&path), MirrorWeights::default());
+    }
+}
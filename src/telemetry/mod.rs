@@ -0,0 +1,197 @@
+//! Opt-in upload of crash reports and fatal errors to an endpoint chosen by the app's developer.
+
+pub mod analytics;
+pub mod cdn;
+
+use crate::config::settings::Settings;
+use crate::config::telemetry::TelemetryConfig;
+use crate::logging::phases::{self, PhaseTiming};
+use crate::{Error, Result};
+use log::{debug, info};
+use reqwest::Client;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+static LAST_PHASE: Mutex<Option<&'static str>> = Mutex::new(None);
+
+/// Remembers the update phase that is running, reported if the runner fails.
+pub fn set_last_phase(phase: &'static str) {
+    if let Ok(mut last_phase) = LAST_PHASE.lock() {
+        *last_phase = Some(phase);
+    }
+}
+
+pub fn last_phase() -> Option<&'static str> {
+    LAST_PHASE.lock().ok().and_then(|last_phase| *last_phase)
+}
+
+/// Anonymous failure event sent to PatchKit, so that failure rates per app can be tracked.
+/// It identifies neither the user nor the machine.
+#[derive(Debug, Serialize)]
+pub struct ErrorEvent {
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<&'static str>,
+    pub os: &'static str,
+    pub runner_version: &'static str,
+    pub app_slug: String,
+}
+
+impl ErrorEvent {
+    pub fn new(error: &Error, app_slug: &str) -> Self {
+        Self {
+            code: error.code(),
+            phase: last_phase(),
+            os: std::env::consts::OS,
+            runner_version: env!("CARGO_PKG_VERSION"),
+            app_slug: app_slug.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportKind {
+    Error,
+    Crash,
+}
+
+/// Body of an uploaded report.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub kind: ReportKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub runner_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// Durations of the update phases that finished before the report.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl ErrorReport {
+    pub fn from_error(error: &Error) -> Self {
+        Self {
+            phases: phases::timings(),
+            ..Self::new(ReportKind::Error, Some(error.code()), error.to_string())
+        }
+    }
+
+    /// Report with the contents of a crash file.
+    pub fn crash(report: String) -> Self {
+        Self::new(ReportKind::Crash, None, report)
+    }
+
+    fn new(kind: ReportKind, code: Option<&'static str>, message: String) -> Self {
+        Self {
+            kind,
+            code,
+            message,
+            runner_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            phases: Vec::new(),
+        }
+    }
+}
+
+pub struct Telemetry {
+    client: Client,
+    endpoint: String,
+}
+
+impl Telemetry {
+    /// Telemetry if the dat configures an endpoint and the user agreed to send reports.
+    pub fn new(config: Option<&TelemetryConfig>, settings: &Settings) -> Option<Self> {
+        let config = config?;
+        if !settings.send_error_reports {
+            debug!("Error reports are disabled in the settings");
+            return None;
+        }
+
+        Some(Self {
+            client: Client::new(),
+            endpoint: config.endpoint.clone(),
+        })
+    }
+
+    pub async fn send(&self, report: &ErrorReport) -> Result<()> {
+        self.client
+            .post(&self.endpoint)
+            .timeout(REPORT_TIMEOUT)
+            .json(report)
+            .send()
+            .await?
+            .error_for_status()?;
+        info!("Sent {:?} report", report.kind);
+        Ok(())
+    }
+
+    /// Sends the crash report left by a previous run, if any. It's renamed once sent, so it's
+    /// still available to the user but not sent again.
+    pub async fn upload_crash_report(&self, path: &Path) -> Result<()> {
+        let Ok(report) = fs::read_to_string(path) else {
+            return Ok(());
+        };
+
+        self.send(&ErrorReport::crash(report)).await?;
+        fs::rename(path, sent_path(path))?;
+        Ok(())
+    }
+}
+
+fn sent_path(path: &Path) -> PathBuf {
+    path.with_extension("sent.txt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requires_consent() {
+        let config = TelemetryConfig { endpoint: "https://example.com/reports".into() };
+        let mut settings = Settings::default();
+        assert!(Telemetry::new(Some(&config), &settings).is_none());
+
+        settings.send_error_reports = true;
+        assert!(Telemetry::new(None, &settings).is_none());
+        assert!(Telemetry::new(Some(&config), &settings).is_some());
+    }
+
+    #[test]
+    fn test_error_report() {
+        let report = ErrorReport::from_error(&Error::Launch("Not found".into()));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["kind"], "error");
+        assert_eq!(json["code"], "PKR-4101");
+        assert_eq!(json["message"], "Launch error: Not found");
+
+        let json = serde_json::to_value(ErrorReport::crash("boom".into())).unwrap();
+        assert_eq!(json["kind"], "crash");
+        assert!(json.get("code").is_none());
+    }
+
+    #[test]
+    fn test_error_event() {
+        set_last_phase("Download");
+        let event = ErrorEvent::new(&Error::Connection("No internet connection".into()), "abcd1234");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["code"], "PKR-1101");
+        assert_eq!(json["phase"], "Download");
+        assert_eq!(json["app_slug"], "abcd1234");
+        assert!(json.get("message").is_none());
+    }
+
+    #[test]
+    fn test_sent_path() {
+        assert_eq!(sent_path(Path::new("logs/launcher-crash.txt")), Path::new("logs/launcher-crash.sent.txt"));
+    }
+}
@@ -0,0 +1,209 @@
+//! Friendly, translated explanations for error codes. The technical message is still shown
+//! as details, for support.
+
+use crate::config::settings::LANGUAGES;
+use crate::format::format_timestamp;
+use crate::Error;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HELP_URL: &str = "https://docs.patchkit.net/runner/errors";
+
+/// An error as shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayError {
+    /// Code of the runner error, `None` for problems of the UI itself.
+    pub code: Option<&'static str>,
+    pub details: String,
+}
+
+impl DisplayError {
+    pub fn new(error: &Error) -> Self {
+        Self {
+            code: Some(error.code()),
+            details: error.to_string(),
+        }
+    }
+
+    pub fn plain(details: impl Into<String>) -> Self {
+        Self {
+            code: None,
+            details: details.into(),
+        }
+    }
+
+    /// What went wrong and what to do about it, in `language` or English.
+    pub fn summary(&self, language: Option<&str>) -> Option<String> {
+        let messages = messages(self.code?);
+        let index = language
+            .and_then(|language| LANGUAGES.iter().position(|(code, _)| *code == language))
+            .unwrap_or(0);
+        Some(messages[index].replace("{year}", &clock_year()))
+    }
+
+    pub fn help_url(&self) -> Option<String> {
+        self.code.map(|code| format!("{}#{}", HELP_URL, code.to_ascii_lowercase()))
+    }
+}
+
+impl fmt::Display for DisplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} [{}]", self.details, code),
+            None => write!(f, "{}", self.details),
+        }
+    }
+}
+
+/// The year the computer's clock is set to.
+fn clock_year() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    format_timestamp(now)[..4].to_string()
+}
+
+/// Messages for `code`, in the order of [`LANGUAGES`]. `{year}` is replaced with the year of
+/// the computer's clock.
+fn messages(code: &str) -> [&'static str; 9] {
+    match code {
+        "PKR-1101" => [
+            "Couldn't connect to the internet. Check your connection and try again.",
+            "Keine Internetverbindung. Überprüfe deine Verbindung und versuche es erneut.",
+            "No se pudo conectar a internet. Comprueba tu conexión e inténtalo de nuevo.",
+            "Impossible de se connecter à Internet. Vérifiez votre connexion et réessayez.",
+            "Brak połączenia z internetem. Sprawdź połączenie i spróbuj ponownie.",
+            "Нет подключения к интернету. Проверьте соединение и повторите попытку.",
+            "インターネットに接続できません。接続を確認して、もう一度お試しください。",
+            "인터넷에 연결할 수 없습니다. 연결을 확인하고 다시 시도하세요.",
+            "无法连接到互联网。请检查网络连接后重试。",
+        ],
+        "PKR-1201" | "PKR-2201" => [
+            "The update server couldn't be reached. Please try again later.",
+            "Der Update-Server ist nicht erreichbar. Bitte versuche es später erneut.",
+            "No se pudo contactar con el servidor de actualizaciones. Inténtalo más tarde.",
+            "Le serveur de mise à jour est injoignable. Veuillez réessayer plus tard.",
+            "Nie udało się połączyć z serwerem aktualizacji. Spróbuj ponownie później.",
+            "Сервер обновлений недоступен. Повторите попытку позже.",
+            "アップデートサーバーに接続できません。しばらくしてからもう一度お試しください。",
+            "업데이트 서버에 연결할 수 없습니다. 나중에 다시 시도하세요.",
+            "无法连接到更新服务器。请稍后重试。",
+        ],
+        "PKR-1203" => [
+            "The update servers are busy right now. Please try again in a few minutes.",
+            "Die Update-Server sind gerade ausgelastet. Bitte versuche es in ein paar Minuten erneut.",
+            "Los servidores de actualizaciones están ocupados. Inténtalo de nuevo en unos minutos.",
+            "Les serveurs de mise à jour sont surchargés. Veuillez réessayer dans quelques minutes.",
+            "Serwery aktualizacji są teraz przeciążone. Spróbuj ponownie za kilka minut.",
+            "Серверы обновлений сейчас перегружены. Повторите попытку через несколько минут.",
+            "アップデートサーバーが混み合っています。数分後にもう一度お試しください。",
+            "업데이트 서버가 혼잡합니다. 몇 분 후에 다시 시도하세요.",
+            "更新服务器当前繁忙。请几分钟后重试。",
+        ],
+        "PKR-1301" => [
+            "Your computer's date/time appears incorrect (set to {year}), so secure connections fail. Correct it in the system settings and try again.",
+            "Datum und Uhrzeit deines Computers scheinen falsch zu sein (eingestellt auf {year}), daher schlagen sichere Verbindungen fehl. Korrigiere sie in den Systemeinstellungen und versuche es erneut.",
+            "La fecha y hora de tu equipo parecen incorrectas (configuradas en {year}), por lo que las conexiones seguras fallan. Corrígelas en la configuración del sistema e inténtalo de nuevo.",
+            "La date et l'heure de votre ordinateur semblent incorrectes (réglées sur {year}), les connexions sécurisées échouent donc. Corrigez-les dans les paramètres système et réessayez.",
+            "Data i godzina komputera wydają się nieprawidłowe (ustawione na {year}), przez co bezpieczne połączenia się nie udają. Popraw je w ustawieniach systemu i spróbuj ponownie.",
+            "Похоже, на компьютере установлены неверные дата и время ({year} год), поэтому защищённые соединения не работают. Исправьте их в настройках системы и повторите попытку.",
+            "コンピューターの日付と時刻が正しくないようです（{year}年に設定されています）。そのため安全な接続ができません。システム設定で修正して、もう一度お試しください。",
+            "컴퓨터의 날짜와 시간이 잘못된 것 같습니다({year}년으로 설정됨). 그래서 보안 연결에 실패합니다. 시스템 설정에서 수정한 후 다시 시도하세요.",
+            "您的电脑日期和时间似乎不正确（设置为 {year} 年），因此安全连接失败。请在系统设置中更正后重试。",
+        ],
+        "PKR-2101" => [
+            "The game's launcher files are damaged. Please reinstall the game.",
+            "Die Startdateien des Spiels sind beschädigt. Bitte installiere das Spiel neu.",
+            "Los archivos del lanzador del juego están dañados. Reinstala el juego.",
+            "Les fichiers du lanceur du jeu sont endommagés. Veuillez réinstaller le jeu.",
+            "Pliki launchera gry są uszkodzone. Zainstaluj grę ponownie.",
+            "Файлы лаунчера игры повреждены. Переустановите игру.",
+            "ゲームのランチャーファイルが破損しています。ゲームを再インストールしてください。",
+            "게임 런처 파일이 손상되었습니다. 게임을 다시 설치하세요.",
+            "游戏启动器文件已损坏。请重新安装游戏。",
+        ],
+        "PKR-2301" | "PKR-2401" | "PKR-4101" | "PKR-4201" => [
+            "The launcher couldn't be installed or started. Try repairing the installation.",
+            "Der Launcher konnte nicht installiert oder gestartet werden. Versuche, die Installation zu reparieren.",
+            "No se pudo instalar o iniciar el lanzador. Intenta reparar la instalación.",
+            "Le lanceur n'a pas pu être installé ou démarré. Essayez de réparer l'installation.",
+            "Nie udało się zainstalować ani uruchomić launchera. Spróbuj naprawić instalację.",
+            "Не удалось установить или запустить лаунчер. Попробуйте восстановить установку.",
+            "ランチャーをインストールまたは起動できませんでした。インストールの修復をお試しください。",
+            "런처를 설치하거나 실행할 수 없습니다. 설치를 복구해 보세요.",
+            "无法安装或启动启动器。请尝试修复安装。",
+        ],
+        "PKR-3101" | "PKR-3201" | "PKR-3401" => [
+            "The update couldn't be saved. Check that there is enough free disk space.",
+            "Das Update konnte nicht gespeichert werden. Überprüfe, ob genug Speicherplatz frei ist.",
+            "No se pudo guardar la actualización. Comprueba que haya suficiente espacio libre en disco.",
+            "La mise à jour n'a pas pu être enregistrée. Vérifiez qu'il reste assez d'espace disque.",
+            "Nie udało się zapisać aktualizacji. Sprawdź, czy na dysku jest wystarczająco miejsca.",
+            "Не удалось сохранить обновление. Убедитесь, что на диске достаточно свободного места.",
+            "アップデートを保存できませんでした。ディスクの空き容量が十分にあるか確認してください。",
+            "업데이트를 저장할 수 없습니다. 디스크 여유 공간이 충분한지 확인하세요.",
+            "无法保存更新。请检查磁盘空间是否充足。",
+        ],
+        "PKR-3301" => [
+            "The runner isn't allowed to write the game files. Try running it as administrator.",
+            "Keine Berechtigung zum Schreiben der Spieldateien. Versuche, das Programm als Administrator auszuführen.",
+            "No hay permiso para escribir los archivos del juego. Prueba a ejecutarlo como administrador.",
+            "Autorisation refusée pour écrire les fichiers du jeu. Essayez de le lancer en tant qu'administrateur.",
+            "Brak uprawnień do zapisu plików gry. Spróbuj uruchomić program jako administrator.",
+            "Нет прав на запись файлов игры. Попробуйте запустить программу от имени администратора.",
+            "ゲームファイルを書き込む権限がありません。管理者として実行してみてください。",
+            "게임 파일을 쓸 권한이 없습니다. 관리자 권한으로 실행해 보세요.",
+            "没有写入游戏文件的权限。请尝试以管理员身份运行。",
+        ],
+        "PKR-5101" => [
+            "The update was cancelled.",
+            "Das Update wurde abgebrochen.",
+            "La actualización se ha cancelado.",
+            "La mise à jour a été annulée.",
+            "Aktualizacja została anulowana.",
+            "Обновление отменено.",
+            "アップデートはキャンセルされました。",
+            "업데이트가 취소되었습니다.",
+            "更新已取消。",
+        ],
+        _ => [
+            "Something went wrong. Please try again.",
+            "Etwas ist schiefgelaufen. Bitte versuche es erneut.",
+            "Algo salió mal. Inténtalo de nuevo.",
+            "Une erreur s'est produite. Veuillez réessayer.",
+            "Coś poszło nie tak. Spróbuj ponownie.",
+            "Что-то пошло не так. Повторите попытку.",
+            "問題が発生しました。もう一度お試しください。",
+            "문제가 발생했습니다. 다시 시도하세요.",
+            "出现问题。请重试。",
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let error = DisplayError::new(&Error::Connection("No internet connection".into()));
+        assert_eq!(error.to_string(), "Connection error: No internet connection [PKR-1101]");
+        assert!(error.summary(None).unwrap().starts_with("Couldn't connect"));
+        assert!(error.summary(Some("pl")).unwrap().starts_with("Brak połączenia"));
+        // Unknown languages fall back to English
+        assert_eq!(error.summary(Some("xx")), error.summary(Some("en")));
+        assert_eq!(error.help_url().unwrap(), "https://docs.patchkit.net/runner/errors#pkr-1101");
+
+        let error = DisplayError { code: Some("PKR-1301"), details: String::new() };
+        assert!(error.summary(None).unwrap().contains(&format!("(set to {})", clock_year())));
+        assert!(!error.summary(Some("ja")).unwrap().contains("{year}"));
+
+        let error = DisplayError::plain("Failed to start repair");
+        assert_eq!(error.summary(None), None);
+        assert_eq!(error.help_url(), None);
+    }
+
+    #[test]
+    fn test_messages_match_languages() {
+        assert_eq!(messages("PKR-9001").len(), LANGUAGES.len());
+        assert_eq!(LANGUAGES[0].0, "en");
+    }
+}
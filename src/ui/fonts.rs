@@ -0,0 +1,72 @@
+use eframe::egui::{self, FontData, FontDefinitions, FontFamily};
+use log::{debug, info};
+use std::path::Path;
+
+/// System fonts covering CJK scripts, by preference. egui's bundled fonts cover Latin and Cyrillic
+/// but not CJK, so app names and translations in these scripts would otherwise render as boxes.
+/// The first one found is used.
+#[cfg(target_os = "windows")]
+const FALLBACK_FONTS: &[&str] = &[
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+    "C:\\Windows\\Fonts\\malgun.ttf",
+];
+#[cfg(target_os = "macos")]
+const FALLBACK_FONTS: &[&str] = &[
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/System/Library/Fonts/AppleSDGothicNeo.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const FALLBACK_FONTS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+];
+
+const FALLBACK_FONT_NAME: &str = "system-fallback";
+
+/// Loads a system font with CJK coverage and registers it as a fallback for all text.
+pub fn install_fallback_fonts(ctx: &egui::Context) {
+    let Some((path, data)) = FALLBACK_FONTS.iter().find_map(|path| {
+        std::fs::read(path).ok().map(|data| (Path::new(path), data))
+    }) else {
+        debug!("No CJK fallback font found");
+        return;
+    };
+
+    info!("Using fallback font {}", path.display());
+    let mut fonts = FontDefinitions::default();
+    add_fallback(&mut fonts, FALLBACK_FONT_NAME, FontData::from_owned(data));
+    ctx.set_fonts(fonts);
+}
+
+/// Adds `data` after the default fonts, so it's only used for glyphs they don't have.
+fn add_fallback(fonts: &mut FontDefinitions, name: &str, data: FontData) {
+    fonts.font_data.insert(name.to_owned(), data);
+    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+        fonts.families.entry(family).or_default().push(name.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_fallback() {
+        let mut fonts = FontDefinitions::default();
+        add_fallback(&mut fonts, "fallback", FontData::from_owned(Vec::new()));
+
+        assert!(fonts.font_data.contains_key("fallback"));
+        for family in [FontFamily::Proportional, FontFamily::Monospace] {
+            let names = &fonts.families[&family];
+            assert_eq!(names.last().map(String::as_str), Some("fallback"));
+            assert!(names.len() > 1);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::config::Branding;
+
+const DEFAULT_WIDTH: f32 = 400.0;
+const DEFAULT_HEIGHT: f32 = 120.0;
+const DEFAULT_MAX_HEIGHT: f32 = 400.0;
+const MIN_WIDTH: f32 = 300.0;
+const MAX_SIZE: f32 = 1600.0;
+// Space kept below the content, matching the central panel's inner margin
+const CONTENT_MARGIN: f32 = 8.0;
+
+/// Window size and optional panels, configurable through the dat file's branding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub width: f32,
+    /// Height of the window before it grows to fit its content.
+    pub height: f32,
+    pub max_height: f32,
+    pub show_changelog: bool,
+    pub show_news: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            max_height: DEFAULT_MAX_HEIGHT,
+            show_changelog: true,
+            show_news: true,
+        }
+    }
+}
+
+impl Layout {
+    pub fn from_branding(branding: &Branding) -> Self {
+        let default = Self::default();
+        let width = branding.window_width.map_or(default.width, |width| width.clamp(MIN_WIDTH, MAX_SIZE));
+        let height = branding
+            .window_height
+            .map_or(default.height, |height| height.clamp(DEFAULT_HEIGHT, MAX_SIZE));
+        Self {
+            width,
+            height,
+            // The window can still grow for long errors or the expanded changelog
+            max_height: default.max_height.max(height),
+            show_changelog: branding.show_changelog.unwrap_or(default.show_changelog),
+            show_news: branding.show_news.unwrap_or(default.show_news),
+        }
+    }
+
+    /// Window height needed to show content ending at `content_bottom`, within the allowed range.
+    pub fn fit_height(&self, content_bottom: f32) -> f32 {
+        (content_bottom + CONTENT_MARGIN)
+            .ceil()
+            .clamp(self.height, self.max_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_height() {
+        let layout = Layout::default();
+        assert_eq!(layout.fit_height(50.0), DEFAULT_HEIGHT);
+        assert_eq!(layout.fit_height(200.0), 200.0 + CONTENT_MARGIN);
+        assert_eq!(layout.fit_height(10_000.0), DEFAULT_MAX_HEIGHT);
+    }
+
+    #[test]
+    fn test_from_branding() {
+        let branding: Branding = serde_json::from_str(
+            r#"{"window_width": 640, "window_height": 480, "show_news": false}"#,
+        ).unwrap();
+        let layout = Layout::from_branding(&branding);
+        assert_eq!((layout.width, layout.height, layout.max_height), (640.0, 480.0, 480.0));
+        assert!(layout.show_changelog);
+        assert!(!layout.show_news);
+
+        let branding: Branding = serde_json::from_str(r#"{"window_width": 10}"#).unwrap();
+        assert_eq!(Layout::from_branding(&branding).width, MIN_WIDTH);
+        assert_eq!(Layout::from_branding(&Branding::default()), Layout::default());
+    }
+}
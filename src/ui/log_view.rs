@@ -0,0 +1,106 @@
+use eframe::egui::{self, RichText};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+const LOG_HEIGHT: f32 = 150.0;
+
+/// The most recent log lines, kept in memory so that they can be shown in the window.
+#[derive(Clone)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Wraps the logger's output so that everything written to it is also kept in the buffer.
+    pub fn writer<W: Write>(&self, inner: W) -> LogWriter<W> {
+        LogWriter {
+            inner,
+            buffer: self.clone(),
+            partial: String::new(),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().map(String::as_str).collect::<Vec<_>>().join("\n")
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// Log target writing to `inner` and to a [`LogBuffer`].
+pub struct LogWriter<W: Write> {
+    inner: W,
+    buffer: LogBuffer,
+    partial: String,
+}
+
+impl<W: Write> Write for LogWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.partial.push_str(&String::from_utf8_lossy(&buf[..written]));
+        while let Some(end) = self.partial.find('\n') {
+            let line = self.partial[..end].trim_end_matches('\r').to_string();
+            self.partial.drain(..=end);
+            self.buffer.push(line);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Collapsible "Show log" section tailing the buffer.
+pub fn show(ui: &mut egui::Ui, buffer: &LogBuffer) {
+    egui::CollapsingHeader::new("Show log")
+        .default_open(false)
+        .show(ui, |ui| {
+            egui::ScrollArea::both()
+                .max_height(LOG_HEIGHT)
+                .stick_to_bottom(true)
+                .show(ui, |ui| ui.label(RichText::new(buffer.text()).monospace().small()));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_splits_lines() {
+        let buffer = LogBuffer::new(10);
+        let mut output = Vec::new();
+        {
+            let mut writer = buffer.writer(&mut output);
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\r\n").unwrap();
+        }
+        assert_eq!(output, b"first\nsecond\r\n");
+        assert_eq!(buffer.text(), "first\nsecond");
+    }
+
+    #[test]
+    fn test_buffer_keeps_latest_lines() {
+        let buffer = LogBuffer::new(2);
+        let mut writer = buffer.writer(io::sink());
+        writer.write_all(b"one\ntwo\nthree\n").unwrap();
+        assert_eq!(buffer.text(), "two\nthree");
+    }
+}
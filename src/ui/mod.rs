@@ -1,58 +1,527 @@
+use crate::cli::{FORCE_UPDATE_FLAG, REPAIR_FLAG};
+use crate::config::{LocalizedStrings, Settings, ThemeMode};
+use crate::diagnostics::BundleSources;
+use error_catalog::DisplayError;
+use crate::format::{format_duration, format_speed};
+use crate::network::DownloadControl;
+use crate::system::elevation;
 use eframe::egui::{self, Color32, RichText};
-use std::sync::mpsc::{channel, Receiver, Sender};
+use log::{info, warn};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+pub mod error_catalog;
+pub mod fonts;
+pub mod layout;
+pub mod log_view;
+pub mod settings;
+pub mod splash;
+pub mod taskbar;
+pub mod terminal;
+#[cfg(feature = "tray")]
+pub mod tray;
+
+const CHANGELOG_HEIGHT: f32 = 150.0;
+const ERROR_HEIGHT: f32 = 250.0;
+const NEWS_HEIGHT: f32 = 80.0;
+// How often the message relay checks whether the download was paused
+const RELAY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Repaint interval when no messages arrive
+const IDLE_REPAINT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Connect,
+    AppInfo,
+    Version,
+    Download,
+    Extract,
+    Launch,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::Connect,
+        Phase::AppInfo,
+        Phase::Version,
+        Phase::Download,
+        Phase::Extract,
+        Phase::Launch,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Phase::Connect => "Connect",
+            Phase::AppInfo => "App info",
+            Phase::Version => "Version",
+            Phase::Download => "Download",
+            Phase::Extract => "Extract",
+            Phase::Launch => "Launch",
+        }
+    }
+}
+
+/// Answer to [`UiMessage::AskUpdate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateChoice {
+    Update,
+    LaunchInstalled,
+}
 
 #[derive(Debug)]
 pub enum UiMessage {
+    SetPhase(Phase),
     SetStatus(String),
     SetProgress(f32),
-    SetDownloadProgress { progress: f32, speed_kbps: f64 },
-    ShowError(String),
+    /// Download progress with the speed over the last seconds and the time left, if known.
+    SetDownloadProgress { progress: f32, speed_kbps: f64, eta: Option<Duration> },
+    /// No download data has arrived for the given time. Cleared by the next progress update.
+    SetStalled(Duration),
+    SetChangelog(String),
+    SetNews(String),
+    SetVersionInfo { app_name: Option<String>, version: String },
+    /// Asks whether to install `version` now. Updating is assumed if `reply` is dropped unanswered.
+    AskUpdate { version: String, countdown: Duration, reply: oneshot::Sender<UpdateChoice> },
+    /// Asks which of the apps to run, answered with its index. The run fails if `reply` is dropped.
+    AskApp { apps: Vec<String>, reply: oneshot::Sender<usize> },
+    /// Soft failure shown as a dismissible toast, without interrupting the update.
+    ShowWarning(String),
+    ShowError(DisplayError),
     Close,
 }
 
+/// Pending "Update available" question, answered with `Update` once the deadline passes.
+struct UpdatePrompt {
+    version: String,
+    deadline: Instant,
+    reply: oneshot::Sender<UpdateChoice>,
+}
+
 pub struct RunnerApp {
+    phase: Option<Phase>,
     status: String,
     progress: f32,
-    error: Option<String>,
-    download_speed: Option<f64>,
+    error: Option<DisplayError>,
+    warnings: Vec<String>,
+    /// Speed in KB/s and the time left, if known.
+    download_speed: Option<(f64, Option<Duration>)>,
+    stalled_for: Option<Duration>,
+    changelog: Option<String>,
+    news: Option<String>,
+    version_info: Option<String>,
+    update_prompt: Option<UpdatePrompt>,
+    app_prompt: Option<(Vec<String>, oneshot::Sender<usize>)>,
+    actions_focused: bool,
+    gaming_mode: bool,
+    confirm_close: bool,
+    exiting: bool,
+    /// Flag the runner restarts with once the cancelled update has cleaned up.
+    restart_flag: Option<&'static str>,
+    layout: layout::Layout,
+    window_height: f32,
     receiver: Receiver<UiMessage>,
     sender: Sender<UiMessage>,
+    control: DownloadControl,
+    settings: Settings,
+    settings_path: Option<PathBuf>,
+    settings_panel: Option<settings::SettingsPanel>,
+    theme: ThemeMode,
+    strings: LocalizedStrings,
+    cache_dir: Option<PathBuf>,
+    diagnostics: Option<(BundleSources, PathBuf)>,
+    bundle_message: Option<String>,
+    splash: Option<splash::Splash>,
+    window: taskbar::WindowSlot,
+    log: Option<log_view::LogBuffer>,
+    #[cfg(feature = "tray")]
+    tray: Option<tray::Tray>,
 }
 
 impl RunnerApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // Set window size
-        cc.egui_ctx.set_pixels_per_point(1.0);
-        cc.egui_ctx.set_visuals(egui::Visuals::dark());
-        
+    /// Creates the app. The OS scale factor is respected, `scale` multiplies it further.
+    pub fn new(cc: &eframe::CreationContext<'_>, scale: Option<f32>) -> Self {
+        if let Some(scale) = scale {
+            cc.egui_ctx.set_zoom_factor(scale);
+        }
+        fonts::install_fallback_fonts(&cc.egui_ctx);
+
         let (sender, receiver) = channel();
         
         Self {
+            phase: None,
             status: String::from("Initializing..."),
             progress: 0.0,
             error: None,
+            warnings: Vec::new(),
             download_speed: None,
+            stalled_for: None,
+            changelog: None,
+            news: None,
+            version_info: None,
+            update_prompt: None,
+            app_prompt: None,
+            actions_focused: false,
+            gaming_mode: false,
+            confirm_close: false,
+            exiting: false,
+            restart_flag: None,
+            layout: layout::Layout::default(),
+            window_height: layout::Layout::default().height,
             receiver,
             sender,
+            control: DownloadControl::default(),
+            settings: Settings::default(),
+            settings_path: None,
+            settings_panel: None,
+            theme: ThemeMode::default(),
+            strings: LocalizedStrings::default(),
+            cache_dir: None,
+            diagnostics: None,
+            bundle_message: None,
+            splash: None,
+            window: taskbar::WindowSlot::default(),
+            log: None,
+            #[cfg(feature = "tray")]
+            tray: tray::Tray::new(),
         }
     }
 
+    /// Enables the settings dialog, saving changes to `path`. `theme` is the currently applied theme.
+    pub fn with_settings(mut self, settings: Settings, path: PathBuf, theme: ThemeMode) -> Self {
+        self.settings = settings;
+        self.settings_path = Some(path);
+        self.theme = theme;
+        self
+    }
+
+    /// Translations from the dat's branding, used over the runner's own strings.
+    pub fn with_strings(mut self, strings: LocalizedStrings) -> Self {
+        self.strings = strings;
+        self
+    }
+
+    /// Directory removed by the settings dialog's "Delete cache" action.
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    /// Enables "Create support bundle" on the error screen, saving bundles to `output_dir`.
+    pub fn with_diagnostics(mut self, sources: BundleSources, output_dir: PathBuf) -> Self {
+        self.diagnostics = Some((sources, output_dir));
+        self
+    }
+
+    /// Splash to close once the first frame has been painted.
+    pub fn with_splash(mut self, splash: Option<splash::Splash>) -> Self {
+        self.splash = splash;
+        self
+    }
+
+    /// Window size and panels. It must match the size the window was created with.
+    pub fn with_layout(mut self, layout: layout::Layout) -> Self {
+        self.layout = layout;
+        self.window_height = layout.height;
+        self
+    }
+
+    /// Fullscreen under gamescope, driven with a gamepad: there's no title bar to close the window
+    /// with, and the actions keep the focus so the D-pad can move between them.
+    pub fn with_gaming_mode(mut self, gaming_mode: bool) -> Self {
+        self.gaming_mode = gaming_mode;
+        self
+    }
+
+    /// Log lines shown in the "Show log" section.
+    pub fn with_log(mut self, log: log_view::LogBuffer) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    pub fn with_download_control(mut self, control: DownloadControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Sender straight to the window. It doesn't wake the UI, so messages may wait for the next
+    /// repaint; the runner logic should use [`Self::relay_sender`].
     pub fn sender(&self) -> Sender<UiMessage> {
         self.sender.clone()
     }
+
+    /// Sender for the runner logic whose messages are relayed to the window. The relay also
+    /// updates the taskbar button and wakes the UI, so progress keeps showing while minimized.
+    pub fn relay_sender(&self, ctx: &egui::Context) -> Sender<UiMessage> {
+        let (sender, receiver) = channel();
+        let to_ui = self.sender.clone();
+        let ctx = ctx.clone();
+        let control = self.control.clone();
+        let mut taskbar = taskbar::Taskbar::new(self.window.clone());
+
+        std::thread::spawn(move || loop {
+            match receiver.recv_timeout(RELAY_POLL_INTERVAL) {
+                Ok(message) => {
+                    taskbar.set_state(taskbar.state().next(&message).with_pause(control.is_paused()));
+                    let close = matches!(message, UiMessage::Close);
+                    if to_ui.send(message).is_err() {
+                        break;
+                    }
+                    if close {
+                        // A minimized window isn't updated, so it would never see the request
+                        taskbar.restore_window();
+                    }
+                    ctx.request_repaint();
+                },
+                // Nothing is sent while paused, so poll for pause changes
+                Err(RecvTimeoutError::Timeout) => {
+                    taskbar.set_state(taskbar.state().with_pause(control.is_paused()));
+                },
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        sender
+    }
+
+    fn show_settings_button(&mut self, ctx: &egui::Context) {
+        let Some(path) = &self.settings_path else {
+            return;
+        };
+        if self.settings_panel.is_some() {
+            return;
+        }
+
+        egui::Area::new("settings_button")
+            .anchor(egui::Align2::RIGHT_TOP, [-4.0, 4.0])
+            .show(ctx, |ui| {
+                if ui.small_button("⚙").on_hover_text("Settings").clicked() {
+                    self.settings_panel = Some(settings::SettingsPanel::new(
+                        &self.settings,
+                        self.theme,
+                        path.clone(),
+                        self.cache_dir.clone(),
+                    ));
+                }
+            });
+    }
+
+    /// Stands in for the title bar's close button, which fullscreen windows don't have.
+    fn show_exit_button(&self, ctx: &egui::Context) {
+        if !self.gaming_mode {
+            return;
+        }
+
+        egui::Area::new("exit_button")
+            .anchor(egui::Align2::LEFT_TOP, [4.0, 4.0])
+            .show(ctx, |ui| {
+                if ui.small_button("✕ Exit").clicked() {
+                    // Asks for confirmation mid-update like closing the window does
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            });
+    }
+
+    fn show_warnings(&mut self, ctx: &egui::Context) {
+        if self.warnings.is_empty() {
+            return;
+        }
+
+        let mut dismissed = None;
+        egui::Area::new("warnings")
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -4.0])
+            .show(ctx, |ui| {
+                for (i, warning) in self.warnings.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(warning).color(ui.visuals().warn_fg_color));
+                            if ui.small_button("✕").on_hover_text("Dismiss").clicked() {
+                                dismissed = Some(i);
+                            }
+                        });
+                    });
+                }
+            });
+
+        if let Some(i) = dismissed {
+            self.warnings.remove(i);
+        }
+    }
+
+    fn apply_theme_preview(&self, ctx: &egui::Context, frame: &eframe::Frame, mode: ThemeMode) {
+        let theme = match mode {
+            ThemeMode::Dark => eframe::Theme::Dark,
+            ThemeMode::Light => eframe::Theme::Light,
+            ThemeMode::Auto => frame.info().system_theme.unwrap_or(eframe::Theme::Dark),
+        };
+        ctx.set_visuals(theme.egui_visuals());
+    }
+
+    /// Whether an update step is running that must not be disturbed, e.g. by deleting the cache.
+    fn is_busy(&self) -> bool {
+        self.error.is_none() && matches!(self.phase, Some(Phase::Download) | Some(Phase::Extract))
+    }
+
+    /// Restarts with `--force-update`, cancelling the running update first.
+    fn force_update(&mut self, ctx: &egui::Context) {
+        info!("Forced update requested with the shortcut");
+        if self.is_busy() {
+            self.control.cancel();
+            self.confirm_close = false;
+            self.exiting = true;
+            self.restart_flag = Some(FORCE_UPDATE_FLAG);
+            self.status = String::from("Restarting for a full update...");
+            return;
+        }
+        match restart_with(FORCE_UPDATE_FLAG) {
+            Ok(()) => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            Err(e) => self.error = Some(DisplayError::plain(format!("Failed to restart: {}", e))),
+        }
+    }
+
+    fn show_changelog(&self, ui: &mut egui::Ui) {
+        if let Some(changelog) = self.changelog.as_ref().filter(|_| self.layout.show_changelog) {
+            egui::CollapsingHeader::new("What's new")
+                .default_open(false)
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(CHANGELOG_HEIGHT)
+                        .show(ui, |ui| ui.label(changelog));
+                });
+        }
+    }
+
+    fn show_phases(&self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 4.0;
+            for (i, phase) in Phase::ALL.iter().enumerate() {
+                if i > 0 {
+                    ui.label(RichText::new("→").small().weak());
+                }
+                let text = RichText::new(phase.label()).small();
+                let text = match self.phase {
+                    Some(current) if current == *phase => text.strong().color(ui.visuals().strong_text_color()),
+                    Some(current) if current > *phase => text,
+                    _ => text.weak(),
+                };
+                ui.label(text);
+            }
+        });
+    }
+}
+
+/// Text announced by screen readers for the progress bar.
+fn progress_label(progress: f32) -> String {
+    format!("Progress {}%", (progress.clamp(0.0, 1.0) * 100.0).round())
+}
+
+/// Tells the user what is being installed, e.g. "Installing My Game — version 42".
+pub fn version_info_label(app_name: Option<&str>, version: &str) -> String {
+    match app_name {
+        Some(app_name) => format!("Installing {} — version {}", app_name, version),
+        None => format!("Installing version {}", version),
+    }
+}
+
+/// The download speed, e.g. "Download speed: 12.30 MB/s, 45s remaining".
+fn download_speed_label(speed_kbps: f64, eta: Option<Duration>) -> String {
+    match eta {
+        Some(eta) => format!("Download speed: {}, {} remaining", format_speed(speed_kbps), format_duration(eta)),
+        None => format!("Download speed: {}", format_speed(speed_kbps)),
+    }
+}
+
+/// Shown instead of the status while no data arrives, so a stalled download isn't mistaken for a hang.
+pub fn stalled_label(stalled_for: Duration) -> String {
+    format!("Connection appears stalled — retrying... ({})", format_duration(stalled_for))
+}
+
+fn update_prompt_label(version: &str, remaining: Duration) -> String {
+    format!(
+        "Version {} is available. Updating in {}s.",
+        version,
+        remaining.as_secs_f32().ceil() as u64
+    )
+}
+
+fn show_error_details(ui: &mut egui::Ui, error: &DisplayError) {
+    egui::ScrollArea::vertical()
+        .max_height(ERROR_HEIGHT)
+        .show(ui, |ui| ui.label(RichText::new(error.to_string()).color(Color32::RED)));
+}
+
+/// Starts a new runner with the same arguments plus `flag`; the caller closes this one.
+fn restart_with(flag: &str) -> std::io::Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.iter().any(|arg| arg == flag) {
+        args.push(flag.to_string());
+    }
+    std::process::Command::new(std::env::current_exe()?).args(args).spawn()?;
+    Ok(())
+}
+
+/// Configures the window's theme. Visuals are applied by eframe, which also tracks OS changes in auto mode.
+pub fn apply_theme(options: &mut eframe::NativeOptions, mode: ThemeMode) {
+    options.follow_system_theme = mode == ThemeMode::Auto;
+    options.default_theme = match mode {
+        ThemeMode::Light => eframe::Theme::Light,
+        ThemeMode::Dark | ThemeMode::Auto => eframe::Theme::Dark,
+    };
 }
 
 impl eframe::App for RunnerApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Update runs again only after the first frame was painted, so the splash can go
+        if self.splash.is_some() && ctx.frame_nr() > 0 {
+            self.splash = None;
+        }
+        if ctx.frame_nr() == 0 {
+            self.window.set(frame);
+        }
+
         // Process any pending messages
         while let Ok(message) = self.receiver.try_recv() {
             match message {
+                UiMessage::SetPhase(phase) => self.phase = Some(phase),
                 UiMessage::SetStatus(status) => self.status = status,
                 UiMessage::SetProgress(progress) => self.progress = progress,
-                UiMessage::SetDownloadProgress { progress, speed_kbps } => {
+                UiMessage::SetDownloadProgress { progress, speed_kbps, eta } => {
                     self.progress = progress;
-                    self.download_speed = Some(speed_kbps);
+                    self.download_speed = Some((speed_kbps, eta));
+                    self.stalled_for = None;
                 },
+                UiMessage::SetStalled(stalled_for) => self.stalled_for = Some(stalled_for),
+                UiMessage::SetChangelog(changelog) => self.changelog = Some(changelog),
+                UiMessage::SetNews(news) => self.news = Some(news),
+                UiMessage::SetVersionInfo { app_name, version } => {
+                    let language = self.settings.language.as_deref();
+                    let app_name = self.strings.get(language, "app_display_name").or(app_name.as_deref());
+                    self.version_info = Some(version_info_label(app_name, &version));
+                },
+                UiMessage::AskUpdate { version, countdown, reply } => {
+                    self.update_prompt = Some(UpdatePrompt {
+                        version,
+                        deadline: Instant::now() + countdown,
+                        reply,
+                    });
+                    self.actions_focused = false;
+                },
+                UiMessage::AskApp { apps, reply } => {
+                    self.app_prompt = Some((apps, reply));
+                    self.actions_focused = false;
+                },
+                // The runner logic reports the cancellation as an error, which means it's done cleaning up
+                UiMessage::ShowError(_) if self.exiting => {
+                    if let Some(flag) = self.restart_flag.take() {
+                        if let Err(e) = restart_with(flag) {
+                            warn!("Failed to restart with {}: {}", flag, e);
+                        }
+                    }
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    return;
+                },
+                UiMessage::ShowWarning(warning) => self.warnings.push(warning),
                 UiMessage::ShowError(error) => self.error = Some(error),
                 UiMessage::Close => {
                     ctx.send_viewport_cmd(egui::ViewportCommand::Close);
@@ -61,30 +530,234 @@ impl eframe::App for RunnerApp {
             }
         }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical_centered(|ui| {
-                if let Some(error) = &self.error {
-                    ui.label(RichText::new(error).color(Color32::RED));
-                    if ui.button("Close").clicked() {
+        // Closing mid-update would leave a partial download or extraction behind, so ask first
+        if ctx.input(|i| i.viewport().close_requested()) && self.is_busy() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            if !self.exiting {
+                self.confirm_close = true;
+            }
+        }
+
+        #[cfg(feature = "tray")]
+        if let Some(tray) = &mut self.tray {
+            tray.update(ctx, self.progress, &self.control);
+        }
+
+        // Hidden shortcut for support, so users don't have to delete version.txt
+        let force_update = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::F);
+        if !self.exiting && ctx.input_mut(|i| i.consume_shortcut(&force_update)) {
+            self.force_update(ctx);
+        }
+
+        self.show_settings_button(ctx);
+        self.show_exit_button(ctx);
+        self.show_warnings(ctx);
+        let mut settings_panel = self.settings_panel.take();
+        let mut settings_action = settings::SettingsAction::None;
+        let busy = self.is_busy();
+
+        // Move keyboard focus to the error or confirmation actions once, so they can be used without a mouse.
+        // A gamepad only moves the focus with the D-pad, so in gaming mode it's restored when lost.
+        let focus_lost = self.gaming_mode && ctx.memory(|memory| memory.focus().is_none());
+        let focus_actions = (self.error.is_some()
+            || self.confirm_close
+            || self.update_prompt.is_some()
+            || self.app_prompt.is_some())
+            && (!self.actions_focused || focus_lost);
+
+        let panel = egui::CentralPanel::default().show(ctx, |ui| {
+            let content = ui.vertical_centered(|ui| {
+                if self.confirm_close {
+                    ui.label("An update is in progress. Cancel it and exit?");
+                    if ui.button("Cancel update and exit").clicked() {
+                        info!("Cancelling the update before exiting");
+                        self.control.cancel();
+                        self.confirm_close = false;
+                        self.exiting = true;
+                        self.status = String::from("Cancelling update...");
+                    }
+                    let keep = ui.button("Keep updating");
+                    if focus_actions {
+                        keep.request_focus();
+                    }
+                    if keep.clicked() {
+                        self.confirm_close = false;
+                        self.actions_focused = false;
+                    }
+                } else if let Some(prompt) = &self.update_prompt {
+                    let remaining = prompt.deadline.saturating_duration_since(Instant::now());
+                    ui.label(update_prompt_label(&prompt.version, remaining));
+                    let update = ui.button("Update now");
+                    if focus_actions {
+                        update.request_focus();
+                    }
+                    let choice = if update.clicked() || remaining.is_zero() {
+                        Some(UpdateChoice::Update)
+                    } else if ui.button("Launch installed version").clicked() {
+                        Some(UpdateChoice::LaunchInstalled)
+                    } else {
+                        None
+                    };
+                    self.show_changelog(ui);
+                    if let (Some(choice), Some(prompt)) = (choice, self.update_prompt.take()) {
+                        info!("Update prompt answered: {:?}", choice);
+                        let _ = prompt.reply.send(choice);
+                    }
+                } else if let Some((apps, _)) = &self.app_prompt {
+                    ui.label("Which game do you want to play?");
+                    let mut chosen = None;
+                    for (i, app) in apps.iter().enumerate() {
+                        let button = ui.button(app);
+                        if focus_actions && i == 0 {
+                            button.request_focus();
+                        }
+                        if button.clicked() {
+                            chosen = Some(i);
+                        }
+                    }
+                    if let (Some(i), Some((apps, reply))) = (chosen, self.app_prompt.take()) {
+                        info!("Chose app {}", apps[i]);
+                        let _ = reply.send(i);
+                    }
+                } else if let Some(panel) = &mut settings_panel {
+                    settings_action = panel.show(ui, busy);
+                } else if let Some(error) = &self.error {
+                    let language = self.settings.language.as_deref();
+                    let summary = error.code
+                        .and_then(|code| self.strings.get(language, code))
+                        .map(str::to_string)
+                        .or_else(|| error.summary(language));
+                    match summary {
+                        Some(summary) => {
+                            ui.label(RichText::new(summary).color(Color32::RED));
+                            if let Some(help_url) = error.help_url() {
+                                ui.hyperlink_to("Get help", help_url);
+                            }
+                            egui::CollapsingHeader::new("Details")
+                                .default_open(false)
+                                .show(ui, |ui| show_error_details(ui, error));
+                        },
+                        None => show_error_details(ui, error),
+                    }
+                    // Only a location the user chose ends here, others fall back to the user's directory
+                    let elevate = error.code == Some("PKR-3301") && elevation::can_elevate();
+                    let close = ui.button("Close");
+                    if focus_actions {
+                        close.request_focus();
+                    }
+                    if close.clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
+                    if ui.button("Repair")
+                        .on_hover_text("Verify the installed files and reinstall the launcher")
+                        .clicked()
+                    {
+                        match restart_with(REPAIR_FLAG) {
+                            Ok(()) => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+                            Err(e) => self.error = Some(DisplayError::plain(format!("Failed to start repair: {}", e))),
+                        }
+                    }
+                    if elevate && ui.button("Run as administrator")
+                        .on_hover_text("Restart with the rights to write to the chosen install location")
+                        .clicked()
+                    {
+                        match elevation::restart_elevated() {
+                            Ok(()) => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+                            Err(e) => self.error = Some(DisplayError::new(&e)),
+                        }
+                    }
+                    if let Some((sources, output_dir)) = &self.diagnostics {
+                        if ui.button("Create support bundle")
+                            .on_hover_text("Collect the log and configuration into a zip file for a support ticket")
+                            .clicked()
+                        {
+                            self.bundle_message = Some(match sources.create(output_dir) {
+                                Ok(path) => format!("Support bundle saved to {}", path.display()),
+                                Err(e) => format!("Failed to create support bundle: {}", e),
+                            });
+                        }
+                    }
+                    if let Some(message) = &self.bundle_message {
+                        ui.label(RichText::new(message).small());
+                    }
+                    if let Some(log) = &self.log {
+                        log_view::show(ui, log);
+                    }
                 } else {
-                    ui.label(&self.status);
+                    if let Some(news) = self.news.as_ref().filter(|_| self.layout.show_news) {
+                        egui::Frame::group(ui.style()).show(ui, |ui| {
+                            egui::ScrollArea::vertical()
+                                .max_height(NEWS_HEIGHT)
+                                .show(ui, |ui| ui.label(news));
+                        });
+                    }
+                    if let Some(version_info) = &self.version_info {
+                        ui.strong(version_info);
+                    }
+                    self.show_phases(ui);
+                    match self.stalled_for {
+                        Some(stalled_for) => {
+                            ui.label(RichText::new(stalled_label(stalled_for)).color(ui.visuals().warn_fg_color))
+                        },
+                        None => ui.label(self.strings.translate(self.settings.language.as_deref(), &self.status)),
+                    };
                     ui.add_space(10.0);
                     
-                    ui.add(egui::ProgressBar::new(self.progress)
-                        .show_percentage()
-                        .animate(true));
+                    // Not animated, that would repaint continuously
+                    let progress_bar = ui.add(egui::ProgressBar::new(self.progress).show_percentage());
+                    progress_bar.widget_info(|| {
+                        egui::WidgetInfo::labeled(egui::WidgetType::Other, progress_label(self.progress))
+                    });
                         
-                    if let Some(speed) = self.download_speed {
-                        ui.label(format!("Download speed: {:.2} KB/s", speed));
+                    if let Some((speed, eta)) = self.download_speed {
+                        ui.label(download_speed_label(speed, eta));
+                    }
+
+                    self.show_changelog(ui);
+
+                    if let Some(log) = &self.log {
+                        log_view::show(ui, log);
                     }
                 }
             });
+            content.response.rect.bottom()
         });
 
-        // Request a repaint
-        ctx.request_repaint();
+        if focus_actions {
+            self.actions_focused = true;
+        }
+
+        match settings_action {
+            settings::SettingsAction::None => self.settings_panel = settings_panel,
+            settings::SettingsAction::PreviewTheme(mode) => {
+                self.apply_theme_preview(ctx, frame, mode);
+                self.settings_panel = settings_panel;
+            },
+            settings::SettingsAction::Saved(settings) => {
+                if let Some(mode) = settings.theme {
+                    self.theme = mode;
+                }
+                self.settings = settings;
+                self.apply_theme_preview(ctx, frame, self.theme);
+            },
+            settings::SettingsAction::Cancelled => self.apply_theme_preview(ctx, frame, self.theme),
+        }
+
+        // Fit the window to its content, e.g. a long error message or the expanded changelog
+        let window_height = self.layout.fit_height(panel.inner);
+        if !self.gaming_mode && (window_height - self.window_height).abs() >= 1.0 {
+            self.window_height = window_height;
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(self.layout.width, window_height)));
+        }
+
+        // Messages from the runner logic wake the UI through the relay, the timer only refreshes
+        // time-based content such as the update countdown and tray menu events
+        if self.splash.is_some() {
+            // The splash is closed on the frame after the first one
+            ctx.request_repaint();
+        } else {
+            ctx.request_repaint_after(IDLE_REPAINT_INTERVAL);
+        }
     }
 }
 
@@ -102,4 +775,50 @@ mod tests {
         assert!(matches!(rx.recv().unwrap(), UiMessage::SetProgress(0.5)));
         assert!(matches!(rx.recv().unwrap(), UiMessage::SetStatus(s) if s == "Testing"));
     }
+
+    #[test]
+    fn test_progress_label() {
+        assert_eq!(progress_label(0.456), "Progress 46%");
+        assert_eq!(progress_label(2.0), "Progress 100%");
+    }
+
+    #[test]
+    fn test_version_info_label() {
+        assert_eq!(version_info_label(Some("My Game"), "42"), "Installing My Game — version 42");
+        assert_eq!(version_info_label(None, "42"), "Installing version 42");
+    }
+
+    #[test]
+    fn test_download_speed_label() {
+        assert_eq!(
+            download_speed_label(12.3 * 1024.0, Some(Duration::from_secs(45))),
+            "Download speed: 12.30 MB/s, 45s remaining"
+        );
+        assert_eq!(download_speed_label(100.0, None), "Download speed: 100.00 KB/s");
+    }
+
+    #[test]
+    fn test_stalled_label() {
+        assert_eq!(
+            stalled_label(Duration::from_secs(12)),
+            "Connection appears stalled — retrying... (12s)"
+        );
+    }
+
+    #[test]
+    fn test_update_prompt_label() {
+        assert_eq!(
+            update_prompt_label("43", Duration::from_millis(9200)),
+            "Version 43 is available. Updating in 10s."
+        );
+        assert_eq!(update_prompt_label("43", Duration::ZERO), "Version 43 is available. Updating in 0s.");
+    }
+
+    #[test]
+    fn test_phase_order() {
+        assert_eq!(Phase::ALL.first(), Some(&Phase::Connect));
+        assert_eq!(Phase::ALL.last(), Some(&Phase::Launch));
+        assert!(Phase::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Phase::AppInfo.label(), "App info");
+    }
 } 
\ No newline at end of file
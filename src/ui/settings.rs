@@ -0,0 +1,169 @@
+use crate::config::settings::LANGUAGES;
+use crate::config::{Settings, ThemeMode};
+use crate::file::FileManager;
+use eframe::egui::{self, RichText};
+use log::{info, warn};
+use std::path::PathBuf;
+
+pub enum SettingsAction {
+    None,
+    /// Preview a theme change before it's saved.
+    PreviewTheme(ThemeMode),
+    Saved(Settings),
+    Cancelled,
+}
+
+/// Editable copy of the settings shown in place of the progress view.
+pub struct SettingsPanel {
+    draft: Settings,
+    default_theme: ThemeMode,
+    install_dir: String,
+    bandwidth_limit_kbps: u64,
+    path: PathBuf,
+    cache_dir: Option<PathBuf>,
+    message: Option<String>,
+}
+
+impl SettingsPanel {
+    /// `default_theme` is shown while the settings don't override the branding theme.
+    pub fn new(settings: &Settings, default_theme: ThemeMode, path: PathBuf, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            draft: settings.clone(),
+            default_theme,
+            install_dir: settings
+                .install_dir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            bandwidth_limit_kbps: settings.bandwidth_limit_kbps.unwrap_or(0),
+            path,
+            cache_dir,
+            message: None,
+        }
+    }
+
+    /// Draws the panel. `busy` disables actions that would interfere with a running update.
+    pub fn show(&mut self, ui: &mut egui::Ui, busy: bool) -> SettingsAction {
+        let mut action = SettingsAction::None;
+
+        egui::Grid::new("settings_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Install directory");
+            ui.add(egui::TextEdit::singleline(&mut self.install_dir).hint_text("Default"));
+            ui.end_row();
+
+            ui.label("Bandwidth limit");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.bandwidth_limit_kbps).speed(16.0).suffix(" KB/s"));
+                if self.bandwidth_limit_kbps == 0 {
+                    ui.label(RichText::new("unlimited").weak());
+                }
+            });
+            ui.end_row();
+
+            ui.label("Language");
+            let selected = self
+                .draft
+                .language
+                .as_deref()
+                .and_then(|code| LANGUAGES.iter().find(|(c, _)| *c == code))
+                .map_or("System default", |(_, name)| *name);
+            egui::ComboBox::from_id_source("settings_language")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.draft.language, None, "System default");
+                    for (code, name) in LANGUAGES {
+                        ui.selectable_value(&mut self.draft.language, Some(code.to_string()), *name);
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Theme");
+            ui.horizontal(|ui| {
+                let current = self.draft.theme.unwrap_or(self.default_theme);
+                let mut theme = current;
+                ui.radio_value(&mut theme, ThemeMode::Dark, "Dark");
+                ui.radio_value(&mut theme, ThemeMode::Light, "Light");
+                ui.radio_value(&mut theme, ThemeMode::Auto, "System");
+                if theme != current {
+                    self.draft.theme = Some(theme);
+                    action = SettingsAction::PreviewTheme(theme);
+                }
+            });
+            ui.end_row();
+
+            ui.label("Error reports");
+            ui.checkbox(&mut self.draft.send_error_reports, "Send to the developer")
+                .on_hover_text("Crash and error details, if the developer collects them");
+            ui.end_row();
+
+            ui.label("Error statistics");
+            let mut share = self.draft.share_error_statistics.unwrap_or(true);
+            if ui.checkbox(&mut share, "Share anonymously with PatchKit")
+                .on_hover_text("Only the error code, update step, OS and runner version")
+                .changed()
+            {
+                self.draft.share_error_statistics = Some(share);
+            }
+            ui.end_row();
+
+            ui.label("Download statistics");
+            ui.checkbox(&mut self.draft.share_download_statistics, "Share with PatchKit")
+                .on_hover_text("Download speed per server, so the fastest servers are used next time");
+            ui.end_row();
+        });
+
+        ui.add_space(6.0);
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                action = self.save();
+            }
+            if ui.button("Cancel").clicked() {
+                action = SettingsAction::Cancelled;
+            }
+            let delete = ui
+                .add_enabled(!busy && self.cache_dir.is_some(), egui::Button::new("Delete cache"))
+                .on_disabled_hover_text("Not available while an update is in progress");
+            if delete.clicked() {
+                self.delete_cache();
+            }
+        });
+
+        if let Some(message) = &self.message {
+            ui.label(RichText::new(message).small());
+        }
+
+        action
+    }
+
+    fn save(&mut self) -> SettingsAction {
+        let install_dir = self.install_dir.trim();
+        self.draft.install_dir = (!install_dir.is_empty()).then(|| PathBuf::from(install_dir));
+        self.draft.bandwidth_limit_kbps = (self.bandwidth_limit_kbps > 0).then_some(self.bandwidth_limit_kbps);
+
+        match self.draft.save(&self.path) {
+            Ok(()) => {
+                info!("Settings saved to {}", self.path.display());
+                SettingsAction::Saved(self.draft.clone())
+            },
+            Err(e) => {
+                warn!("Failed to save settings: {}", e);
+                self.message = Some(format!("Failed to save settings: {}", e));
+                SettingsAction::None
+            },
+        }
+    }
+
+    fn delete_cache(&mut self) {
+        let Some(cache_dir) = &self.cache_dir else {
+            return;
+        };
+
+        self.message = Some(match FileManager::clear_cache(cache_dir) {
+            Ok(()) => "Cache deleted. The launcher will be downloaded again on next start.".into(),
+            Err(e) => {
+                warn!("Failed to delete cache: {}", e);
+                format!("Failed to delete cache: {}", e)
+            },
+        });
+    }
+}
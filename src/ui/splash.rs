@@ -0,0 +1,154 @@
+//! Lightweight splash shown while eframe creates the window and its graphics context,
+//! which can take several seconds on slow machines.
+
+/// Native splash window. It's closed when dropped, i.e. once the first egui frame is painted.
+pub struct Splash {
+    #[cfg(windows)]
+    _window: native::SplashWindow,
+}
+
+impl Splash {
+    /// Shows the splash. Only Windows has a native splash; elsewhere the window appears quickly
+    /// enough and this returns `None`.
+    pub fn show(title: &str, width: i32, height: i32) -> Option<Self> {
+        #[cfg(windows)]
+        {
+            native::SplashWindow::show(title, width, height).map(|window| Self { _window: window })
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = (title, width, height);
+            None
+        }
+    }
+}
+
+#[cfg(windows)]
+mod native {
+    use log::{debug, warn};
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::{null, null_mut};
+    use std::sync::mpsc::channel;
+    use std::thread::JoinHandle;
+    use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+    use winapi::shared::windef::{HBRUSH, HWND, RECT};
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::winuser::{
+        BeginPaint, CreateWindowExW, DefWindowProcW, DispatchMessageW, DrawTextW, EndPaint,
+        GetClientRect, GetMessageW, GetSystemMetrics, GetWindowTextW, LoadCursorW, PostMessageW,
+        PostQuitMessage, RegisterClassW, TranslateMessage, COLOR_WINDOW, DT_CENTER,
+        DT_SINGLELINE, DT_VCENTER, IDC_APPSTARTING, MSG, PAINTSTRUCT, SM_CXSCREEN, SM_CYSCREEN,
+        WM_CLOSE, WM_DESTROY, WM_PAINT, WNDCLASSW, WS_BORDER, WS_EX_TOOLWINDOW, WS_POPUP,
+        WS_VISIBLE,
+    };
+
+    const CLASS_NAME: &str = "PatchKitRunnerSplash";
+
+    /// Borderless window drawing the title, running its own message loop on a separate thread.
+    pub struct SplashWindow {
+        hwnd: usize,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl SplashWindow {
+        pub fn show(title: &str, width: i32, height: i32) -> Option<Self> {
+            let title = wide(title);
+            let (sender, receiver) = channel();
+
+            let thread = std::thread::spawn(move || unsafe {
+                let class_name = wide(CLASS_NAME);
+                let instance = GetModuleHandleW(null());
+                let class = WNDCLASSW {
+                    style: 0,
+                    lpfnWndProc: Some(window_proc),
+                    cbClsExtra: 0,
+                    cbWndExtra: 0,
+                    hInstance: instance,
+                    hIcon: null_mut(),
+                    hCursor: LoadCursorW(null_mut(), IDC_APPSTARTING),
+                    hbrBackground: (COLOR_WINDOW + 1) as HBRUSH,
+                    lpszMenuName: null(),
+                    lpszClassName: class_name.as_ptr(),
+                };
+                RegisterClassW(&class);
+
+                let x = (GetSystemMetrics(SM_CXSCREEN) - width) / 2;
+                let y = (GetSystemMetrics(SM_CYSCREEN) - height) / 2;
+                let hwnd = CreateWindowExW(
+                    WS_EX_TOOLWINDOW,
+                    class_name.as_ptr(),
+                    title.as_ptr(),
+                    WS_POPUP | WS_BORDER | WS_VISIBLE,
+                    x,
+                    y,
+                    width,
+                    height,
+                    null_mut(),
+                    null_mut(),
+                    instance,
+                    null_mut(),
+                );
+                let _ = sender.send(hwnd as usize);
+                if hwnd.is_null() {
+                    return;
+                }
+
+                let mut msg: MSG = std::mem::zeroed();
+                while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                }
+            });
+
+            match receiver.recv() {
+                Ok(hwnd) if hwnd != 0 => {
+                    debug!("Splash window shown");
+                    Some(Self { hwnd, thread: Some(thread) })
+                }
+                _ => {
+                    warn!("Failed to create splash window");
+                    let _ = thread.join();
+                    None
+                }
+            }
+        }
+    }
+
+    impl Drop for SplashWindow {
+        fn drop(&mut self) {
+            unsafe {
+                PostMessageW(self.hwnd as HWND, WM_CLOSE, 0, 0);
+            }
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+            debug!("Splash window closed");
+        }
+    }
+
+    unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        match msg {
+            WM_PAINT => {
+                let mut paint: PAINTSTRUCT = std::mem::zeroed();
+                let hdc = BeginPaint(hwnd, &mut paint);
+                let mut rect: RECT = std::mem::zeroed();
+                GetClientRect(hwnd, &mut rect);
+                let mut title = [0u16; 256];
+                let len = GetWindowTextW(hwnd, title.as_mut_ptr(), title.len() as i32);
+                DrawTextW(hdc, title.as_ptr(), len, &mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+                EndPaint(hwnd, &paint);
+                0
+            }
+            WM_DESTROY => {
+                PostQuitMessage(0);
+                0
+            }
+            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        }
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        OsStr::new(text).encode_wide().chain(Some(0)).collect()
+    }
+}
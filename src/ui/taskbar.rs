@@ -0,0 +1,246 @@
+//! Download progress on the taskbar button. Only Windows has one (ITaskbarList3); elsewhere
+//! the state is tracked but not shown.
+
+use super::UiMessage;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(windows)]
+use std::sync::Arc;
+
+/// What the taskbar button shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskbarState {
+    Idle,
+    Progress(f32),
+    Paused(f32),
+    Error,
+}
+
+impl TaskbarState {
+    /// State after `message` was sent by the runner logic.
+    pub fn next(self, message: &UiMessage) -> Self {
+        match message {
+            UiMessage::SetProgress(progress)
+            | UiMessage::SetDownloadProgress { progress, .. } => Self::Progress(*progress),
+            UiMessage::ShowError(_) => Self::Error,
+            UiMessage::Close => Self::Idle,
+            _ => self,
+        }
+    }
+
+    /// Shows running progress as paused while the download is paused.
+    pub fn with_pause(self, paused: bool) -> Self {
+        match self {
+            Self::Progress(progress) if paused => Self::Paused(progress),
+            Self::Paused(progress) if !paused => Self::Progress(progress),
+            state => state,
+        }
+    }
+}
+
+/// Native window handle, filled in by the UI once the window exists.
+#[derive(Clone, Default)]
+pub struct WindowSlot {
+    #[cfg(windows)]
+    hwnd: Arc<AtomicUsize>,
+}
+
+impl WindowSlot {
+    pub fn set(&self, frame: &eframe::Frame) {
+        #[cfg(windows)]
+        {
+            use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+            if let RawWindowHandle::Win32(handle) = frame.raw_window_handle() {
+                self.hwnd.store(handle.hwnd as usize, Ordering::Relaxed);
+            }
+        }
+        #[cfg(not(windows))]
+        let _ = frame;
+    }
+
+    #[cfg(windows)]
+    fn get(&self) -> Option<usize> {
+        match self.hwnd.load(Ordering::Relaxed) {
+            0 => None,
+            hwnd => Some(hwnd),
+        }
+    }
+}
+
+/// Taskbar button of the runner window. It's driven from the message relay rather than the UI,
+/// because a minimized window isn't repainted.
+pub struct Taskbar {
+    state: TaskbarState,
+    #[cfg(windows)]
+    window: WindowSlot,
+    #[cfg(windows)]
+    list: Option<native::TaskbarList>,
+}
+
+impl Taskbar {
+    pub fn new(window: WindowSlot) -> Self {
+        #[cfg(not(windows))]
+        let _ = window;
+        Self {
+            state: TaskbarState::Idle,
+            #[cfg(windows)]
+            window,
+            #[cfg(windows)]
+            list: None,
+        }
+    }
+
+    pub fn state(&self) -> TaskbarState {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: TaskbarState) {
+        #[cfg(windows)]
+        {
+            // The window may not exist yet when the first messages arrive
+            let connected = self.list.is_none() && self.connect();
+            if connected || state != self.state {
+                if let Some(list) = &self.list {
+                    list.set_state(state);
+                }
+            }
+        }
+        self.state = state;
+    }
+
+    #[cfg(windows)]
+    fn connect(&mut self) -> bool {
+        if let Some(hwnd) = self.window.get() {
+            self.list = native::TaskbarList::new(hwnd);
+        }
+        self.list.is_some()
+    }
+
+    /// Brings a minimized window back so that it can process a close request.
+    pub fn restore_window(&self) {
+        #[cfg(windows)]
+        if let Some(list) = &self.list {
+            list.restore_window();
+        }
+    }
+}
+
+#[cfg(windows)]
+mod native {
+    use super::TaskbarState;
+    use log::warn;
+    use std::ptr::null_mut;
+    use winapi::shared::windef::HWND;
+    use winapi::shared::winerror::SUCCEEDED;
+    use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+    use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx, CoUninitialize};
+    use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+    use winapi::um::shobjidl_core::{
+        CLSID_TaskbarList, ITaskbarList3, TBPF_ERROR, TBPF_NOPROGRESS, TBPF_NORMAL, TBPF_PAUSED,
+    };
+    use winapi::um::winuser::{IsIconic, ShowWindow, SW_RESTORE};
+    use winapi::Interface;
+
+    const PROGRESS_TOTAL: u64 = 1000;
+
+    pub struct TaskbarList {
+        list: *mut ITaskbarList3,
+        hwnd: HWND,
+    }
+
+    impl TaskbarList {
+        pub fn new(hwnd: usize) -> Option<Self> {
+            unsafe {
+                CoInitializeEx(null_mut(), COINIT_APARTMENTTHREADED);
+                let mut list: *mut ITaskbarList3 = null_mut();
+                let result = CoCreateInstance(
+                    &CLSID_TaskbarList,
+                    null_mut(),
+                    CLSCTX_INPROC_SERVER,
+                    &ITaskbarList3::uuidof(),
+                    &mut list as *mut *mut ITaskbarList3 as *mut _,
+                );
+                if !SUCCEEDED(result) || list.is_null() {
+                    warn!("Failed to create taskbar list: {:#x}", result);
+                    CoUninitialize();
+                    return None;
+                }
+                if !SUCCEEDED((*list).HrInit()) {
+                    warn!("Failed to initialize taskbar list");
+                    (*list).Release();
+                    CoUninitialize();
+                    return None;
+                }
+                Some(Self { list, hwnd: hwnd as HWND })
+            }
+        }
+
+        pub fn set_state(&self, state: TaskbarState) {
+            unsafe {
+                let list = &*self.list;
+                match state {
+                    TaskbarState::Idle => {
+                        list.SetProgressState(self.hwnd, TBPF_NOPROGRESS);
+                    }
+                    TaskbarState::Progress(progress) => {
+                        list.SetProgressState(self.hwnd, TBPF_NORMAL);
+                        list.SetProgressValue(self.hwnd, to_units(progress), PROGRESS_TOTAL);
+                    }
+                    TaskbarState::Paused(progress) => {
+                        list.SetProgressState(self.hwnd, TBPF_PAUSED);
+                        list.SetProgressValue(self.hwnd, to_units(progress), PROGRESS_TOTAL);
+                    }
+                    TaskbarState::Error => {
+                        list.SetProgressState(self.hwnd, TBPF_ERROR);
+                        list.SetProgressValue(self.hwnd, PROGRESS_TOTAL, PROGRESS_TOTAL);
+                    }
+                }
+            }
+        }
+
+        pub fn restore_window(&self) {
+            unsafe {
+                if IsIconic(self.hwnd) != 0 {
+                    ShowWindow(self.hwnd, SW_RESTORE);
+                }
+            }
+        }
+    }
+
+    impl Drop for TaskbarList {
+        fn drop(&mut self) {
+            unsafe {
+                (*self.list).Release();
+                CoUninitialize();
+            }
+        }
+    }
+
+    fn to_units(progress: f32) -> u64 {
+        (progress.clamp(0.0, 1.0) * PROGRESS_TOTAL as f32) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::error_catalog::DisplayError;
+
+    #[test]
+    fn test_state_follows_messages() {
+        let state = TaskbarState::Idle
+            .next(&UiMessage::SetStatus("Downloading...".into()))
+            .next(&UiMessage::SetDownloadProgress { progress: 0.25, speed_kbps: 100.0, eta: None });
+        assert_eq!(state, TaskbarState::Progress(0.25));
+        assert_eq!(state.next(&UiMessage::ShowError(DisplayError::plain("Failed"))), TaskbarState::Error);
+        assert_eq!(state.next(&UiMessage::Close), TaskbarState::Idle);
+    }
+
+    #[test]
+    fn test_pause() {
+        let paused = TaskbarState::Progress(0.5).with_pause(true);
+        assert_eq!(paused, TaskbarState::Paused(0.5));
+        assert_eq!(paused.with_pause(false), TaskbarState::Progress(0.5));
+        assert_eq!(TaskbarState::Error.with_pause(true), TaskbarState::Error);
+    }
+}
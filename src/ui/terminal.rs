@@ -0,0 +1,202 @@
+use super::{Phase, UiMessage};
+use crate::format::{format_duration, format_speed};
+use crate::runner::progress::ProgressSink;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 30;
+
+/// Renders runner progress as a single-line progress bar in a terminal.
+pub struct TerminalProgress<W: Write> {
+    out: W,
+    download_started: Option<Instant>,
+    bar_visible: bool,
+    last_progress: f32,
+}
+
+impl<W: Write> TerminalProgress<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            download_started: None,
+            bar_visible: false,
+            last_progress: 0.0,
+        }
+    }
+
+    pub fn handle(&mut self, message: &UiMessage) {
+        // Terminal output is best-effort, a closed pipe must not abort the update
+        let _ = self.try_handle(message);
+    }
+
+    fn try_handle(&mut self, message: &UiMessage) -> std::io::Result<()> {
+        match message {
+            UiMessage::SetStatus(status) => self.print_line(status)?,
+            UiMessage::SetChangelog(changelog) => self.print_line(&format!("What's new:\n{}", changelog))?,
+            UiMessage::SetNews(news) => self.print_line(&format!("News: {}", news))?,
+            UiMessage::SetVersionInfo { app_name, version } => {
+                self.print_line(&super::version_info_label(app_name.as_deref(), version))?
+            },
+            UiMessage::ShowWarning(warning) => self.print_line(&format!("Warning: {}", warning))?,
+            UiMessage::ShowError(error) => self.print_line(&format!("Error: {}", error))?,
+            UiMessage::SetProgress(progress) => self.render_bar(*progress, None)?,
+            UiMessage::SetDownloadProgress { progress, speed_kbps, eta } => {
+                let started = *self.download_started.get_or_insert_with(Instant::now);
+                let eta = eta.or_else(|| estimate_remaining(started.elapsed(), *progress));
+                self.render_bar(*progress, Some((*speed_kbps, eta)))?;
+            },
+            UiMessage::SetStalled(stalled_for) => {
+                let line = format!("{} {}", format_bar(self.last_progress), super::stalled_label(*stalled_for));
+                self.render_line(&line)?;
+            },
+            // Nobody can answer in the terminal, dropping the reply updates right away
+            UiMessage::SetPhase(_) | UiMessage::AskUpdate { .. } => {},
+            // The pipeline doesn't ask the terminal, `--app` chooses there
+            UiMessage::AskApp { .. } => {},
+            UiMessage::Close => self.finish_bar()?,
+        }
+        self.out.flush()
+    }
+
+    fn print_line(&mut self, text: &str) -> std::io::Result<()> {
+        self.finish_bar()?;
+        writeln!(self.out, "{}", text)
+    }
+
+    fn finish_bar(&mut self) -> std::io::Result<()> {
+        if self.bar_visible {
+            self.bar_visible = false;
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    fn render_bar(&mut self, progress: f32, download: Option<(f64, Option<Duration>)>) -> std::io::Result<()> {
+        let mut line = format_bar(progress);
+        if let Some((speed_kbps, eta)) = download {
+            line.push_str(&format!(" {}", format_speed(speed_kbps)));
+            if let Some(eta) = eta {
+                line.push_str(&format!(", {} remaining", format_duration(eta)));
+            }
+        }
+        self.last_progress = progress;
+        self.render_line(&line)
+    }
+
+    fn render_line(&mut self, line: &str) -> std::io::Result<()> {
+        self.bar_visible = true;
+        // Clear to the end of the line so a shorter update doesn't leave stale characters
+        write!(self.out, "\r{}\x1b[K", line)
+    }
+}
+
+/// Prints the pipeline's progress, shared with its download task.
+impl<W: Write + Send + 'static> ProgressSink for Mutex<TerminalProgress<W>> {
+    fn phase(&self, _phase: Phase) {}
+
+    fn message(&self, message: String) {
+        print(self, UiMessage::SetStatus(message));
+    }
+
+    fn percentage(&self, percentage: f32) {
+        print(self, UiMessage::SetProgress(percentage));
+    }
+
+    fn speed(&self, percentage: f32, speed_kbps: f64, eta: Option<Duration>) {
+        print(self, UiMessage::SetDownloadProgress { progress: percentage, speed_kbps, eta });
+    }
+
+    fn stalled(&self, stalled_for: Duration) {
+        print(self, UiMessage::SetStalled(stalled_for));
+    }
+
+    fn warning(&self, warning: String) {
+        print(self, UiMessage::ShowWarning(warning));
+    }
+
+    fn news(&self, news: String) {
+        print(self, UiMessage::SetNews(news));
+    }
+
+    fn changelog(&self, changelog: String) {
+        print(self, UiMessage::SetChangelog(changelog));
+    }
+
+    fn version_info(&self, app_name: Option<String>, version: String) {
+        print(self, UiMessage::SetVersionInfo { app_name, version });
+    }
+
+    // Nobody can answer in the terminal, so the update goes ahead right away
+
+    fn finished(&self) {
+        print(self, UiMessage::Close);
+    }
+}
+
+fn print<W: Write>(progress: &Mutex<TerminalProgress<W>>, message: UiMessage) {
+    if let Ok(mut progress) = progress.lock() {
+        progress.handle(&message);
+    }
+}
+
+fn format_bar(progress: f32) -> String {
+    let progress = progress.clamp(0.0, 1.0);
+    let filled = (progress * BAR_WIDTH as f32).round() as usize;
+    format!(
+        "[{}{}] {:>3.0}%",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        progress * 100.0
+    )
+}
+
+/// Extrapolates the remaining time from the fraction completed so far.
+fn estimate_remaining(elapsed: Duration, progress: f32) -> Option<Duration> {
+    if progress <= 0.0 || progress >= 1.0 {
+        return None;
+    }
+    let total = elapsed.as_secs_f64() / progress as f64;
+    Some(Duration::from_secs_f64(total - elapsed.as_secs_f64()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bar() {
+        assert_eq!(format_bar(0.0), format!("[{}]   0%", "-".repeat(BAR_WIDTH)));
+        assert_eq!(format_bar(0.5), format!("[{}{}]  50%", "#".repeat(15), "-".repeat(15)));
+        assert_eq!(format_bar(1.5), format!("[{}] 100%", "#".repeat(BAR_WIDTH)));
+    }
+
+    #[test]
+    fn test_estimate_remaining() {
+        assert_eq!(estimate_remaining(Duration::from_secs(10), 0.25), Some(Duration::from_secs(30)));
+        assert_eq!(estimate_remaining(Duration::from_secs(10), 0.0), None);
+        assert_eq!(estimate_remaining(Duration::from_secs(10), 1.0), None);
+    }
+
+    #[test]
+    fn test_status_ends_progress_line() {
+        let mut progress = TerminalProgress::new(Vec::new());
+        progress.handle(&UiMessage::SetProgress(0.5));
+        progress.handle(&UiMessage::SetStatus("Extracting launcher...".into()));
+
+        let output = String::from_utf8(progress.out).unwrap();
+        assert!(output.starts_with('\r'));
+        assert!(output.ends_with("\nExtracting launcher...\n"));
+    }
+
+    #[test]
+    fn test_stall_keeps_progress_line() {
+        let mut progress = TerminalProgress::new(Vec::new());
+        progress.handle(&UiMessage::SetDownloadProgress { progress: 0.5, speed_kbps: 100.0, eta: None });
+        progress.handle(&UiMessage::SetStalled(Duration::from_secs(6)));
+
+        let output = String::from_utf8(progress.out).unwrap();
+        assert!(!output.contains('\n'));
+        assert!(output.contains(&format!("{} Connection appears stalled", format_bar(0.5))));
+    }
+}
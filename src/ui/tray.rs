@@ -0,0 +1,90 @@
+use crate::network::DownloadControl;
+use eframe::egui;
+use log::warn;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::{BadIcon, Icon, TrayIcon, TrayIconBuilder};
+
+const ICON_SIZE: u32 = 16;
+const ICON_COLOR: [u8; 4] = [0x2d, 0x8c, 0xf0, 0xff];
+
+/// Tray icon showing the download percentage, with Pause/Cancel/Show window actions.
+pub struct Tray {
+    icon: TrayIcon,
+    pause: MenuItem,
+    cancel: MenuItem,
+    show: MenuItem,
+    percentage: Option<u32>,
+}
+
+impl Tray {
+    /// Creates the tray icon. Failing to do so is not fatal, the window keeps working without it.
+    pub fn new() -> Option<Self> {
+        match Self::build() {
+            Ok(tray) => Some(tray),
+            Err(e) => {
+                warn!("Failed to create tray icon: {}", e);
+                None
+            }
+        }
+    }
+
+    fn build() -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let pause = MenuItem::new("Pause", true, None);
+        let cancel = MenuItem::new("Cancel", true, None);
+        let show = MenuItem::new("Show window", true, None);
+
+        let menu = Menu::new();
+        menu.append_items(&[&pause, &cancel, &show])?;
+
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("PatchKit Runner")
+            .with_icon(default_icon()?)
+            .build()?;
+
+        Ok(Self {
+            icon,
+            pause,
+            cancel,
+            show,
+            percentage: None,
+        })
+    }
+
+    /// Handles pending menu clicks and refreshes the tooltip with the current progress.
+    pub fn update(&mut self, ctx: &egui::Context, progress: f32, control: &DownloadControl) {
+        while let Ok(event) = MenuEvent::receiver().try_recv() {
+            if event.id == *self.pause.id() {
+                if control.is_paused() {
+                    control.resume();
+                    self.pause.set_text("Pause");
+                } else {
+                    control.pause();
+                    self.pause.set_text("Resume");
+                }
+            } else if event.id == *self.cancel.id() {
+                control.cancel();
+                self.pause.set_enabled(false);
+                self.cancel.set_enabled(false);
+            } else if event.id == *self.show.id() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            }
+        }
+
+        let percentage = (progress.clamp(0.0, 1.0) * 100.0).round() as u32;
+        if self.percentage != Some(percentage) {
+            self.percentage = Some(percentage);
+            let tooltip = format!("PatchKit Runner - {}%", percentage);
+            if let Err(e) = self.icon.set_tooltip(Some(tooltip)) {
+                warn!("Failed to update tray tooltip: {}", e);
+            }
+        }
+    }
+}
+
+fn default_icon() -> std::result::Result<Icon, BadIcon> {
+    let rgba = ICON_COLOR.repeat((ICON_SIZE * ICON_SIZE) as usize);
+    Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)
+}
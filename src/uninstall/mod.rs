@@ -0,0 +1,195 @@
+//! The app's entry in Windows' installed apps list (Settings → Apps), and the `--uninstall` mode
+//! the entry runs. Other platforms have no such list, so only the removal of the files applies.
+
+use crate::cli::UNINSTALL_FLAG;
+use crate::config::paths::{InstallScope, PathProvider};
+use crate::config::LauncherData;
+use crate::file::{FileManager, CREATED_MARKER};
+use crate::runner::app_slug;
+use crate::system::registry::{self, RegistryValue};
+use crate::Result;
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the entries are, under HKEY_CURRENT_USER so no elevation is needed.
+const UNINSTALL_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Uninstall";
+
+/// Name shown when the dat doesn't have the app's name.
+const DEFAULT_DISPLAY_NAME: &str = "PatchKit App";
+
+/// An app's entry in the installed apps list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UninstallEntry {
+    pub app_slug: String,
+    pub display_name: String,
+    pub publisher: Option<String>,
+    pub version: String,
+    pub install_location: PathBuf,
+    /// The runner, whose icon is shown and which is run to uninstall.
+    pub runner_path: PathBuf,
+}
+
+impl UninstallEntry {
+    pub fn new(launcher_data: &LauncherData, version: &str, install_location: &Path, runner_path: &Path) -> Self {
+        Self {
+            app_slug: app_slug(launcher_data).to_string(),
+            display_name: launcher_data.app_display_name.clone().unwrap_or_else(|| DEFAULT_DISPLAY_NAME.into()),
+            publisher: launcher_data.app_author.clone(),
+            version: version.to_string(),
+            install_location: install_location.to_path_buf(),
+            runner_path: runner_path.to_path_buf(),
+        }
+    }
+
+    pub fn uninstall_command(&self) -> String {
+        format!("\"{}\" {}", self.runner_path.display(), UNINSTALL_FLAG)
+    }
+
+    /// Values of the entry's registry key.
+    pub fn values(&self) -> Vec<(&'static str, RegistryValue)> {
+        let mut values = vec![
+            ("DisplayName", RegistryValue::Text(self.display_name.clone())),
+            ("DisplayIcon", RegistryValue::Text(format!("{},0", self.runner_path.display()))),
+            ("DisplayVersion", RegistryValue::Text(self.version.clone())),
+            ("InstallLocation", RegistryValue::Text(self.install_location.display().to_string())),
+            ("UninstallString", RegistryValue::Text(self.uninstall_command())),
+            // The runner can only remove the app, not change or repair it from the list
+            ("NoModify", RegistryValue::Number(1)),
+            ("NoRepair", RegistryValue::Number(1)),
+        ];
+        if let Some(publisher) = &self.publisher {
+            values.push(("Publisher", RegistryValue::Text(publisher.clone())));
+        }
+        values
+    }
+
+    /// Adds the entry to the list, replacing the app's previous one.
+    pub fn register(&self) -> Result<()> {
+        info!("Registering {} {} in the installed apps list", self.display_name, self.version);
+        registry::write_key(&key_path(&self.app_slug), &self.values())
+    }
+}
+
+fn key_path(app_slug: &str) -> String {
+    format!(r"{}\PatchKit-{}", UNINSTALL_KEY, app_slug)
+}
+
+/// Removes the installed app: the app's files, the launcher and the installed apps entry. The
+/// runner itself, its dat and settings stay, as a running program can't delete itself.
+///
+/// Directories the runner didn't create, e.g. an `install_dir` from the settings pointing at a
+/// games folder, may hold other data. Only the launcher's installed files are removed from them,
+/// and the directory itself if that leaves it empty.
+pub fn uninstall(file_manager: &FileManager, app_slug: &str) -> Result<()> {
+    let runner_path = std::env::current_exe()?;
+    file_manager.remove_old_files()?;
+    for dir in [file_manager.get_install_dir(), file_manager.patcher_dir()] {
+        remove_dir(dir, &runner_path, is_runner_dir(dir, app_slug))?;
+    }
+    registry::delete_key(&key_path(app_slug))?;
+    info!("Uninstalled");
+    Ok(())
+}
+
+/// Whether everything in `dir` is the app's: it carries the [`CREATED_MARKER`], or it's one of
+/// the app's default directories, which runners before the marker created without one.
+fn is_runner_dir(dir: &Path, app_slug: &str) -> bool {
+    if dir.join(CREATED_MARKER).exists() {
+        return true;
+    }
+    let Ok(paths) = PathProvider::current() else {
+        return false;
+    };
+    [InstallScope::Portable, InstallScope::User]
+        .into_iter()
+        .flat_map(|scope| [paths.data_dir(scope, app_slug), paths.patcher_dir(scope, app_slug)])
+        .any(|default| default.is_ok_and(|default| default == dir))
+}
+
+/// Removes `dir` with everything in it if the runner `created` it, otherwise only if it's empty.
+fn remove_dir(dir: &Path, runner_path: &Path, created: bool) -> Result<()> {
+    if !dir.exists() {
+        debug!("Nothing to remove at {}", dir.display());
+        return Ok(());
+    }
+    if !created {
+        if fs::read_dir(dir)?.next().is_none() {
+            fs::remove_dir(dir)?;
+        } else {
+            warn!("Not removing the rest of {}, the runner didn't create it", dir.display());
+        }
+        return Ok(());
+    }
+    // An install directory set to a drive or the runner's own directory isn't the app's alone
+    if dir.parent().is_none() || runner_path.starts_with(dir) {
+        warn!("Not removing {}, it holds more than the app", dir.display());
+        return Ok(());
+    }
+    info!("Removing {}", dir.display());
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_entry_values() {
+        let mut launcher_data: LauncherData = serde_json::from_str(
+            r#"{"patcher_secret": "patchersecret", "app_secret": "appsecret123", "app_author": "Studio"}"#,
+        )
+        .unwrap();
+        let entry = UninstallEntry::new(&launcher_data, "42", Path::new("games/app"), Path::new("games/runner.exe"));
+        assert_eq!(entry.display_name, DEFAULT_DISPLAY_NAME);
+        assert_eq!(key_path(&entry.app_slug), format!(r"{}\PatchKit-appsecre", UNINSTALL_KEY));
+
+        let values = entry.values();
+        let value = |name: &str| values.iter().find(|(key, _)| *key == name).map(|(_, value)| value.clone());
+        let runner = Path::new("games/runner.exe").display();
+        assert_eq!(value("UninstallString"), Some(RegistryValue::Text(format!("\"{}\" --uninstall", runner))));
+        assert_eq!(value("DisplayIcon"), Some(RegistryValue::Text(format!("{},0", runner))));
+        assert_eq!(value("DisplayVersion"), Some(RegistryValue::Text("42".into())));
+        assert_eq!(value("Publisher"), Some(RegistryValue::Text("Studio".into())));
+
+        launcher_data.app_author = None;
+        let entry = UninstallEntry::new(&launcher_data, "42", Path::new("app"), Path::new("runner.exe"));
+        assert!(!entry.values().iter().any(|(name, _)| *name == "Publisher"));
+    }
+
+    #[test]
+    fn test_remove_dir_keeps_the_runner() {
+        let dir = tempdir().unwrap();
+        let app_dir = dir.path().join("app");
+        fs::create_dir_all(app_dir.join("data")).unwrap();
+        let runner_path = dir.path().join("runner2");
+
+        remove_dir(dir.path(), &runner_path, true).unwrap();
+        assert!(app_dir.exists());
+        remove_dir(&app_dir, &runner_path, true).unwrap();
+        assert!(!app_dir.exists());
+        // Already gone is fine
+        remove_dir(&app_dir, &runner_path, true).unwrap();
+    }
+
+    #[test]
+    fn test_uninstall_keeps_foreign_files() {
+        let dir = tempdir().unwrap();
+        let games_dir = dir.path().join("Games");
+        let patcher_dir = dir.path().join("Patcher");
+        fs::create_dir_all(&games_dir).unwrap();
+        fs::write(games_dir.join("other game.sav"), "progress").unwrap();
+        let file_manager = || FileManager::new("test123").unwrap().with_install_dir(games_dir.clone()).with_patcher_dir(patcher_dir.clone());
+        file_manager().check_writable().unwrap();
+        fs::create_dir_all(patcher_dir.join("bin")).unwrap();
+        fs::write(patcher_dir.join("bin/launcher"), "launcher").unwrap();
+        fs::write(patcher_dir.join("installed_files.txt"), "bin\nbin/launcher\n").unwrap();
+
+        uninstall(&file_manager(), "test123").unwrap();
+        // The install directory held other data before the runner used it
+        assert_eq!(fs::read_to_string(games_dir.join("other game.sav")).unwrap(), "progress");
+        assert!(!patcher_dir.exists());
+    }
+}
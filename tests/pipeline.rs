@@ -0,0 +1,179 @@
+//! Runs the whole update pipeline against a local HTTP server that fakes the PatchKit API and
+//! serves a fixture package, installing into a temp directory.
+#![cfg(unix)]
+
+use runner2::config::Settings;
+use runner2::runner::progress::NoProgress;
+use runner2::runner::{Runner, RunnerConfig};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Cursor, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+const APP_SECRET: &str = "appsecret123";
+const PATCHER_SECRET: &str = "patchersecret";
+const VERSION: &str = "2";
+
+const MANIFEST: &str = r#"{
+    "manifest_version": 4,
+    "target": "/bin/sh",
+    "target_arguments": [
+        { "value": ["{exedir}/launch.sh"] },
+        { "value": ["--installdir", "{installdir}"] },
+        { "value": ["--secret", "{secret}"] }
+    ],
+    "capabilities": []
+}"#;
+
+/// Stands in for the launcher, writing the arguments it was started with next to itself.
+const LAUNCH_SCRIPT: &str = "printf '%s\\n' \"$@\" > \"$(dirname \"$0\")/launched.txt\"\n";
+
+/// Responses by request path, as (status, body).
+type Routes = HashMap<String, (u16, Vec<u8>)>;
+
+struct FakeServer {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeServer {
+    fn start(routes: impl FnOnce(&str) -> Routes) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes = Arc::new(routes(&url));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let log = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = routes.clone();
+                let log = log.clone();
+                std::thread::spawn(move || respond(stream, &routes, &log));
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn requests_to(&self, path: &str) -> usize {
+        self.requests.lock().unwrap().iter().filter(|request| *request == path).count()
+    }
+}
+
+fn respond(mut stream: TcpStream, routes: &Routes, log: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    // Headers aren't needed, only read past them
+    let mut header = String::new();
+    while reader.read_line(&mut header).unwrap() > 2 {
+        header.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default().to_string();
+    log.lock().unwrap().push(path.clone());
+    let (status, body) = routes.get(&path).cloned().unwrap_or((404, b"{}".to_vec()));
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        if status == 200 { "OK" } else { "Not Found" },
+        body.len()
+    )
+    .unwrap();
+    stream.write_all(&body).unwrap();
+}
+
+fn api_routes(url: &str, package: Vec<u8>) -> Routes {
+    let json = |status: u16, body: String| (status, body.into_bytes());
+    let versions = format!("/1/apps/{}/versions", PATCHER_SECRET);
+    HashMap::from([
+        ("/network-test".to_string(), json(200, "ok".into())),
+        (
+            format!("/1/apps/{}", APP_SECRET),
+            json(200, format!(r#"{{"id": 1, "patcher_secret": "{}", "secret": "{}"}}"#, PATCHER_SECRET, APP_SECRET)),
+        ),
+        (format!("{}/latest/id", versions), json(200, format!(r#"{{"id": {}}}"#, VERSION))),
+        (format!("{}/{}", versions, VERSION), json(200, r#"{"changelog": "Fixes"}"#.into())),
+        (
+            format!("{}/{}/content_urls", versions, VERSION),
+            json(200, format!(r#"[{{"size": {}, "url": "{}/content/launcher.zip"}}]"#, package.len(), url)),
+        ),
+        ("/content/launcher.zip".to_string(), (200, package)),
+    ])
+}
+
+fn fixture_package() -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("patcher.manifest", FileOptions::default()).unwrap();
+    zip.write_all(MANIFEST.as_bytes()).unwrap();
+    zip.start_file("launch.sh", FileOptions::default()).unwrap();
+    zip.write_all(LAUNCH_SCRIPT.as_bytes()).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+/// A dat in the JSON format, with analytics off so nothing is sent anywhere else.
+fn write_dat(path: &Path) {
+    let json = format!(
+        r#"{{"patcher_secret": "{}", "app_secret": "{}", "disable_analytics": true}}"#,
+        PATCHER_SECRET, APP_SECRET
+    );
+    let mut dat = b".bLa".to_vec();
+    dat.extend_from_slice(&((json.len() * 2) as u32).to_le_bytes());
+    for byte in json.bytes() {
+        // Each byte is rotated left, inverted and followed by a zero
+        dat.push(!byte.rotate_left(1));
+        dat.push(0);
+    }
+    fs::write(path, dat).unwrap();
+}
+
+#[tokio::test]
+async fn test_update_and_launch() {
+    let server = FakeServer::start(|url| api_routes(url, fixture_package()));
+    std::env::set_var("PK_RUNNER_API_URL", &server.url);
+    std::env::set_var("PK_RUNNER_NETWORK_TEST_URL", format!("{}/network-test", server.url));
+
+    let dir = tempdir().unwrap();
+    let dat_path = dir.path().join("launcher.dat");
+    write_dat(&dat_path);
+    let install_dir = dir.path().join("app");
+    let patcher_dir = dir.path().join("Patcher");
+    let config = RunnerConfig {
+        settings: Settings { install_dir: Some(install_dir.clone()), ..Default::default() },
+        dat_path,
+        patcher_dir: Some(patcher_dir.clone()),
+        ..Default::default()
+    };
+
+    Runner::new(config.clone()).run(NoProgress).await.unwrap();
+
+    assert_eq!(server.requests_to("/content/launcher.zip"), 1);
+    assert!(patcher_dir.join("patcher.manifest").exists());
+    assert_eq!(
+        fs::read_to_string(patcher_dir.join("version.txt")).unwrap(),
+        format!("{}:{}", PATCHER_SECRET, VERSION)
+    );
+    let launched = fs::read_to_string(patcher_dir.join("launched.txt")).unwrap();
+    let arguments: Vec<&str> = launched.lines().collect();
+    assert_eq!(arguments[..2], ["--installdir", install_dir.to_str().unwrap()]);
+    assert_eq!(arguments[2], "--secret");
+
+    // The package and the checkpoint are cleaned up once the run finished
+    let leftovers: Vec<_> = fs::read_dir(&patcher_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".zip.part") || name == "runner-state.json")
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+    // The current version is launched without downloading it again
+    fs::remove_file(patcher_dir.join("launched.txt")).unwrap();
+    Runner::new(config).run(NoProgress).await.unwrap();
+    assert_eq!(server.requests_to("/content/launcher.zip"), 1);
+    assert!(patcher_dir.join("launched.txt").exists());
+}